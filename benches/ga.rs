@@ -0,0 +1,103 @@
+// Timing harness for the core GA operations. Run with `cargo bench`, or
+// `cargo bench -- step` to only run benchmarks whose name contains "step".
+// Everything is seeded, so numbers are comparable between builds.
+
+use ga_image::ga::chromosome::Chromosome;
+use ga_image::ga::color::ColorSpace;
+use ga_image::ga::config::GaConfig;
+use ga_image::ga::fitness::FitnessMetric;
+use ga_image::ga::population::SimpleGA;
+use ga_image::ga::run::run_ga;
+use image::{Rgb, RgbImage};
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const SAMPLES: usize = 15;
+const SAMPLE_TIME: Duration = Duration::from_millis(100);
+
+// Prints the median time per call over `SAMPLES` samples, each sized to run
+// for about `SAMPLE_TIME`.
+fn bench(filter: Option<&str>, name: &str, mut f: impl FnMut()) {
+    if filter.is_some_and(|filter| !name.contains(filter)) {
+        return;
+    }
+    let mut iters = 1u32;
+    loop {
+        let start = Instant::now();
+        (0..iters).for_each(|_| f());
+        if start.elapsed() >= SAMPLE_TIME / 10 || iters >= 1 << 24 {
+            let per_iter = start.elapsed() / iters;
+            iters = (SAMPLE_TIME.as_nanos() / per_iter.as_nanos().max(1)).clamp(1, u32::MAX as u128) as u32;
+            break;
+        }
+        iters *= 2;
+    }
+
+    let mut samples: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            (0..iters).for_each(|_| f());
+            start.elapsed() / iters
+        })
+        .collect();
+    samples.sort();
+    println!("{:<28} {:>12.3?} / iter ({} x {} iters)", name, samples[SAMPLES / 2], SAMPLES, iters);
+}
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    let filter = filter.as_deref();
+
+    let pixel_target = RgbImage::from_pixel(1, 1, Rgb([200, 120, 40]));
+    let config = GaConfig { seed: Some(1), ..GaConfig::default() };
+    let mut ga = SimpleGA::new((0, 0), &config, &pixel_target, ColorSpace::Rgb);
+    let mut generation = 0;
+    bench(filter, "simple_ga_step", || {
+        ga.step(black_box(&pixel_target), &config, generation);
+        generation += 1;
+    });
+
+    let mut rng = ChaCha12Rng::seed_from_u64(1);
+    let a = Chromosome::new((0, 0), ColorSpace::Rgb, &mut rng);
+    let b = Chromosome::new((0, 0), ColorSpace::Rgb, &mut rng);
+    bench(filter, "chromosome_get_fitness", || {
+        black_box(black_box(&a).get_fitness(&pixel_target, &FitnessMetric::RgbRmse));
+    });
+    bench(filter, "uniform_crossover", || {
+        black_box(black_box(&a).uniform_crossover(&b, 0.8, &mut rng));
+    });
+
+    let grid_target = RgbImage::from_fn(20, 20, |x, y| Rgb([x as u8 * 12, y as u8 * 12, 128]));
+    // Scores every chromosome of every cell once, as one generation does,
+    // one call per chromosome or one batch per cell.
+    for block_size in [1, 4] {
+        let config = GaConfig { width: 20, height: 20, block_size, seed: Some(1), ..GaConfig::default() };
+        let grid: Vec<SimpleGA> = (0..20)
+            .step_by(block_size)
+            .flat_map(|i| (0..20).step_by(block_size).map(move |j| (i, j)))
+            .map(|pos| SimpleGA::new(pos, &config, &grid_target, ColorSpace::Rgb))
+            .collect();
+        bench(filter, &format!("score_grid_per_call_block{}", block_size), || {
+            for ga in &grid {
+                ga.population.pool.iter().for_each(|chr| {
+                    black_box(ga.score(chr, black_box(&grid_target)));
+                });
+            }
+        });
+        let mut fitness = Vec::new();
+        bench(filter, &format!("score_grid_batched_block{}", block_size), || {
+            for ga in &grid {
+                fitness.clear();
+                ga.score_batch(&ga.population.pool, black_box(&grid_target), &mut fitness);
+                black_box(&fitness);
+            }
+        });
+    }
+
+    let grid_config = GaConfig { width: 20, height: 20, iterations: 5, seed: Some(1), ..GaConfig::default() };
+    bench(filter, "grid_20x20_5_generations", || {
+        black_box(run_ga(&grid_target, &grid_config, |_, _, _| {}).unwrap());
+    });
+}
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "native")]
+use std::error::Error;
+#[cfg(feature = "native")]
+use std::fs::File;
+#[cfg(feature = "native")]
+use std::io::{BufReader, BufWriter};
+
+use super::population::SimpleGA;
+
+/// A snapshot of the grid after `generation` completed generations. Each
+/// cell keeps its RNG state, so resuming continues the same sequence.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub generation: usize,
+    pub grid: Vec<Vec<SimpleGA>>,
+    pub hsv_grid: Option<Vec<Vec<SimpleGA>>>,
+    pub fitness_evaluations: u64,
+}
+
+// Borrowed mirror of `Checkpoint`; field order must match for bincode.
+#[cfg(feature = "native")]
+#[derive(Serialize)]
+struct CheckpointRef<'a> {
+    generation: usize,
+    grid: &'a [Vec<SimpleGA>],
+    hsv_grid: Option<&'a [Vec<SimpleGA>]>,
+    fitness_evaluations: u64,
+}
+
+#[cfg(feature = "native")]
+pub fn save_checkpoint(
+    grid: &[Vec<SimpleGA>],
+    hsv_grid: Option<&[Vec<SimpleGA>]>,
+    generation: usize,
+    fitness_evaluations: u64,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    // Write to a temporary file first so an interrupted save keeps the old checkpoint.
+    let tmp_path = format!("{}.tmp", path);
+    let writer = BufWriter::new(File::create(&tmp_path)?);
+    bincode::serialize_into(writer, &CheckpointRef { generation, grid, hsv_grid, fitness_evaluations })?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(feature = "native")]
+pub fn load_checkpoint(path: &str) -> Result<Checkpoint, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(bincode::deserialize_from(reader)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ga::color::ColorSpace;
+    use crate::ga::config::GaConfig;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn restored_grid_continues_the_same_run() {
+        let config = GaConfig { seed: Some(7), ..GaConfig::default() };
+        let target = RgbImage::from_pixel(2, 1, Rgb([200, 40, 90]));
+        let mut grid = vec![(0..2).map(|j| SimpleGA::new((0, j), &config, &target, ColorSpace::Rgb)).collect::<Vec<_>>()];
+        for gen in 0..3 {
+            grid.iter_mut().flatten().for_each(|ga| ga.step(&target, &config, gen));
+        }
+
+        let path = std::env::temp_dir().join(format!("ga-checkpoint-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        save_checkpoint(&grid, None, 3, 0, path).unwrap();
+        let mut restored = load_checkpoint(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(restored.generation, 3);
+
+        for gen in 3..6 {
+            grid.iter_mut().flatten().for_each(|ga| ga.step(&target, &config, gen));
+            restored.grid.iter_mut().flatten().for_each(|ga| ga.step(&target, &config, gen));
+        }
+        for (a, b) in grid.iter().flatten().zip(restored.grid.iter().flatten()) {
+            assert_eq!(a.population.pool, b.population.pool);
+        }
+    }
+}
@@ -0,0 +1,1059 @@
+use image::{ImageBuffer, Rgb};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::sync::Arc;
+
+use super::color::{ColorMode, ColorSpace};
+use super::config::GaConfig;
+use super::fitness::{Fitness, FitnessCurve, FitnessFunction};
+use super::image::nearest_palette_index;
+use super::population::Genome;
+use super::{DEEP_GENE_LENGTH, GENE_LENGTH};
+
+/// `Creep` decodes each channel, adds Gaussian noise with standard deviation
+/// `sigma` and re-encodes the clamped value, so it makes small nudges where a
+/// bit flip can swing a channel by half its range.
+#[derive(Clone, Copy, Debug)]
+pub enum MutationOperator {
+    BitFlip,
+    FixedFlips { n: usize },
+    Creep { sigma: f64 },
+}
+
+/// How each channel's bits map to its 8-bit value. With `GrayCode`,
+/// adjacent values differ in exactly one bit.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GeneEncoding {
+    Binary,
+    GrayCode,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrossoverKind {
+    Uniform,
+    SinglePoint,
+    TwoPoint,
+}
+
+pub fn color_rmse(val: [u8; 3], target_pixel: &Rgb<u8>) -> Fitness {
+    let mut diff_sum = 0.0;
+    for i in 0..3 {
+        let diff = val[i] as Fitness - target_pixel[i] as Fitness;
+        diff_sum += diff * diff;
+    }
+    
+    (diff_sum / 3.0).sqrt()
+}
+
+/// RMSE of two 16-bit colors, in 8-bit units so it maps through
+/// `fitness_from_rmse` like `color_rmse`.
+pub fn deep_color_rmse(val: [u16; 3], target: [u16; 3]) -> Fitness {
+    let diff_sum: Fitness = (0..3).map(|i| (val[i] as Fitness - target[i] as Fitness).powi(2)).sum();
+    (diff_sum / 3.0).sqrt() / 257.0
+}
+
+/// Score of an RGB RMSE under the default `FitnessCurve`.
+pub fn fitness_from_rmse(rmse: Fitness) -> Fitness {
+    FitnessCurve::default().score(rmse)
+}
+
+pub fn to_gray(val: u8) -> u8 {
+    val ^ (val >> 1)
+}
+
+pub fn from_gray(gray: u8) -> u8 {
+    from_gray_wide(gray as u16) as u8
+}
+
+fn from_gray_wide(gray: u16) -> u16 {
+    let mut val = gray;
+    let mut shift = gray >> 1;
+    while shift != 0 {
+        val ^= shift;
+        shift >>= 1;
+    }
+    val
+}
+
+/// One channel's gene bits (up to 16), packed into an integer with the first
+/// bit most significant, so the bits read as the channel's binary value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Channel {
+    bits: u16,
+    len: u8,
+}
+
+impl Channel {
+    pub fn new(bits: u16, len: usize) -> Self {
+        debug_assert!(len <= 16);
+        Channel { bits: bits & low_bits(len), len: len as u8 }
+    }
+
+    pub fn len(self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.len == 0
+    }
+
+    pub fn bits(self) -> u16 {
+        self.bits
+    }
+
+    // Mask of bit `i`, counting from the first (most significant) bit.
+    fn mask(self, i: usize) -> u16 {
+        1 << (self.len() - 1 - i)
+    }
+
+    pub fn get(self, i: usize) -> bool {
+        self.bits & self.mask(i) != 0
+    }
+
+    pub fn flip(&mut self, i: usize) {
+        self.bits ^= self.mask(i);
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = bool> {
+        (0..self.len()).map(move |i| self.get(i))
+    }
+
+    // Takes the bits set in `mask` from `other`.
+    fn splice(&mut self, other: Channel, mask: u16) {
+        self.bits = (self.bits & !mask) | (other.bits & mask);
+    }
+}
+
+impl FromIterator<bool> for Channel {
+    fn from_iter<I: IntoIterator<Item = bool>>(bits: I) -> Self {
+        bits.into_iter().fold(Channel::default(), |channel, bit| Channel { bits: channel.bits << 1 | bit as u16, len: channel.len + 1 })
+    }
+}
+
+// The lowest `len` bits set.
+fn low_bits(len: usize) -> u16 {
+    ((1u32 << len) - 1) as u16
+}
+
+fn encode_channel(val: u8, encoding: GeneEncoding) -> Channel {
+    encode_bits(val.into(), GENE_LENGTH, encoding)
+}
+
+fn encode_bits(val: u16, len: usize, encoding: GeneEncoding) -> Channel {
+    let bits = match encoding {
+        GeneEncoding::Binary => val,
+        GeneEncoding::GrayCode => val ^ (val >> 1),
+    };
+    Channel::new(bits, len)
+}
+
+// Channel bit lengths and color mode of the gene `config` builds for a
+// cell of `pixels` pixels in `space`; palette and deep genes are always RGB.
+fn layout(config: &GaConfig, pixels: usize, space: ColorSpace) -> (Vec<usize>, ColorMode) {
+    let (bits, channels, space) = match (&config.palette, config.deep_color) {
+        (Some(palette), _) => (palette_index_bits(palette.len()), 1, ColorSpace::Rgb),
+        (None, true) => (DEEP_GENE_LENGTH, ColorSpace::Rgb.channels(), ColorSpace::Rgb),
+        (None, false) => (GENE_LENGTH, space.channels(), space),
+    };
+    let mut lengths = vec![bits; channels * pixels];
+    if config.alpha {
+        lengths.push(GENE_LENGTH);
+    }
+    (lengths, ColorMode { space, alpha: config.alpha })
+}
+
+/// Bits needed to index a palette of `size` colors (at least 1).
+pub fn palette_index_bits(size: usize) -> usize {
+    (usize::BITS - size.saturating_sub(1).leading_zeros()).max(1) as usize
+}
+
+// Full value of a channel of up to 16 bits.
+fn decode_wide(channel: Channel, encoding: GeneEncoding) -> u16 {
+    match encoding {
+        GeneEncoding::Binary => channel.bits(),
+        GeneEncoding::GrayCode => from_gray_wide(channel.bits()),
+    }
+}
+
+// 16-bit channels decode to their top byte.
+fn decode_channel(channel: Channel, encoding: GeneEncoding) -> u8 {
+    (decode_wide(channel, encoding) >> channel.len().saturating_sub(GENE_LENGTH)) as u8
+}
+
+/// Standard normal sample (Box-Muller).
+fn standard_normal(rng: &mut impl Rng) -> f64 {
+    let u1 = 1.0 - rng.gen::<f64>();
+    let u2 = rng.gen::<f64>();
+    (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
+
+pub fn normalized_fitness_from_rmse(rmse: Fitness) -> Fitness {
+    let max_error = ((1u32 << GENE_LENGTH) - 1) as Fitness;
+    1.0 - rmse / max_error
+}
+
+/// `gene` holds `space.channels()` channels (one index in palette mode) per
+/// pixel, plus a trailing alpha channel in RGBA mode (see `ColorMode`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Chromosome {
+    pub pos: (usize, usize),
+    pub gene: Vec<Channel>,
+    pub space: ColorSpace,
+    pub encoding: GeneEncoding,
+    /// In palette mode each channel is an index into these colors; indices
+    /// past the end clamp to the last entry.
+    pub palette: Option<Arc<Vec<[u8; 3]>>>,
+    // Decoded `get_val` result; `None` marks the gene dirty.
+    #[serde(skip)]
+    decoded: Cell<Option<[u8; 3]>>,
+}
+
+impl Clone for Chromosome {
+    fn clone(&self) -> Self {
+        Chromosome {
+            pos: self.pos,
+            gene: self.gene.clone(),
+            space: self.space,
+            encoding: self.encoding,
+            palette: self.palette.clone(),
+            decoded: self.decoded.clone(),
+        }
+    }
+
+    // Reuses the gene's buffers, so recycled pool slots do not reallocate.
+    fn clone_from(&mut self, source: &Self) {
+        self.pos = source.pos;
+        self.gene.clone_from(&source.gene);
+        self.space = source.space;
+        self.encoding = source.encoding;
+        self.palette.clone_from(&source.palette);
+        self.decoded.set(source.decoded.get());
+    }
+}
+
+impl PartialEq for Chromosome {
+    fn eq(&self, other: &Self) -> bool {
+        self.pos == other.pos && self.gene == other.gene && self.space == other.space && self.encoding == other.encoding
+    }
+}
+
+impl Chromosome {
+    pub fn new(pos: (usize, usize), space: ColorSpace, rng: &mut impl Rng) -> Self {
+        Chromosome::random_block(pos, 1, space, rng)
+    }
+
+    /// Random chromosome covering `pixels` pixels of a block, in row-major order.
+    pub fn random_block(pos: (usize, usize), pixels: usize, space: ColorSpace, rng: &mut impl Rng) -> Self {
+        let gene = (0..space.channels() * pixels)
+            .map(|_| {
+                (0..GENE_LENGTH)
+                    .map(|_| rng.gen_bool(0.5))
+                    .collect()
+            })
+            .collect();
+
+        Chromosome { pos, gene, space, encoding: GeneEncoding::Binary, palette: None, decoded: Cell::new(None) }
+    }
+
+    /// Random palette-mode chromosome covering `pixels` pixels.
+    pub fn random_palette(pos: (usize, usize), pixels: usize, palette: Arc<Vec<[u8; 3]>>, rng: &mut impl Rng) -> Self {
+        let bits = palette_index_bits(palette.len());
+        let gene = (0..pixels).map(|_| (0..bits).map(|_| rng.gen_bool(0.5)).collect()).collect();
+
+        Chromosome { pos, gene, space: ColorSpace::Rgb, encoding: GeneEncoding::Binary, palette: Some(palette), decoded: Cell::new(None) }
+    }
+
+    /// Palette-mode chromosome holding the palette entry nearest each color.
+    pub fn from_palette_colors(pos: (usize, usize), colors: &[[u8; 3]], palette: Arc<Vec<[u8; 3]>>) -> Self {
+        let bits = palette_index_bits(palette.len());
+        let gene = colors
+            .iter()
+            .map(|&color| encode_bits(nearest_palette_index(&palette, color) as u16, bits, GeneEncoding::Binary))
+            .collect();
+
+        Chromosome { pos, gene, space: ColorSpace::Rgb, encoding: GeneEncoding::Binary, palette: Some(palette), decoded: Cell::new(None) }
+    }
+
+    /// Random single-pixel RGB chromosome with 16 bits per channel.
+    pub fn random_deep(pos: (usize, usize), rng: &mut impl Rng) -> Self {
+        let gene = (0..ColorSpace::Rgb.channels()).map(|_| (0..DEEP_GENE_LENGTH).map(|_| rng.gen_bool(0.5)).collect()).collect();
+
+        Chromosome { pos, gene, space: ColorSpace::Rgb, encoding: GeneEncoding::Binary, palette: None, decoded: Cell::new(None) }
+    }
+
+    pub fn from_deep_color(pos: (usize, usize), color: [u16; 3]) -> Self {
+        let gene = color.iter().map(|&val| encode_bits(val, DEEP_GENE_LENGTH, GeneEncoding::Binary)).collect();
+
+        Chromosome { pos, gene, space: ColorSpace::Rgb, encoding: GeneEncoding::Binary, palette: None, decoded: Cell::new(None) }
+    }
+
+    /// A random chromosome with the same shape, color space, encoding and
+    /// palette as `self`.
+    pub fn random_like(&self, rng: &mut impl Rng) -> Self {
+        let chr = match &self.palette {
+            Some(palette) => Chromosome::random_palette(self.pos, self.pixel_count(), Arc::clone(palette), rng),
+            None if self.is_deep() => Chromosome::random_deep(self.pos, rng),
+            None => Chromosome::random_block(self.pos, self.pixel_count(), self.space, rng),
+        };
+        let mut chr = chr.with_encoding(self.encoding);
+        if self.has_alpha() {
+            chr.push_alpha(rng.gen());
+        }
+        chr
+    }
+
+    pub fn from_color(pos: (usize, usize), color: [u8; 3], space: ColorSpace) -> Self {
+        Chromosome::from_colors(pos, &[color], space)
+    }
+
+    /// Single-pixel chromosome encoding `rgba` with `mode`'s channel layout.
+    pub fn from_rgba(pos: (usize, usize), rgba: [u8; 4], mode: ColorMode) -> Self {
+        let gene = mode.encode(rgba).into_iter().map(|val| encode_channel(val, GeneEncoding::Binary)).collect();
+
+        Chromosome { pos, gene, space: mode.space, encoding: GeneEncoding::Binary, palette: None, decoded: Cell::new(None) }
+    }
+
+    /// Block chromosome with one color per pixel, in row-major order.
+    pub fn from_colors(pos: (usize, usize), colors: &[[u8; 3]], space: ColorSpace) -> Self {
+        let gene = colors
+            .iter()
+            .flat_map(|&color| space.encode(color))
+            .map(|val| encode_channel(val, GeneEncoding::Binary))
+            .collect();
+
+        Chromosome { pos, gene, space, encoding: GeneEncoding::Binary, palette: None, decoded: Cell::new(None) }
+    }
+
+    /// Re-encodes the gene so it decodes to the same values under `encoding`.
+    pub fn with_encoding(mut self, encoding: GeneEncoding) -> Self {
+        for channel in &mut self.gene {
+            let val = decode_wide(*channel, self.encoding);
+            *channel = encode_bits(val, channel.len(), encoding);
+        }
+        self.encoding = encoding;
+        self
+    }
+
+    /// Appends an alpha channel, switching the chromosome to RGBA.
+    pub fn push_alpha(&mut self, alpha: u8) {
+        self.gene.truncate(self.channels_per_pixel());
+        self.gene.push(encode_channel(alpha, self.encoding));
+    }
+
+    /// Whether channels hold 16 bits; `get_val` then returns their top byte.
+    pub fn is_deep(&self) -> bool {
+        self.gene[0].len() == DEEP_GENE_LENGTH
+    }
+
+    fn channels_per_pixel(&self) -> usize {
+        match self.palette {
+            Some(_) => 1,
+            None => self.space.channels(),
+        }
+    }
+
+    // Block chromosomes never carry alpha, so only an alpha channel leaves
+    // a remainder.
+    pub fn has_alpha(&self) -> bool {
+        !self.gene.len().is_multiple_of(self.channels_per_pixel())
+    }
+
+    /// The gene's color space, plus alpha in RGBA mode.
+    pub fn color_mode(&self) -> ColorMode {
+        ColorMode { space: self.space, alpha: self.has_alpha() }
+    }
+
+    /// Number of gene bits that differ from `other`'s.
+    pub fn hamming_distance(&self, other: &Chromosome) -> usize {
+        self.gene
+            .iter()
+            .zip(&other.gene)
+            .map(|(a, b)| (a.bits() ^ b.bits()).count_ones() as usize)
+            .sum()
+    }
+
+    /// Number of pixels the gene encodes; 1 unless it is a block chromosome.
+    pub fn pixel_count(&self) -> usize {
+        self.gene.len() / self.channels_per_pixel()
+    }
+
+    /// The gene's channel values in hex, pixel by pixel in row-major order:
+    /// `RRGGBB` for an RGB pixel, otherwise the channels of its color space
+    /// (then alpha), with four digits per 16-bit channel and as few as a
+    /// palette index needs.
+    pub fn to_hex(&self) -> String {
+        self.gene
+            .iter()
+            .map(|channel| format!("{:0width$x}", decode_wide(*channel, self.encoding), width = channel.len().div_ceil(4)))
+            .collect()
+    }
+
+    /// Parses `to_hex` output into the chromosome `config` builds for the
+    /// cell at `pos`, the top-left pixel of a block: the same color space,
+    /// alpha, palette, bit depth and gene encoding.
+    pub fn from_hex(pos: (usize, usize), hex: &str, config: &GaConfig) -> Result<Self, String> {
+        let block_size = config.block_size.max(1);
+        if pos.0 >= config.height || pos.1 >= config.width || !pos.0.is_multiple_of(block_size) || !pos.1.is_multiple_of(block_size) {
+            return Err(format!(
+                "position {:?} is not the corner of a cell in the {}x{} grid of {}x{} blocks",
+                pos,
+                config.width.div_ceil(block_size),
+                config.height.div_ceil(block_size),
+                block_size,
+                block_size
+            ));
+        }
+
+        let pixels = block_size.min(config.height - pos.0) * block_size.min(config.width - pos.1);
+        let (lengths, mode) = layout(config, pixels, config.color_space());
+        let digits: usize = lengths.iter().map(|len| len.div_ceil(4)).sum();
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if hex.len() != digits || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("invalid chromosome hex '{}', expected {} hex digits", hex, digits));
+        }
+
+        let mut start = 0;
+        let gene = lengths
+            .iter()
+            .map(|&len| {
+                let end = start + len.div_ceil(4);
+                let val = u16::from_str_radix(&hex[start..end], 16).map_err(|e| e.to_string())?;
+                if u32::from(val) >> len != 0 {
+                    return Err(format!("chromosome hex '{}' holds {:#x}, which does not fit in {} bits", hex, val, len));
+                }
+                start = end;
+                Ok(encode_bits(val, len, config.encoding))
+            })
+            .collect::<Result<_, String>>()?;
+        let palette = config.palette.as_ref().map(|palette| Arc::new(palette.iter().map(|color| color.0).collect()));
+        Ok(Chromosome { pos, gene, space: mode.space, encoding: config.encoding, palette, decoded: Cell::new(None) })
+    }
+
+    /// Whether the gene has the layout `config` builds for a cell of
+    /// `pixels` pixels in `space`: the same palette size, channel bit
+    /// lengths, color mode and encoding.
+    pub fn has_layout(&self, config: &GaConfig, pixels: usize, space: ColorSpace) -> bool {
+        let (lengths, mode) = layout(config, pixels, space);
+        self.palette.as_ref().map(|palette| palette.len()) == config.palette.as_ref().map(Vec::len)
+            && self.gene.iter().map(|channel| channel.len()).eq(lengths)
+            && self.color_mode() == mode
+            && self.encoding == config.encoding
+    }
+
+    /// Child whose colors are the per-channel rounded means of the parents'
+    /// decoded RGB colors, re-encoded in the first parent's color space.
+    pub fn centroid(parents: &[Chromosome]) -> Chromosome {
+        let mut sums = vec![[0u32; 3]; parents[0].pixel_count()];
+        for parent in parents {
+            for (pixel, color) in sums.iter_mut().zip(parent.get_block_vals()) {
+                for (sum, val) in pixel.iter_mut().zip(color) {
+                    *sum += val as u32;
+                }
+            }
+        }
+
+        let n = parents.len() as f64;
+        if parents[0].is_deep() {
+            let sums = parents.iter().fold([0.0; 3], |sums, parent| {
+                let val = parent.get_deep_val();
+                [0, 1, 2].map(|c| sums[c] + val[c] as f64)
+            });
+            return Chromosome::from_deep_color(parents[0].pos, sums.map(|sum| (sum / n).round() as u16))
+                .with_encoding(parents[0].encoding);
+        }
+        let colors: Vec<[u8; 3]> = sums.iter().map(|pixel| pixel.map(|sum| (sum as f64 / n).round() as u8)).collect();
+        if let Some(palette) = &parents[0].palette {
+            return Chromosome::from_palette_colors(parents[0].pos, &colors, Arc::clone(palette)).with_encoding(parents[0].encoding);
+        }
+        let mut child = Chromosome::from_colors(parents[0].pos, &colors, parents[0].space).with_encoding(parents[0].encoding);
+        if parents[0].has_alpha() {
+            let alpha = parents.iter().map(|parent| parent.get_alpha() as f64).sum::<f64>() / n;
+            child.push_alpha(alpha.round() as u8);
+        }
+        child
+    }
+
+    /// Must be called after editing `gene` directly so `get_val` re-decodes it.
+    pub fn mark_dirty(&mut self) {
+        self.decoded.set(None);
+    }
+
+    pub fn flip_bits(&mut self, rate: f64, rng: &mut impl Rng) {
+        self.flip_channel_bits(|_| rate, rng);
+    }
+
+    fn flip_channel_bits(&mut self, rate: impl Fn(usize) -> f64, rng: &mut impl Rng) {
+        self.mark_dirty();
+        for (c, channel) in self.gene.iter_mut().enumerate() {
+            let rate = rate(c);
+            for i in 0..channel.len() {
+                if rng.gen::<f64>() < rate {
+                    channel.flip(i);
+                }
+            }
+        }
+    }
+
+    // The rate in `rates` of each gene channel by index (red, green and blue,
+    // or hue, saturation and value, of every pixel); an alpha channel uses `alpha`.
+    fn channel_rates(&self, rates: [f64; 3], alpha: f64) -> impl Fn(usize) -> f64 {
+        let alpha_channel = self.has_alpha().then(|| self.gene.len() - 1);
+        let channels = self.space.channels();
+        move |c| if Some(c) == alpha_channel { alpha } else { rates[c % channels] }
+    }
+
+    /// `rate` is the per-bit flip probability for `BitFlip`, normally
+    /// `config.mutation_rate_at(generation)`; `config.channel_mutation_rates`
+    /// overrides it for the color channels.
+    pub fn mutate(&mut self, config: &GaConfig, rate: f64, rng: &mut impl Rng) {
+        self.mark_dirty();
+        match config.mutation {
+            MutationOperator::BitFlip => {
+                match config.channel_mutation_rates {
+                    Some(rates) => self.flip_channel_bits(self.channel_rates(rates, rate), rng),
+                    None => self.flip_bits(rate, rng),
+                }
+                
+                if rng.gen::<f64>() < config.extra_flip_rate {
+                    let channel_idx = rng.gen_range(0..self.gene.len());
+                    let bit_idx = rng.gen_range(0..self.gene[channel_idx].len());
+                    self.gene[channel_idx].flip(bit_idx);
+                }
+            }
+            MutationOperator::FixedFlips { n } => {
+                // All channels of a chromosome have the same length.
+                let bits = self.gene[0].len();
+                let total_bits = self.gene.len() * bits;
+                for idx in rand::seq::index::sample(rng, total_bits, n.min(total_bits)) {
+                    self.gene[idx / bits].flip(idx % bits);
+                }
+            }
+            MutationOperator::Creep { sigma } => {
+                for channel in &mut self.gene {
+                    let max = ((1u32 << channel.len()) - 1) as f64;
+                    // Sigma is in 8-bit steps, so 16-bit channels take proportionally larger ones.
+                    let scale = max / 255.0;
+                    let val = decode_wide(*channel, self.encoding) as f64 + sigma * scale.max(1.0) * standard_normal(rng);
+                    *channel = encode_bits(val.round().clamp(0.0, max) as u16, channel.len(), self.encoding);
+                }
+            }
+        }
+    }
+
+    pub fn crossover_with(&self, kind: CrossoverKind, other: &Chromosome, crossover_rate: f64, rng: &mut impl Rng) -> (Chromosome, Chromosome) {
+        match kind {
+            CrossoverKind::Uniform => self.uniform_crossover(other, crossover_rate, rng),
+            CrossoverKind::SinglePoint => self.crossover(other, crossover_rate, rng),
+            CrossoverKind::TwoPoint => self.two_point_crossover(other, crossover_rate, rng),
+        }
+    }
+
+    /// Like `crossover_with`, but draws whether to cross each channel
+    /// separately, with its color channel's rate in `rates`; an alpha channel
+    /// uses `alpha_rate`.
+    pub fn channel_crossover_with<R: Rng>(&self, kind: CrossoverKind, other: &Chromosome, rates: [f64; 3], alpha_rate: f64, rng: &mut R) -> (Chromosome, Chromosome) {
+        let rate = self.channel_rates(rates, alpha_rate);
+        self.cross_channels(kind, other, rng, |c, rng: &mut R| rng.gen::<f64>() < rate(c))
+    }
+
+    pub fn crossover(&self, other: &Chromosome, crossover_rate: f64, rng: &mut impl Rng) -> (Chromosome, Chromosome) {
+        if rng.gen::<f64>() > crossover_rate {
+            return (self.clone(), other.clone());
+        }
+        self.cross_channels(CrossoverKind::SinglePoint, other, rng, |_, _| true)
+    }
+
+    /// Per channel, swaps the bits between two distinct cut points.
+    pub fn two_point_crossover(&self, other: &Chromosome, crossover_rate: f64, rng: &mut impl Rng) -> (Chromosome, Chromosome) {
+        if rng.gen::<f64>() > crossover_rate {
+            return (self.clone(), other.clone());
+        }
+        self.cross_channels(CrossoverKind::TwoPoint, other, rng, |_, _| true)
+    }
+
+    pub fn uniform_crossover(&self, other: &Chromosome, crossover_rate: f64, rng: &mut impl Rng) -> (Chromosome, Chromosome) {
+        if rng.gen::<f64>() > crossover_rate {
+            return (self.clone(), other.clone());
+        }
+        self.cross_channels(CrossoverKind::Uniform, other, rng, |_, _| true)
+    }
+
+    // Swaps bits between the parents in every channel `cross` picks: the tail
+    // after one cut point, the segment between two, or each bit with
+    // probability 1/2.
+    fn cross_channels<R: Rng>(&self, kind: CrossoverKind, other: &Chromosome, rng: &mut R, mut cross: impl FnMut(usize, &mut R) -> bool) -> (Chromosome, Chromosome) {
+        let mut child1 = self.clone();
+        let mut child2 = other.clone();
+        child1.mark_dirty();
+        child2.mark_dirty();
+
+        for i in 0..self.gene.len() {
+            if !cross(i, rng) {
+                continue;
+            }
+            let channel = self.gene[i];
+            let len = channel.len();
+            let mask = match kind {
+                CrossoverKind::SinglePoint => {
+                    // A 1-bit channel has no interior cut point and is left alone.
+                    let crossover_point = rng.gen_range(1..len.max(2));
+                    low_bits(len.saturating_sub(crossover_point))
+                }
+                CrossoverKind::TwoPoint => {
+                    let mut cuts = rand::seq::index::sample(rng, len + 1, 2).into_vec();
+                    cuts.sort_unstable();
+                    low_bits(cuts[1] - cuts[0]) << (len - cuts[1])
+                }
+                CrossoverKind::Uniform => (0..len).filter(|_| rng.gen_bool(0.5)).fold(0, |mask, j| mask | channel.mask(j)),
+            };
+            child1.gene[i].splice(other.gene[i], mask);
+            child2.gene[i].splice(self.gene[i], mask);
+        }
+
+        (child1, child2)
+    }
+
+    fn palette_color(&self, palette: &[[u8; 3]], channel: Channel) -> [u8; 3] {
+        let index = decode_channel(channel, self.encoding) as usize;
+        palette[index.min(palette.len() - 1)]
+    }
+
+    pub fn get_raw_val(&self) -> [u8; 3] {
+        if let Some(palette) = &self.palette {
+            return self.palette_color(palette, self.gene[0]);
+        }
+        if self.space == ColorSpace::Gray {
+            return [decode_channel(self.gene[0], self.encoding); 3];
+        }
+        self.raw_pixel(&self.gene)
+    }
+
+    // Decoded values of the color channels at the start of `channels`.
+    fn raw_pixel(&self, channels: &[Channel]) -> [u8; 3] {
+        let mut raw = [0u8; 3];
+        for (val, channel) in raw.iter_mut().zip(&channels[..self.space.channels()]) {
+            *val = decode_channel(*channel, self.encoding);
+        }
+        raw
+    }
+
+    /// Fully opaque when the chromosome has no alpha channel.
+    pub fn get_alpha(&self) -> u8 {
+        if self.has_alpha() {
+            decode_channel(self.gene[self.channels_per_pixel()], self.encoding)
+        } else {
+            255
+        }
+    }
+
+    pub fn get_rgba(&self) -> [u8; 4] {
+        let [r, g, b] = self.get_val();
+        [r, g, b, self.get_alpha()]
+    }
+
+    pub fn get_val(&self) -> [u8; 3] {
+        if let Some(val) = self.decoded.get() {
+            return val;
+        }
+
+        let val = match &self.palette {
+            Some(_) => self.get_raw_val(),
+            None => self.space.decode(&self.raw_pixel(&self.gene)),
+        };
+        self.decoded.set(Some(val));
+        val
+    }
+
+    /// Decoded RGB color of every pixel; `[get_val()]` for a single pixel.
+    pub fn get_block_vals(&self) -> Vec<[u8; 3]> {
+        if self.pixel_count() <= 1 {
+            return vec![self.get_val()];
+        }
+        if let Some(palette) = &self.palette {
+            return self.gene.iter().map(|&channel| self.palette_color(palette, channel)).collect();
+        }
+        self.gene.chunks(self.space.channels()).map(|pixel| self.space.decode(&self.raw_pixel(pixel))).collect()
+    }
+
+    /// Full 16-bit color; 8-bit channels are scaled up by 257.
+    pub fn get_deep_val(&self) -> [u16; 3] {
+        if !self.is_deep() {
+            return self.get_val().map(|val| val as u16 * 257);
+        }
+        [0, 1, 2].map(|c| decode_wide(self.gene[c], self.encoding))
+    }
+
+    /// RGB RMSE fitness against a 16-bit target color.
+    pub fn get_deep_fitness(&self, target: [u16; 3], curve: FitnessCurve) -> Fitness {
+        curve.score(deep_color_rmse(self.get_deep_val(), target))
+    }
+
+    pub fn get_rmse(&self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Fitness {
+        let target_pixel = target_image.get_pixel(self.pos.1 as u32, self.pos.0 as u32);
+        color_rmse(self.get_val(), target_pixel)
+    }
+
+    pub fn get_fitness(&self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>, fitness: &dyn FitnessFunction) -> Fitness {
+        self.get_fitness_against(*target_image.get_pixel(self.pos.1 as u32, self.pos.0 as u32), fitness)
+    }
+
+    /// `get_fitness` with the target pixel already looked up.
+    pub fn get_fitness_against(&self, target_pixel: Rgb<u8>, fitness: &dyn FitnessFunction) -> Fitness {
+        fitness.score(self.get_val(), target_pixel)
+    }
+}
+
+impl Genome for Chromosome {
+    fn crossover<R: Rng>(&self, other: &Self, config: &GaConfig, rate: f64, rng: &mut R) -> (Self, Self) {
+        match config.channel_crossover_rates {
+            Some(rates) => self.channel_crossover_with(config.crossover, other, rates, rate, rng),
+            None => self.crossover_with(config.crossover, other, rate, rng),
+        }
+    }
+
+    fn mutate<R: Rng>(&mut self, config: &GaConfig, rate: f64, rng: &mut R) {
+        Chromosome::mutate(self, config, rate, rng);
+    }
+
+    fn random_like<R: Rng>(&self, rng: &mut R) -> Self {
+        Chromosome::random_like(self, rng)
+    }
+
+    fn distance(&self, other: &Self) -> usize {
+        self.hamming_distance(other)
+    }
+
+    fn centroid(parents: &[Self]) -> Self {
+        Chromosome::centroid(parents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ga::color::{luma, EncodingSpace};
+    use rand::rngs::StdRng;
+
+    fn solid(bit: bool) -> Chromosome {
+        Chromosome {
+            pos: (0, 0),
+            gene: vec![Channel::new(if bit { u16::MAX } else { 0 }, GENE_LENGTH); ColorMode::RGB.channels()],
+            space: ColorSpace::Rgb,
+            encoding: GeneEncoding::Binary,
+            palette: None,
+            decoded: Cell::new(None),
+        }
+    }
+
+    const KINDS: [CrossoverKind; 3] = [CrossoverKind::Uniform, CrossoverKind::SinglePoint, CrossoverKind::TwoPoint];
+
+    #[test]
+    fn zero_crossover_rate_returns_parents() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let a = Chromosome::new((0, 0), ColorSpace::Rgb, &mut rng);
+        let b = Chromosome::new((0, 0), ColorSpace::Rgb, &mut rng);
+
+        for kind in KINDS {
+            for _ in 0..100 {
+                let (child1, child2) = a.crossover_with(kind, &b, 0.0, &mut rng);
+                assert_eq!(child1, a);
+                assert_eq!(child2, b);
+            }
+        }
+    }
+
+    #[test]
+    fn crossover_only_exchanges_bits_between_parents() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let a = Chromosome::new((0, 0), ColorSpace::Rgb, &mut rng);
+        let b = Chromosome::new((0, 0), ColorSpace::Rgb, &mut rng);
+
+        for kind in KINDS {
+            for _ in 0..100 {
+                let (child1, child2) = a.crossover_with(kind, &b, 1.0, &mut rng);
+                for i in 0..ColorMode::RGB.channels() {
+                    for j in 0..GENE_LENGTH {
+                        let (x, y) = (child1.gene[i].get(j), child2.gene[i].get(j));
+                        let (p, q) = (a.gene[i].get(j), b.gene[i].get(j));
+                        assert!((x, y) == (p, q) || (x, y) == (q, p));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_point_crossover_swaps_only_the_tail() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let (zeros, ones) = (solid(false), solid(true));
+
+        for _ in 0..100 {
+            let (child1, child2) = zeros.crossover(&ones, 1.0, &mut rng);
+            for (c1, c2) in child1.gene.iter().zip(&child2.gene) {
+                let (c1, c2): (Vec<bool>, Vec<bool>) = (c1.iter().collect(), c2.iter().collect());
+                let point = c1.iter().position(|&bit| bit).expect("tail must come from the other parent");
+                assert!((1..GENE_LENGTH).contains(&point));
+                assert!(c1[..point].iter().all(|&bit| !bit));
+                assert!(c1[point..].iter().all(|&bit| bit));
+                assert!(c2.iter().zip(&c1).all(|(x, y)| x != y));
+            }
+        }
+    }
+
+    #[test]
+    fn two_point_crossover_swaps_one_contiguous_segment() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let (zeros, ones) = (solid(false), solid(true));
+
+        for _ in 0..100 {
+            let (child, _) = zeros.two_point_crossover(&ones, 1.0, &mut rng);
+            for channel in &child.gene {
+                let channel: Vec<bool> = channel.iter().collect();
+                let start = channel.iter().position(|&bit| bit).expect("segment must be non-empty");
+                let len = channel[start..].iter().take_while(|&&bit| bit).count();
+                assert!(channel[start + len..].iter().all(|&bit| !bit));
+            }
+        }
+    }
+
+    #[test]
+    fn decoded_value_follows_gene_changes() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut chr = solid(false);
+        assert_eq!(chr.get_val(), [0, 0, 0]);
+
+        chr.flip_bits(1.0, &mut rng);
+        assert_eq!(chr.get_val(), [255, 255, 255]);
+
+        chr.gene[0] = Channel::new(0, GENE_LENGTH);
+        chr.mark_dirty();
+        assert_eq!(chr.get_val(), [0, 255, 255]);
+
+        let (child, _) = chr.crossover(&solid(false), 1.0, &mut rng);
+        assert_eq!(child.get_val(), child.get_raw_val());
+    }
+
+    #[test]
+    fn get_val_decodes_most_significant_bit_first() {
+        assert_eq!(solid(false).get_val(), [0, 0, 0]);
+        assert_eq!(solid(true).get_val(), [255, 255, 255]);
+
+        let bits = |pattern: &str| pattern.chars().map(|c| c == '1').collect::<Channel>();
+        let mut chr = solid(false);
+        chr.gene = vec![bits("10000000"), bits("00000001"), bits("01010101")];
+        chr.mark_dirty();
+        assert_eq!(chr.get_val(), [128, 1, 85]);
+
+        for val in 0..=255u8 {
+            let color = [val, 255 - val, val.rotate_left(3)];
+            assert_eq!(Chromosome::from_color((0, 0), color, ColorSpace::Rgb).get_val(), color);
+        }
+    }
+
+    #[test]
+    fn channel_bits_read_most_significant_first() {
+        let mut channel: Channel = [true, false, true, true].into_iter().collect();
+        assert_eq!((channel.bits(), channel.len()), (0b1011, 4));
+        channel.flip(0);
+        assert!(!channel.get(0) && channel.get(3));
+        assert_eq!(channel.iter().collect::<Vec<_>>(), [false, false, true, true]);
+        assert_eq!(Channel::new(0xFFFF, 3).bits(), 0b111);
+    }
+
+    #[test]
+    fn gray_code_round_trips_and_steps_by_one_bit() {
+        for val in 0..=255u8 {
+            assert_eq!(from_gray(to_gray(val)), val);
+            if val < 255 {
+                assert_eq!((to_gray(val) ^ to_gray(val + 1)).count_ones(), 1);
+            }
+        }
+
+        let chr = Chromosome::from_color((0, 0), [127, 128, 200], ColorSpace::Rgb);
+        let gray = chr.clone().with_encoding(GeneEncoding::GrayCode);
+        assert_eq!(gray.get_val(), [127, 128, 200]);
+        assert_ne!(gray.gene, chr.gene);
+        assert_eq!(gray.with_encoding(GeneEncoding::Binary), chr);
+    }
+
+    #[test]
+    fn block_chromosome_decodes_every_pixel() {
+        let colors = [[10, 20, 30], [200, 100, 0], [255, 255, 255], [0, 0, 0]];
+        for space in [ColorSpace::Rgb, ColorSpace::Hsv] {
+            let block = Chromosome::from_colors((0, 0), &colors, space).with_encoding(GeneEncoding::GrayCode);
+            assert_eq!(block.pixel_count(), 4);
+            assert!(!block.has_alpha());
+            assert_eq!(block.get_alpha(), 255);
+            assert_eq!(block.get_val(), block.get_block_vals()[0]);
+            if space == ColorSpace::Rgb {
+                assert_eq!(block.get_block_vals(), colors);
+            }
+        }
+
+        let dark = Chromosome::from_colors((0, 0), &[[0, 0, 0], [100, 100, 100]], ColorSpace::Rgb);
+        let light = Chromosome::from_colors((0, 0), &[[100, 50, 0], [200, 200, 200]], ColorSpace::Rgb);
+        let centroid = Chromosome::centroid(&[dark, light]);
+        assert_eq!(centroid.get_block_vals(), [[50, 25, 0], [150, 150, 150]]);
+    }
+
+    #[test]
+    fn grayscale_chromosome_broadcasts_one_channel() {
+        let colors = [[200, 200, 200], [255, 0, 0]];
+        let gray = Chromosome::from_colors((0, 0), &colors, ColorSpace::Gray).with_encoding(GeneEncoding::GrayCode);
+        assert_eq!(gray.gene.len(), 2);
+        assert_eq!(gray.pixel_count(), 2);
+        assert!(!gray.has_alpha());
+        assert_eq!(gray.get_block_vals(), [[200, 200, 200], [54, 54, 54]]);
+
+        let mut rng = StdRng::seed_from_u64(2);
+        let [r, g, b] = Chromosome::new((0, 0), ColorSpace::Gray, &mut rng).get_val();
+        assert!(r == g && g == b);
+    }
+
+    #[test]
+    fn every_color_mode_round_trips_through_the_gene() {
+        let rgba = [180, 40, 90, 120];
+        let gray = [luma([180, 40, 90]); 3];
+        let cases = [
+            (ColorMode::RGB, [180, 40, 90, 255]),
+            (ColorMode::GRAY, [gray[0], gray[1], gray[2], 255]),
+            (ColorMode::RGBA, rgba),
+        ];
+        for (mode, expected) in cases {
+            assert_eq!(mode.decode(&mode.encode(rgba)), expected);
+            let chr = Chromosome::from_rgba((0, 0), rgba, mode).with_encoding(GeneEncoding::GrayCode);
+            assert_eq!(chr.gene.len(), mode.channels());
+            assert_eq!(chr.has_alpha(), mode.alpha);
+            assert_eq!(chr.get_rgba(), expected);
+        }
+
+        let hsv = ColorMode { space: ColorSpace::Hsv, alpha: true };
+        assert_eq!(hsv.channels(), 4);
+        assert_eq!(Chromosome::from_rgba((0, 0), [255, 0, 0, 7], hsv).get_rgba(), [255, 0, 0, 7]);
+    }
+
+    #[test]
+    fn palette_indices_clamp_to_the_last_color() {
+        let palette = Arc::new(vec![[0, 0, 0], [255, 0, 0], [0, 0, 255]]);
+        let mut chr = Chromosome::from_palette_colors((0, 0), &[[250, 10, 10]], Arc::clone(&palette));
+        assert_eq!(chr.gene, [Channel::new(0b01, 2)]);
+        assert_eq!(chr.get_val(), [255, 0, 0]);
+
+        // Index 3 is past the end of a 3-color palette.
+        chr.gene[0] = Channel::new(0b11, 2);
+        chr.mark_dirty();
+        assert_eq!(chr.get_val(), [0, 0, 255]);
+        assert_eq!(palette_index_bits(16), 4);
+        assert_eq!(palette_index_bits(1), 1);
+    }
+
+    #[test]
+    fn channel_rates_only_touch_their_channels() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let config = GaConfig { channel_mutation_rates: Some([0.0, 0.0, 1.0]), extra_flip_rate: 0.0, ..GaConfig::default() };
+        let mut chr = solid(false);
+        chr.push_alpha(0);
+        chr.mutate(&config, 1.0, &mut rng);
+        assert_eq!(chr.get_rgba(), [0, 0, 255, 255]);
+
+        let (zeros, ones) = (solid(false), solid(true));
+        for kind in KINDS {
+            for _ in 0..20 {
+                let (child, _) = zeros.channel_crossover_with(kind, &ones, [0.0, 1.0, 0.0], 0.0, &mut rng);
+                assert_eq!(child.gene[0], zeros.gene[0]);
+                assert_eq!(child.gene[2], zeros.gene[2]);
+            }
+        }
+    }
+
+    #[test]
+    fn creep_mutation_makes_small_clamped_steps() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let config = GaConfig { mutation: MutationOperator::Creep { sigma: 2.0 }, ..GaConfig::default() };
+        for encoding in [GeneEncoding::Binary, GeneEncoding::GrayCode] {
+            let color = [128, 3, 250];
+            let mut chr = Chromosome::from_color((0, 0), color, ColorSpace::Rgb);
+            chr.encoding = encoding;
+            chr.gene = color.iter().map(|&v| encode_channel(v, encoding)).collect();
+            chr.mark_dirty();
+            chr.mutate(&config, 0.0, &mut rng);
+            for (after, before) in chr.get_val().into_iter().zip(color) {
+                assert!(after.abs_diff(before) <= 12);
+            }
+        }
+
+        let config = GaConfig { mutation: MutationOperator::Creep { sigma: 1000.0 }, ..GaConfig::default() };
+        let mut chr = solid(true);
+        chr.mutate(&config, 0.0, &mut rng);
+        assert!(chr.get_val().iter().all(|&v| v == 0 || v == 255));
+    }
+
+    #[test]
+    fn fixed_flips_change_exactly_n_distinct_bits() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let differing = |a: &Chromosome, b: &Chromosome| a.gene.iter().zip(&b.gene).map(|(a, b)| (a.bits() ^ b.bits()).count_ones()).sum::<u32>();
+        let parents = [solid(false), Chromosome::from_deep_color((0, 0), [0x1234, 0, 0xffff])];
+        for parent in parents {
+            for n in [0, 1, 5, 24] {
+                let config = GaConfig { mutation: MutationOperator::FixedFlips { n }, ..GaConfig::default() };
+                let mut chr = parent.clone();
+                chr.mutate(&config, 0.0, &mut rng);
+                assert_eq!(differing(&chr, &parent), n as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn deep_channels_round_trip_and_decode_to_their_top_byte() {
+        for encoding in [GeneEncoding::Binary, GeneEncoding::GrayCode] {
+            let color = [0xabcd, 0x00ff, 0xff00];
+            let chr = Chromosome::from_deep_color((0, 0), color).with_encoding(encoding);
+            assert!(chr.is_deep());
+            assert_eq!(chr.get_deep_val(), color);
+            assert_eq!(chr.get_val(), [0xab, 0x00, 0xff]);
+        }
+        assert_eq!(solid(true).get_deep_val(), [65535; 3]);
+    }
+
+    #[test]
+    fn hex_round_trips_and_rejects_bad_input() {
+        let config = GaConfig { width: 4, height: 3, ..GaConfig::default() };
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..50 {
+            let chr = Chromosome::new((2, 3), ColorSpace::Rgb, &mut rng);
+            assert_eq!(Chromosome::from_hex((2, 3), &chr.to_hex(), &config).unwrap(), chr);
+        }
+        assert_eq!(Chromosome::from_hex((0, 0), "#0a0B0c", &config).unwrap().get_val(), [10, 11, 12]);
+
+        // Every layout round-trips through the config that builds it.
+        let palette = vec![Rgb([0, 0, 0]), Rgb([255, 255, 255]), Rgb([255, 0, 0])];
+        let palette_colors = Arc::new(palette.iter().map(|color| color.0).collect());
+        let mut rgba = Chromosome::new((1, 2), ColorSpace::Rgb, &mut rng);
+        rgba.push_alpha(99);
+        let layouts = [
+            (GaConfig { encoding: GeneEncoding::GrayCode, ..config.clone() }, Chromosome::new((1, 2), ColorSpace::Rgb, &mut rng).with_encoding(GeneEncoding::GrayCode)),
+            (GaConfig { encoding_space: EncodingSpace::Hsv, ..config.clone() }, Chromosome::new((1, 2), ColorSpace::Hsv, &mut rng)),
+            (GaConfig { grayscale: true, encoding: GeneEncoding::GrayCode, ..config.clone() }, Chromosome::new((1, 2), ColorSpace::Gray, &mut rng).with_encoding(GeneEncoding::GrayCode)),
+            (GaConfig { alpha: true, ..config.clone() }, rgba),
+            (GaConfig { deep_color: true, ..config.clone() }, Chromosome::random_deep((1, 2), &mut rng)),
+            (GaConfig { palette: Some(palette), ..config.clone() }, Chromosome::random_palette((1, 2), 1, palette_colors, &mut rng)),
+            (GaConfig { block_size: 2, ..config.clone() }, Chromosome::random_block((2, 2), 2, ColorSpace::Rgb, &mut rng)),
+        ];
+        for (config, chr) in layouts {
+            let parsed = Chromosome::from_hex(chr.pos, &chr.to_hex(), &config).unwrap();
+            assert_eq!((parsed.to_hex(), parsed.get_block_vals()), (chr.to_hex(), chr.get_block_vals()));
+            assert_eq!(parsed, chr);
+        }
+        assert_eq!(Chromosome::from_hex((0, 0), "a0", &GaConfig { grayscale: true, ..config.clone() }).unwrap().get_val(), [0xa0; 3]);
+
+        for hex in ["", "0a0b0", "0a0b0c0", "0a0b0g", "+a0b0c"] {
+            assert!(Chromosome::from_hex((0, 0), hex, &config).is_err(), "{}", hex);
+        }
+        let three_colors = GaConfig { palette: Some(vec![Rgb([0, 0, 0]); 3]), ..config.clone() };
+        assert!(Chromosome::from_hex((0, 0), "4", &three_colors).is_err());
+        assert!(Chromosome::from_hex((3, 0), "0a0b0c", &config).is_err());
+        assert!(Chromosome::from_hex((0, 4), "0a0b0c", &config).is_err());
+        assert!(Chromosome::from_hex((1, 2), "0a0b0c0a0b0c0a0b0c0a0b0c", &GaConfig { block_size: 2, ..config }).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use image::{Pixel, Rgb};
+use serde::{Deserialize, Serialize};
+
+/// How a chromosome's channels decode to RGB. `Gray` has a single luminance
+/// channel per pixel, broadcast to R = G = B.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ColorSpace {
+    Rgb,
+    Hsv,
+    Gray,
+}
+
+impl ColorSpace {
+    /// Gene channels per pixel, not counting alpha.
+    pub fn channels(self) -> usize {
+        match self {
+            ColorSpace::Rgb | ColorSpace::Hsv => 3,
+            ColorSpace::Gray => 1,
+        }
+    }
+
+    /// The `channels()` channel values that encode `rgb`.
+    pub fn encode(self, rgb: [u8; 3]) -> Vec<u8> {
+        match self {
+            ColorSpace::Rgb => rgb.to_vec(),
+            ColorSpace::Hsv => rgb_to_hsv(rgb).to_vec(),
+            ColorSpace::Gray => vec![luma(rgb)],
+        }
+    }
+
+    /// The RGB color the first `channels()` values of `raw` encode.
+    pub fn decode(self, raw: &[u8]) -> [u8; 3] {
+        match self {
+            ColorSpace::Rgb => [raw[0], raw[1], raw[2]],
+            ColorSpace::Hsv => hsv_to_rgb([raw[0], raw[1], raw[2]]),
+            ColorSpace::Gray => [raw[0]; 3],
+        }
+    }
+}
+
+/// A pixel's channel layout: its color channels, followed by an alpha
+/// channel in RGBA mode. Palette genes hold one index per pixel instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorMode {
+    pub space: ColorSpace,
+    pub alpha: bool,
+}
+
+impl ColorMode {
+    pub const RGB: ColorMode = ColorMode { space: ColorSpace::Rgb, alpha: false };
+    pub const RGBA: ColorMode = ColorMode { space: ColorSpace::Rgb, alpha: true };
+    pub const GRAY: ColorMode = ColorMode { space: ColorSpace::Gray, alpha: false };
+
+    /// Gene channels per pixel, alpha included.
+    pub fn channels(self) -> usize {
+        self.space.channels() + self.alpha as usize
+    }
+
+    /// The `channels()` channel values that encode `rgba`; without an alpha
+    /// channel the alpha is dropped.
+    pub fn encode(self, rgba: [u8; 4]) -> Vec<u8> {
+        let mut raw = self.space.encode([rgba[0], rgba[1], rgba[2]]);
+        if self.alpha {
+            raw.push(rgba[3]);
+        }
+        raw
+    }
+
+    /// The RGBA color `raw` encodes; fully opaque without an alpha channel.
+    pub fn decode(self, raw: &[u8]) -> [u8; 4] {
+        let [r, g, b] = self.space.decode(raw);
+        let alpha = if self.alpha { raw[self.space.channels()] } else { 255 };
+        [r, g, b, alpha]
+    }
+}
+
+/// Which quantity the three channels of a color chromosome hold. With `Hsv`
+/// they are hue, saturation and value, decoded to RGB for output and fitness,
+/// so mutation nudges a pixel's hue or brightness rather than one primary.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EncodingSpace {
+    Rgb,
+    Hsv,
+}
+
+/// Luminance as computed by `image`'s RGB to luma conversion.
+pub fn luma(rgb: [u8; 3]) -> u8 {
+    Rgb(rgb).to_luma()[0]
+}
+
+/// The 16-color PICO-8 palette, available as `--palette pico8`.
+pub const PICO8_PALETTE: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00], [0x1D, 0x2B, 0x53], [0x7E, 0x25, 0x53], [0x00, 0x87, 0x51],
+    [0xAB, 0x52, 0x36], [0x5F, 0x57, 0x4F], [0xC2, 0xC3, 0xC7], [0xFF, 0xF1, 0xE8],
+    [0xFF, 0x00, 0x4D], [0xFF, 0xA3, 0x00], [0xFF, 0xEC, 0x27], [0x00, 0xE4, 0x36],
+    [0x29, 0xAD, 0xFF], [0x83, 0x76, 0x9C], [0xFF, 0x77, 0xA8], [0xFF, 0xCC, 0xAA],
+];
+
+pub fn parse_hex_color(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut color = [0u8; 3];
+    for (i, c) in color.iter_mut().enumerate() {
+        *c = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(color)
+}
+
+pub fn rgb_to_hsv(rgb: [u8; 3]) -> [u8; 3] {
+    let [r, g, b] = rgb.map(|c| c as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    } / 6.0;
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    [hue, saturation, max].map(|c| (c * 255.0).round() as u8)
+}
+
+pub fn hsv_to_rgb(hsv: [u8; 3]) -> [u8; 3] {
+    let h = hsv[0] as f64 / 256.0 * 6.0;
+    let s = hsv[1] as f64 / 255.0;
+    let v = hsv[2] as f64 / 255.0;
+
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+
+    [r, g, b].map(|ch| ((ch + m) * 255.0).round() as u8)
+}
+
+pub fn blend_colors(a: [u8; 3], b: [u8; 3], weight: f64) -> [u8; 3] {
+    let mut out = [0u8; 3];
+    for i in 0..3 {
+        out[i] = (a[i] as f64 * (1.0 - weight) + b[i] as f64 * weight).round() as u8;
+    }
+    out
+}
+
+pub fn rgb_to_ycbcr(c: [u8; 3]) -> (f64, f64, f64) {
+    let [r, g, b] = c.map(|v| v as f64);
+    (
+        0.299 * r + 0.587 * g + 0.114 * b,
+        128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b,
+        128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b,
+    )
+}
+
+pub fn ycbcr_to_rgb(y: f64, cb: f64, cr: f64) -> [u8; 3] {
+    [
+        y + 1.402 * (cr - 128.0),
+        y - 0.344136 * (cb - 128.0) - 0.714136 * (cr - 128.0),
+        y + 1.772 * (cb - 128.0),
+    ]
+    .map(|v| v.round().clamp(0.0, 255.0) as u8)
+}
+
+/// sRGB (D65) to CIELAB.
+pub fn rgb_to_lab(c: [u8; 3]) -> (f64, f64, f64) {
+    let [r, g, b] = c.map(|v| {
+        let v = v as f64 / 255.0;
+        if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+    });
+
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = (0.0193339 * r + 0.1191920 * g + 0.9503041 * b) / 1.08883;
+
+    let f = |t: f64| if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+/// CIEDE2000 color difference between two CIELAB colors (kL = kC = kH = 1).
+pub fn delta_e_2000(lab1: (f64, f64, f64), lab2: (f64, f64, f64)) -> f64 {
+    let (l1, a1, b1) = lab1;
+    let (l2, a2, b2) = lab2;
+    let pow25_7 = 25f64.powi(7);
+
+    let c_bar = (a1.hypot(b1) + a2.hypot(b2)) / 2.0;
+    let g = 0.5 * (1.0 - (c_bar.powi(7) / (c_bar.powi(7) + pow25_7)).sqrt());
+    let (a1p, a2p) = (a1 * (1.0 + g), a2 * (1.0 + g));
+    let (c1p, c2p) = (a1p.hypot(b1), a2p.hypot(b2));
+
+    let hue = |b: f64, a: f64| if a == 0.0 && b == 0.0 { 0.0 } else { b.atan2(a).to_degrees().rem_euclid(360.0) };
+    let (h1p, h2p) = (hue(b1, a1p), hue(b2, a2p));
+    let chroma_zero = c1p * c2p == 0.0;
+
+    let dl = l2 - l1;
+    let dc = c2p - c1p;
+    let dh = match h2p - h1p {
+        _ if chroma_zero => 0.0,
+        d if d > 180.0 => d - 360.0,
+        d if d < -180.0 => d + 360.0,
+        d => d,
+    };
+    let dh_big = 2.0 * (c1p * c2p).sqrt() * (dh.to_radians() / 2.0).sin();
+
+    let l_bar = (l1 + l2) / 2.0;
+    let c_bar_p = (c1p + c2p) / 2.0;
+    let h_bar = if chroma_zero {
+        h1p + h2p
+    } else if (h1p - h2p).abs() <= 180.0 {
+        (h1p + h2p) / 2.0
+    } else if h1p + h2p < 360.0 {
+        (h1p + h2p + 360.0) / 2.0
+    } else {
+        (h1p + h2p - 360.0) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * (h_bar - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * h_bar).to_radians().cos()
+        + 0.32 * (3.0 * h_bar + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_bar - 63.0).to_radians().cos();
+    let d_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+    let r_c = 2.0 * (c_bar_p.powi(7) / (c_bar_p.powi(7) + pow25_7)).sqrt();
+    let s_l = 1.0 + 0.015 * (l_bar - 50.0).powi(2) / (20.0 + (l_bar - 50.0).powi(2)).sqrt();
+    let s_c = 1.0 + 0.045 * c_bar_p;
+    let s_h = 1.0 + 0.015 * c_bar_p * t;
+    let r_t = -(2.0 * d_theta).to_radians().sin() * r_c;
+
+    let (tl, tc, th) = (dl / s_l, dc / s_c, dh_big / s_h);
+    (tl * tl + tc * tc + th * th + r_t * tc * th).sqrt()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delta_e_2000_matches_sharma_reference_pairs() {
+        // Pairs and differences from Sharma, Wu and Dalal (2005), table 1.
+        let pairs = [
+            ((50.0, 2.6772, -79.7751), (50.0, 0.0, -82.7485), 2.0425),
+            ((50.0, 3.1571, -77.2803), (50.0, 0.0, -82.7485), 2.8615),
+            ((50.0, 2.8361, -74.0200), (50.0, 0.0, -82.7485), 3.4412),
+            // One or both colors have no chroma, so there is no hue to compare.
+            ((50.0, 0.0, 0.0), (50.0, -1.0, 2.0), 2.3669),
+            ((50.0, -1.0, 2.0), (50.0, 0.0, 0.0), 2.3669),
+            // Hues either side of 0/360 and 90/270 degrees.
+            ((50.0, 2.4900, -0.0010), (50.0, -2.4900, 0.0009), 7.1792),
+            ((50.0, 2.4900, -0.0010), (50.0, -2.4900, 0.0010), 7.1792),
+            ((50.0, 2.4900, -0.0010), (50.0, -2.4900, 0.0011), 7.2195),
+            ((50.0, 2.4900, -0.0010), (50.0, -2.4900, 0.0012), 7.2195),
+            ((50.0, -0.0010, 2.4900), (50.0, 0.0009, -2.4900), 4.8045),
+            ((50.0, -0.0010, 2.4900), (50.0, 0.0011, -2.4900), 4.7461),
+            ((50.0, 2.5, 0.0), (50.0, 0.0, -2.5), 4.3065),
+            ((50.0, 2.5, 0.0), (73.0, 25.0, -18.0), 27.1492),
+            ((60.2574, -34.0099, 36.2677), (60.4626, -34.1751, 39.4387), 1.2644),
+            ((2.0776, 0.0795, -1.1350), (0.9033, -0.0636, -0.5514), 0.9082),
+        ];
+        for (lab1, lab2, expected) in pairs {
+            let delta = delta_e_2000(lab1, lab2);
+            assert!((delta - expected).abs() < 1e-4, "{:?} {:?}: {} != {}", lab1, lab2, delta, expected);
+            assert!((delta_e_2000(lab2, lab1) - delta).abs() < 1e-12);
+        }
+        assert_eq!(delta_e_2000((50.0, 0.0, 0.0), (50.0, 0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn rgb_to_lab_matches_reference_values() {
+        let close = |lab: (f64, f64, f64), expected: (f64, f64, f64)| {
+            (lab.0 - expected.0).abs() < 0.01 && (lab.1 - expected.1).abs() < 0.01 && (lab.2 - expected.2).abs() < 0.01
+        };
+        assert!(close(rgb_to_lab([0, 0, 0]), (0.0, 0.0, 0.0)));
+        assert!(close(rgb_to_lab([255, 255, 255]), (100.0, 0.0, 0.0)));
+        assert!(close(rgb_to_lab([255, 0, 0]), (53.2408, 80.0925, 67.2032)), "{:?}", rgb_to_lab([255, 0, 0]));
+        assert!(close(rgb_to_lab([0, 0, 255]), (32.2970, 79.1875, -107.8602)), "{:?}", rgb_to_lab([0, 0, 255]));
+        // Grays have no chroma whatever their lightness.
+        let (_, a, b) = rgb_to_lab([128, 128, 128]);
+        assert!(a.abs() < 0.01 && b.abs() < 0.01);
+    }
+}
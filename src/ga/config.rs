@@ -0,0 +1,587 @@
+use image::imageops::FilterType;
+use image::Rgb;
+use std::path::PathBuf;
+
+use super::chromosome::{palette_index_bits, CrossoverKind, GeneEncoding, MutationOperator};
+use super::color::{ColorMode, ColorSpace, EncodingSpace};
+use super::fitness::{FitnessCurve, FitnessMetric};
+use super::image::AspectMode;
+use super::population::InitStrategy;
+use super::selection::SelectionMethod;
+use super::{DEEP_GENE_LENGTH, GENE_LENGTH};
+
+/// How the per-bit mutation rate changes over the run. `Constant` always uses
+/// `GaConfig::mutation_rate`; the others interpolate from `start` at the first
+/// generation to `end` at the last, linearly or geometrically.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MutationSchedule {
+    Constant,
+    Linear { start: f64, end: f64 },
+    Exponential { start: f64, end: f64 },
+}
+
+/// How the crossover probability changes over the run, independently of the
+/// mutation schedule. `Constant` always uses `GaConfig::crossover_rate`; the
+/// others interpolate from `start` to `end` as `MutationSchedule` does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrossoverSchedule {
+    Constant,
+    Linear { start: f64, end: f64 },
+    Exponential { start: f64, end: f64 },
+}
+
+/// Chromosomes in each island's pool. `Adaptive` scales a cell's pool from
+/// `min` in flat parts of the target to `max` where its local contrast is
+/// highest, spending the evaluations where detail has to be found.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PopulationStrategy {
+    Uniform(usize),
+    Adaptive { min: usize, max: usize },
+}
+
+impl PopulationStrategy {
+    /// Pool size for a cell of `difficulty` in [0, 1].
+    pub fn size(self, difficulty: f64) -> usize {
+        match self {
+            PopulationStrategy::Uniform(n) => n,
+            PopulationStrategy::Adaptive { min, max } => min + (max.saturating_sub(min) as f64 * difficulty.clamp(0.0, 1.0)).round() as usize,
+        }
+    }
+}
+
+/// A number of chromosomes per island, either fixed or a fraction of the
+/// island's population (rounded), so it scales when the population does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Share {
+    Count(usize),
+    Fraction(f64),
+}
+
+impl Share {
+    pub fn of(self, population: usize) -> usize {
+        match self {
+            Share::Count(n) => n,
+            Share::Fraction(fraction) => (fraction * population as f64).round() as usize,
+        }
+    }
+}
+
+/// `HillClimb` is a (1+1) baseline: each cell keeps one chromosome and
+/// replaces it with a mutated copy only when the copy scores higher.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Ga,
+    HillClimb,
+}
+
+/// A `GaConfig` field with a value no run can use, and why.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid {}: {}", self.field, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn invalid(field: &'static str, message: impl Into<String>) -> Result<(), ConfigError> {
+    Err(ConfigError { field, message: message.into() })
+}
+
+fn check_probability(field: &'static str, value: f64) -> Result<(), ConfigError> {
+    match (0.0..=1.0).contains(&value) {
+        true => Ok(()),
+        false => invalid(field, format!("{} is not a probability in [0, 1]", value)),
+    }
+}
+
+/// Tunable GA parameters. `Default` reproduces the original hardcoded setup.
+#[derive(Clone, Debug)]
+pub struct GaConfig {
+    pub width: usize,
+    pub height: usize,
+    pub aspect: AspectMode,
+    /// Filter used when the target is resized to the grid.
+    pub resize_filter: FilterType,
+    /// Size the grid to the target image instead of `width` x `height`.
+    pub keep_native_size: bool,
+    /// Evolve an alpha channel too and write RGBA output.
+    pub alpha: bool,
+    pub encoding: GeneEncoding,
+    pub encoding_space: EncodingSpace,
+    /// Evolve a single luminance channel per pixel against the target's
+    /// luminance.
+    pub grayscale: bool,
+    /// Evolve 16 bits per channel against a 16-bit target and save the
+    /// result as a 16-bit PNG; frames and statistics use the top byte.
+    pub deep_color: bool,
+    pub algorithm: Algorithm,
+    /// Side of the square block of pixels one chromosome covers; 1 evolves
+    /// every pixel separately.
+    pub block_size: usize,
+    /// Size of each island's pool.
+    pub population: PopulationStrategy,
+    /// Independent subpopulations per pixel.
+    pub islands: usize,
+    /// Every this many generations, each island's best replaces the worst
+    /// of the next island (in a ring).
+    pub migration_interval: usize,
+    pub iterations: usize,
+    /// Coarse-to-fine levels; above 1, the run first evolves the target at
+    /// half the resolution (recursively) and seeds the full-resolution pools
+    /// from that result. Every level gets an equal share of the generations.
+    pub pyramid_levels: usize,
+    pub mutation_rate: f64,
+    pub mutation_schedule: MutationSchedule,
+    /// Per-bit mutation rates of the red, green and blue (or hue, saturation
+    /// and value) channels, in place of `mutation_rate`; alpha keeps that.
+    pub channel_mutation_rates: Option<[f64; 3]>,
+    /// Chance of one extra forced bit flip per `BitFlip` mutation.
+    pub extra_flip_rate: f64,
+    pub crossover_rate: f64,
+    pub crossover_schedule: CrossoverSchedule,
+    /// Chance each color channel is crossed over, drawn per channel, in
+    /// place of one `crossover_rate` draw for the whole chromosome.
+    pub channel_crossover_rates: Option<[f64; 3]>,
+    pub crossover: CrossoverKind,
+    pub selection: SelectionMethod,
+    /// Candidates per tournament; at least 1.
+    pub tournament_size: Share,
+    /// Best chromosomes copied unchanged into the next generation; at most
+    /// one less than the population, so every generation breeds offspring.
+    pub elite_size: Share,
+    /// Probability the tournament winner is the fittest candidate.
+    pub selection_pressure: f64,
+    /// Chance an offspring is the average color of the elites.
+    pub centroid_rate: f64,
+    /// Chance the second parent is the best chromosome of an adjacent cell,
+    /// which lets good colors spread across smooth regions.
+    pub spatial_crossover_rate: f64,
+    /// Lock pixels once they match the target exactly.
+    pub freeze_converged: bool,
+    /// Per-cell generation budgets: the run gets `iterations` generations'
+    /// worth of cell steps in total, cells that stop being stepped (frozen
+    /// on a match) hand their unused steps to the rest, and no cell is
+    /// stepped more than this many generations. The HSV twins of
+    /// `--blend-hsv` are cells too: they add to the total and spend from it.
+    /// `None` steps every cell for exactly `iterations` generations.
+    pub max_cell_generations: Option<usize>,
+    /// Stop after this many generations without the average fitness
+    /// improving by more than `min_delta`.
+    pub patience: Option<usize>,
+    pub min_delta: f64,
+    /// Above 0, the first plateau after an improvement replaces this share
+    /// of every cell's non-elite chromosomes with random ones (random
+    /// immigrants) instead of stopping; the run stops if it plateaus again.
+    pub immigrant_fraction: f64,
+    /// Re-randomize a cell's non-elite chromosomes once its diversity (mean
+    /// pairwise Hamming distance, in bits) stays below this for
+    /// `diversity_patience` generations without a perfect match.
+    pub diversity_threshold: Option<f64>,
+    pub diversity_patience: usize,
+    pub fitness: FitnessMetric,
+    /// Divisor of the RGB RMSE in `exp(-rmse / T)`; lower values score near
+    /// misses lower. See `FitnessCurve` for how it affects selection.
+    pub fitness_temperature: f64,
+    /// Multiplier on the score of colors within an RMSE of
+    /// `exact_match_threshold` of the target; 1 disables it, leaving a
+    /// continuous fitness.
+    pub exact_match_bonus: f64,
+    pub exact_match_threshold: f64,
+    /// Weight of the gradient term added to the color fitness; it rewards
+    /// matching the target's steps to the right and bottom neighbors.
+    pub gradient_weight: f64,
+    /// Restricts every pixel to one of these colors; genes then encode a
+    /// palette index instead of RGB channels.
+    pub palette: Option<Vec<Rgb<u8>>>,
+    pub mutation: MutationOperator,
+    pub init: InitStrategy,
+    /// A previous result to refine instead of starting from `init`. Each
+    /// island starts with one exact copy of the image's color and fills up
+    /// with noisy variants of it. Resized to the grid if it does not match.
+    pub warm_start: Option<PathBuf>,
+    /// Fixed seed for bit-for-bit reproducible runs; `None` seeds from entropy.
+    pub seed: Option<u64>,
+    /// Worker threads that step the grid's cells; `None` uses rayon's global
+    /// pool and 1 steps them in order on the calling thread. Every cell draws
+    /// from its own RNG, so a seeded run gives the same result whatever the
+    /// thread count. Ignored without the `native` feature.
+    pub threads: Option<usize>,
+}
+
+impl Default for GaConfig {
+    fn default() -> Self {
+        GaConfig {
+            width: 100,
+            height: 100,
+            aspect: AspectMode::Stretch,
+            resize_filter: FilterType::CatmullRom,
+            keep_native_size: false,
+            alpha: false,
+            encoding: GeneEncoding::Binary,
+            encoding_space: EncodingSpace::Rgb,
+            grayscale: false,
+            deep_color: false,
+            algorithm: Algorithm::Ga,
+            block_size: 1,
+            population: PopulationStrategy::Uniform(6),
+            islands: 1,
+            migration_interval: 10,
+            iterations: 50,
+            pyramid_levels: 1,
+            mutation_rate: 0.05,
+            mutation_schedule: MutationSchedule::Constant,
+            channel_mutation_rates: None,
+            extra_flip_rate: 0.1,
+            crossover_rate: 0.8,
+            crossover_schedule: CrossoverSchedule::Constant,
+            channel_crossover_rates: None,
+            crossover: CrossoverKind::Uniform,
+            selection: SelectionMethod::Tournament,
+            tournament_size: Share::Count(3),
+            elite_size: Share::Count(2),
+            selection_pressure: 1.0,
+            centroid_rate: 0.0,
+            spatial_crossover_rate: 0.0,
+            freeze_converged: false,
+            max_cell_generations: None,
+            patience: None,
+            min_delta: 1e-4,
+            immigrant_fraction: 0.0,
+            diversity_threshold: None,
+            diversity_patience: 5,
+            fitness: FitnessMetric::RgbRmse,
+            fitness_temperature: FitnessCurve::default().temperature,
+            exact_match_bonus: FitnessCurve::default().exact_match_bonus,
+            exact_match_threshold: FitnessCurve::default().exact_match_threshold,
+            gradient_weight: 0.0,
+            palette: None,
+            mutation: MutationOperator::BitFlip,
+            init: InitStrategy::Random,
+            warm_start: None,
+            seed: None,
+            threads: None,
+        }
+    }
+}
+
+/// Named starting points trading speed for quality. Each changes only the
+/// population, generations, schedules and stopping rules, so any other
+/// field can still be set on top.
+impl GaConfig {
+    /// Small pools for few generations, stopping as soon as the average
+    /// fitness stalls: a rough result in a fraction of the default's work.
+    pub fn fast() -> Self {
+        GaConfig {
+            population: PopulationStrategy::Uniform(4),
+            iterations: 30,
+            patience: Some(5),
+            min_delta: 1e-3,
+            ..GaConfig::default()
+        }
+    }
+
+    /// Moderate pools for a few hundred generations, cooling the mutation
+    /// rate so late generations fine-tune, and stopping once progress ends.
+    pub fn balanced() -> Self {
+        GaConfig {
+            population: PopulationStrategy::Uniform(8),
+            iterations: 200,
+            mutation_schedule: MutationSchedule::Exponential { start: 0.08, end: 0.01 },
+            patience: Some(25),
+            ..GaConfig::default()
+        }
+    }
+
+    /// Two islands of large pools over many generations, annealing both
+    /// mutation and crossover. Pixels freeze once their best matches, and
+    /// hand their generations to the rest, up to twice the run's length.
+    pub fn high_quality() -> Self {
+        let iterations = 600;
+        GaConfig {
+            population: PopulationStrategy::Uniform(16),
+            islands: 2,
+            iterations,
+            mutation_schedule: MutationSchedule::Exponential { start: 0.1, end: 0.005 },
+            crossover_schedule: CrossoverSchedule::Linear { start: 0.9, end: 0.5 },
+            patience: Some(60),
+            min_delta: 1e-5,
+            freeze_converged: true,
+            max_cell_generations: Some(2 * iterations),
+            ..GaConfig::default()
+        }
+    }
+}
+
+// The rate `t` of the way from `start` to `end`, geometrically if both are
+// positive and `geometric` is set, linearly otherwise.
+fn interpolate_rate(start: f64, end: f64, t: f64, geometric: bool) -> f64 {
+    let t = t.min(1.0);
+    if geometric && start > 0.0 && end > 0.0 {
+        start * (end / start).powf(t)
+    } else {
+        start + (end - start) * t
+    }
+}
+
+impl GaConfig {
+    // How far `generation` is through the run, from 0 to 1.
+    fn progress(&self, generation: usize) -> f64 {
+        generation as f64 / self.iterations.saturating_sub(1).max(1) as f64
+    }
+
+    pub fn mutation_rate_at(&self, generation: usize) -> f64 {
+        let t = self.progress(generation);
+        match self.mutation_schedule {
+            MutationSchedule::Constant => self.mutation_rate,
+            MutationSchedule::Linear { start, end } => interpolate_rate(start, end, t, false),
+            MutationSchedule::Exponential { start, end } => interpolate_rate(start, end, t, true),
+        }
+    }
+
+    pub fn crossover_rate_at(&self, generation: usize) -> f64 {
+        let t = self.progress(generation);
+        match self.crossover_schedule {
+            CrossoverSchedule::Constant => self.crossover_rate,
+            CrossoverSchedule::Linear { start, end } => interpolate_rate(start, end, t, false),
+            CrossoverSchedule::Exponential { start, end } => interpolate_rate(start, end, t, true),
+        }
+    }
+
+    /// The generation the run ends at, at the latest: past `iterations` when
+    /// per-cell budgets let unconverged cells use the steps of frozen ones.
+    pub fn generation_limit(&self) -> usize {
+        self.max_cell_generations.map_or(self.iterations, |cap| cap.max(self.iterations))
+    }
+
+    /// The generation the full-resolution level of a coarse-to-fine run
+    /// starts at; the coarser levels run before it. 0 without a pyramid.
+    pub fn pyramid_start(&self) -> usize {
+        let levels = self.pyramid_levels.max(1);
+        self.iterations / levels * (levels - 1)
+    }
+
+    /// Elites kept by an island of `population` chromosomes.
+    pub fn elite_count(&self, population: usize) -> usize {
+        self.elite_size.of(population).min(population.saturating_sub(1))
+    }
+
+    /// Tournament size within an island of `population` chromosomes.
+    pub fn tournament_count(&self, population: usize) -> usize {
+        self.tournament_size.of(population).max(1)
+    }
+
+    /// How the grid's chromosomes decode to RGB.
+    pub fn color_space(&self) -> ColorSpace {
+        match (self.grayscale, self.encoding_space) {
+            (true, _) => ColorSpace::Gray,
+            (false, EncodingSpace::Rgb) => ColorSpace::Rgb,
+            (false, EncodingSpace::Hsv) => ColorSpace::Hsv,
+        }
+    }
+
+    /// The grid's channel layout: `color_space()` plus alpha in RGBA mode.
+    pub fn color_mode(&self) -> ColorMode {
+        ColorMode { space: self.color_space(), alpha: self.alpha }
+    }
+
+    /// Bits in the chromosome of one full cell: every channel of every pixel
+    /// in a block, or one palette index per pixel in palette mode.
+    pub fn chromosome_bits(&self) -> usize {
+        let per_pixel = match (&self.palette, self.deep_color) {
+            (Some(palette), _) => palette_index_bits(palette.len()),
+            (None, true) => ColorSpace::Rgb.channels() * DEEP_GENE_LENGTH,
+            (None, false) => self.color_mode().channels() * GENE_LENGTH,
+        };
+        per_pixel * self.block_size * self.block_size
+    }
+
+    /// Checks the invariants the run functions rely on, so a bad setting is
+    /// reported up front rather than as a panic deep inside a generation.
+    /// Elite and tournament counts are checked against the largest pool.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.width == 0 || self.height == 0 {
+            return invalid("width/height", format!("{}x{} grid has no pixels", self.width, self.height));
+        }
+        match self.population {
+            PopulationStrategy::Uniform(0) => return invalid("population", "must be at least 1"),
+            PopulationStrategy::Adaptive { min, max } if min == 0 || min > max => {
+                return invalid("population", format!("adaptive range {}..{} must be non-empty and start at 1 or more", min, max))
+            }
+            _ => {}
+        }
+        let counts = [
+            ("block_size", self.block_size),
+            ("islands", self.islands),
+            ("migration_interval", self.migration_interval),
+            ("pyramid_levels", self.pyramid_levels),
+        ];
+        for (field, value) in counts {
+            if value == 0 {
+                return invalid(field, "must be at least 1");
+            }
+        }
+        if self.threads == Some(0) {
+            return invalid("threads", "must be at least 1");
+        }
+        if self.warm_start.is_some() {
+            if !matches!(self.init, InitStrategy::Random) {
+                return invalid("warm_start", "replaces the init strategy, which must be left at Random");
+            }
+            if self.pyramid_levels > 1 {
+                return invalid("warm_start", "refines at full resolution and cannot start a pyramid");
+            }
+        }
+
+        check_probability("mutation_rate", self.mutation_rate)?;
+        if let MutationSchedule::Linear { start, end } | MutationSchedule::Exponential { start, end } = self.mutation_schedule {
+            check_probability("mutation_schedule", start)?;
+            check_probability("mutation_schedule", end)?;
+        }
+        for (field, rates) in [("channel_mutation_rates", self.channel_mutation_rates), ("channel_crossover_rates", self.channel_crossover_rates)] {
+            rates.iter().flatten().try_for_each(|&rate| check_probability(field, rate))?;
+        }
+        if self.channel_mutation_rates.is_some() && self.mutation_schedule != MutationSchedule::Constant {
+            return invalid("channel_mutation_rates", "cannot follow a mutation schedule");
+        }
+        if let MutationOperator::FixedFlips { n } = self.mutation {
+            if n > self.chromosome_bits() {
+                return invalid("mutation", format!("cannot flip {} bits of a chromosome with {}", n, self.chromosome_bits()));
+            }
+        }
+        check_probability("extra_flip_rate", self.extra_flip_rate)?;
+        check_probability("crossover_rate", self.crossover_rate)?;
+        if let CrossoverSchedule::Linear { start, end } | CrossoverSchedule::Exponential { start, end } = self.crossover_schedule {
+            check_probability("crossover_schedule", start)?;
+            check_probability("crossover_schedule", end)?;
+        }
+        if self.channel_crossover_rates.is_some() && self.crossover_schedule != CrossoverSchedule::Constant {
+            return invalid("channel_crossover_rates", "cannot follow a crossover schedule");
+        }
+        check_probability("selection_pressure", self.selection_pressure)?;
+        check_probability("centroid_rate", self.centroid_rate)?;
+        check_probability("immigrant_fraction", self.immigrant_fraction)?;
+        if let Some(cap) = self.max_cell_generations.filter(|&cap| cap < self.iterations) {
+            return invalid("max_cell_generations", format!("{} is below the {} generations every cell gets anyway", cap, self.iterations));
+        }
+        if self.immigrant_fraction > 0.0 && self.patience.is_none() {
+            return invalid("immigrant_fraction", "needs patience to detect a plateau");
+        }
+        check_probability("spatial_crossover_rate", self.spatial_crossover_rate)?;
+
+        let population = self.population.size(1.0);
+        match self.elite_size {
+            Share::Count(n) if n > population => return invalid("elite_size", format!("{} exceeds the population of {}", n, population)),
+            Share::Fraction(f) => check_probability("elite_size", f)?,
+            _ => {}
+        }
+        match self.tournament_size {
+            Share::Count(0) => return invalid("tournament_size", "must be at least 1"),
+            Share::Count(n) if n > population => {
+                return invalid("tournament_size", format!("{} exceeds the population of {}", n, population))
+            }
+            Share::Fraction(f) if f <= 0.0 || f > 1.0 => return invalid("tournament_size", format!("fraction {} is not in (0, 1]", f)),
+            _ => {}
+        }
+
+        if !(self.fitness_temperature > 0.0 && self.fitness_temperature.is_finite()) {
+            return invalid("fitness_temperature", format!("{} is not a positive number", self.fitness_temperature));
+        }
+        for (field, value) in [
+            ("exact_match_bonus", self.exact_match_bonus),
+            ("exact_match_threshold", self.exact_match_threshold),
+            ("gradient_weight", self.gradient_weight),
+            ("min_delta", self.min_delta),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                return invalid(field, format!("{} is not a non-negative number", value));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fitness_curve(&self) -> FitnessCurve {
+        FitnessCurve {
+            temperature: self.fitness_temperature,
+            exact_match_bonus: self.exact_match_bonus,
+            exact_match_threshold: self.exact_match_threshold,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_names_the_offending_field() {
+        assert_eq!(GaConfig::default().validate(), Ok(()));
+        let field = |config: GaConfig| config.validate().unwrap_err().field;
+
+        assert_eq!(field(GaConfig { width: 0, ..GaConfig::default() }), "width/height");
+        assert_eq!(field(GaConfig { population: PopulationStrategy::Uniform(0), ..GaConfig::default() }), "population");
+        assert_eq!(field(GaConfig { population: PopulationStrategy::Adaptive { min: 8, max: 4 }, ..GaConfig::default() }), "population");
+        assert_eq!(field(GaConfig { islands: 0, ..GaConfig::default() }), "islands");
+        assert_eq!(field(GaConfig { migration_interval: 0, ..GaConfig::default() }), "migration_interval");
+        assert_eq!(field(GaConfig { block_size: 0, ..GaConfig::default() }), "block_size");
+        assert_eq!(field(GaConfig { pyramid_levels: 0, ..GaConfig::default() }), "pyramid_levels");
+        assert_eq!(field(GaConfig { mutation_rate: 1.5, ..GaConfig::default() }), "mutation_rate");
+        assert_eq!(field(GaConfig { mutation_rate: -0.1, ..GaConfig::default() }), "mutation_rate");
+        let schedule = MutationSchedule::Linear { start: 0.1, end: 2.0 };
+        assert_eq!(field(GaConfig { mutation_schedule: schedule, ..GaConfig::default() }), "mutation_schedule");
+        assert_eq!(field(GaConfig { crossover_rate: f64::NAN, ..GaConfig::default() }), "crossover_rate");
+        assert_eq!(field(GaConfig { channel_crossover_rates: Some([0.5, 1.2, 0.5]), ..GaConfig::default() }), "channel_crossover_rates");
+        let schedule = MutationSchedule::Linear { start: 0.1, end: 0.01 };
+        let channel_rates = GaConfig { channel_mutation_rates: Some([0.01, 0.01, 0.2]), mutation_schedule: schedule, ..GaConfig::default() };
+        assert_eq!(field(channel_rates), "channel_mutation_rates");
+        let schedule = CrossoverSchedule::Exponential { start: 0.9, end: -0.1 };
+        assert_eq!(field(GaConfig { crossover_schedule: schedule, ..GaConfig::default() }), "crossover_schedule");
+        let schedule = CrossoverSchedule::Linear { start: 0.9, end: 0.3 };
+        let channel_rates = GaConfig { channel_crossover_rates: Some([0.8, 0.8, 1.0]), crossover_schedule: schedule, ..GaConfig::default() };
+        assert_eq!(field(channel_rates), "channel_crossover_rates");
+        assert_eq!(field(GaConfig { mutation: MutationOperator::FixedFlips { n: 25 }, ..GaConfig::default() }), "mutation");
+        let gray_flips = GaConfig { mutation: MutationOperator::FixedFlips { n: 9 }, grayscale: true, ..GaConfig::default() };
+        assert_eq!(field(gray_flips.clone()), "mutation");
+        assert_eq!(GaConfig { block_size: 2, ..gray_flips }.validate(), Ok(()));
+        assert_eq!(GaConfig { mutation: MutationOperator::FixedFlips { n: 48 }, deep_color: true, ..GaConfig::default() }.validate(), Ok(()));
+        let palette = Some(vec![Rgb([0, 0, 0]); 4]);
+        assert_eq!(field(GaConfig { mutation: MutationOperator::FixedFlips { n: 3 }, palette, ..GaConfig::default() }), "mutation");
+        assert_eq!(field(GaConfig { selection_pressure: 2.0, ..GaConfig::default() }), "selection_pressure");
+        assert_eq!(field(GaConfig { spatial_crossover_rate: 1.1, ..GaConfig::default() }), "spatial_crossover_rate");
+        assert_eq!(field(GaConfig { elite_size: Share::Count(7), ..GaConfig::default() }), "elite_size");
+        assert_eq!(field(GaConfig { elite_size: Share::Fraction(1.5), ..GaConfig::default() }), "elite_size");
+        assert_eq!(field(GaConfig { tournament_size: Share::Count(0), ..GaConfig::default() }), "tournament_size");
+        assert_eq!(field(GaConfig { tournament_size: Share::Count(7), ..GaConfig::default() }), "tournament_size");
+        assert_eq!(field(GaConfig { tournament_size: Share::Fraction(0.0), ..GaConfig::default() }), "tournament_size");
+        assert_eq!(field(GaConfig { fitness_temperature: 0.0, ..GaConfig::default() }), "fitness_temperature");
+        assert_eq!(field(GaConfig { exact_match_bonus: -1.0, ..GaConfig::default() }), "exact_match_bonus");
+        assert_eq!(field(GaConfig { immigrant_fraction: 0.2, ..GaConfig::default() }), "immigrant_fraction");
+        assert_eq!(field(GaConfig { max_cell_generations: Some(10), ..GaConfig::default() }), "max_cell_generations");
+        assert_eq!(field(GaConfig { immigrant_fraction: 1.5, patience: Some(5), ..GaConfig::default() }), "immigrant_fraction");
+        let warm_start = Some(PathBuf::from("previous.png"));
+        let warm_init = GaConfig { warm_start: warm_start.clone(), init: InitStrategy::FromTarget { noise: 0.1 }, ..GaConfig::default() };
+        assert_eq!(field(warm_init), "warm_start");
+        assert_eq!(field(GaConfig { warm_start, pyramid_levels: 2, ..GaConfig::default() }), "warm_start");
+
+        let error = GaConfig { elite_size: Share::Count(7), ..GaConfig::default() }.validate().unwrap_err();
+        assert_eq!(error.to_string(), "invalid elite_size: 7 exceeds the population of 6");
+    }
+
+    #[test]
+    fn crossover_schedule_anneals_independently_of_mutation() {
+        let config = GaConfig { iterations: 11, crossover_schedule: CrossoverSchedule::Linear { start: 0.9, end: 0.4 }, ..GaConfig::default() };
+        assert_eq!(GaConfig::default().crossover_rate_at(7), 0.8);
+        assert_eq!(config.crossover_rate_at(0), 0.9);
+        assert!((config.crossover_rate_at(5) - 0.65).abs() < 1e-12);
+        assert_eq!(config.crossover_rate_at(20), 0.4);
+        assert_eq!(config.mutation_rate_at(10), config.mutation_rate);
+
+        let config = GaConfig { iterations: 3, crossover_schedule: CrossoverSchedule::Exponential { start: 0.8, end: 0.2 }, ..GaConfig::default() };
+        assert!((config.crossover_rate_at(1) - 0.4).abs() < 1e-12);
+    }
+}
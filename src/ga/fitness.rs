@@ -0,0 +1,435 @@
+use image::{GrayImage, Luma, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+use super::chromosome::{color_rmse, fitness_from_rmse, normalized_fitness_from_rmse};
+use super::color::{delta_e_2000, luma, rgb_to_lab};
+
+/// Per-pixel fitness values. `f32` is ample for 8-bit color differences and
+/// keeps pools and caches small; grid-wide totals are still summed in `f64`.
+pub type Fitness = f32;
+
+/// Scores a decoded RGB color against its target pixel; higher is better.
+pub trait FitnessFunction {
+    fn score(&self, val: [u8; 3], target: Rgb<u8>) -> Fitness;
+}
+
+/// Maps an RGB RMSE to a score: `exp(-rmse / temperature)`, multiplied by
+/// `exact_match_bonus` when the RMSE is below `exact_match_threshold`.
+///
+/// The curve is monotonic, so tournament and rank selection (and elitism)
+/// pick the same parents whatever its shape: they only compare scores. It
+/// matters for roulette selection, where parents are drawn in proportion to
+/// their score. A lower temperature makes a few units of error cost more (at
+/// 50 a pixel 10 off still scores 0.82, at 5 it scores 0.14), concentrating
+/// the draws on the closest colors; a higher one flattens the pool toward
+/// uniform picks. The bonus then sets how much an exact match outweighs a
+/// near one. With `gradient_weight`, the temperature also scales the color
+/// term against the gradient term for every selection method.
+///
+/// The bonus makes the score jump at the threshold. A bonus of 1 (or a
+/// threshold of 0) leaves a continuous curve, which is usually the better
+/// choice when analysing selection pressure or comparing scores across
+/// metrics.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FitnessCurve {
+    pub temperature: f64,
+    pub exact_match_bonus: f64,
+    pub exact_match_threshold: f64,
+}
+
+impl Default for FitnessCurve {
+    fn default() -> Self {
+        FitnessCurve { temperature: 50.0, exact_match_bonus: 2.0, exact_match_threshold: 1.0 }
+    }
+}
+
+impl FitnessCurve {
+    pub fn score(self, rmse: Fitness) -> Fitness {
+        let fitness = (-rmse / self.temperature as Fitness).exp();
+
+        if rmse < self.exact_match_threshold as Fitness {
+            fitness * self.exact_match_bonus as Fitness
+        } else {
+            fitness
+        }
+    }
+}
+
+/// The original metric: per-channel RMSE mapped through the default
+/// `FitnessCurve`, `exp(-rmse / 50)` doubled for near-exact matches.
+pub struct RgbRmse;
+
+impl FitnessFunction for RgbRmse {
+    fn score(&self, val: [u8; 3], target: Rgb<u8>) -> Fitness {
+        fitness_from_rmse(color_rmse(val, &target))
+    }
+}
+
+/// `RgbRmse` with per-channel weights, e.g. emphasizing green for perceived
+/// luminance. Weights are normalized by their sum, so equal weights score
+/// exactly like `RgbRmse`.
+pub struct Weighted {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
+impl Weighted {
+    fn rmse(&self, val: [u8; 3], target: Rgb<u8>) -> Fitness {
+        let weights = [self.r, self.g, self.b];
+        let total: f64 = weights.iter().sum();
+        let weighted: f64 = (0..3).map(|c| weights[c] * (val[c] as f64 - target[c] as f64).powi(2)).sum();
+        (weighted / total).sqrt() as Fitness
+    }
+}
+
+impl FitnessFunction for Weighted {
+    fn score(&self, val: [u8; 3], target: Rgb<u8>) -> Fitness {
+        fitness_from_rmse(self.rmse(val, target))
+    }
+}
+
+/// Perceptual distance: Delta-E 2000 in CIELAB mapped through `exp(-dE / 20)`,
+/// doubled below the just-noticeable difference (dE < 1).
+pub struct CieDe2000;
+
+impl FitnessFunction for CieDe2000 {
+    fn score(&self, val: [u8; 3], target: Rgb<u8>) -> Fitness {
+        let delta_e = delta_e_2000(rgb_to_lab(val), rgb_to_lab(target.0)) as Fitness;
+        let fitness = (-delta_e / 20.0).exp();
+
+        if delta_e < 1.0 {
+            fitness * 2.0
+        } else {
+            fitness
+        }
+    }
+}
+
+/// Multiplier applied to the color score in RGBA mode: 1 for a matching
+/// alpha, decaying as `exp(-|da| / 50)`.
+pub fn alpha_fitness(alpha: u8, target: u8) -> Fitness {
+    (-(alpha as Fitness - target as Fitness).abs() / 50.0).exp()
+}
+
+/// How well the steps from `val` to its neighbors match the steps from
+/// `target` to theirs; `neighbors` pairs each candidate neighbor color with
+/// the target neighbor color. The RMSE of the mismatch is mapped through
+/// `exp(-rmse / 50)`, and a pixel without neighbors scores 1.
+pub fn gradient_score(val: [u8; 3], target: [u8; 3], neighbors: &[([u8; 3], [u8; 3])]) -> Fitness {
+    if neighbors.is_empty() {
+        return 1.0;
+    }
+
+    let mut sum = 0.0;
+    for (neighbor, target_neighbor) in neighbors {
+        for c in 0..3 {
+            let diff = (val[c] as Fitness - neighbor[c] as Fitness) - (target[c] as Fitness - target_neighbor[c] as Fitness);
+            sum += diff * diff;
+        }
+    }
+    let rmse = (sum / (neighbors.len() * 3) as Fitness).sqrt();
+    (-rmse / 50.0).exp()
+}
+
+/// Right and bottom neighbors of (x, y) that lie inside a `width` x `height` image.
+pub fn gradient_neighbors(x: u32, y: u32, width: u32, height: u32) -> impl Iterator<Item = (u32, u32)> {
+    [(x + 1, y), (x, y + 1)].into_iter().filter(move |&(nx, ny)| nx < width && ny < height)
+}
+
+/// Gradient score of every pixel of `frame` against `target`, using its right
+/// and bottom neighbors, in row-major order.
+pub fn gradient_map(frame: &RgbImage, target: &RgbImage) -> Vec<Fitness> {
+    let (width, height) = frame.dimensions();
+    frame
+        .enumerate_pixels()
+        .map(|(x, y, pixel)| {
+            let neighbors: Vec<([u8; 3], [u8; 3])> = gradient_neighbors(x, y, width, height)
+                .map(|(nx, ny)| (frame.get_pixel(nx, ny).0, target.get_pixel(nx, ny).0))
+                .collect();
+            gradient_score(pixel.0, target.get_pixel(x, y).0, &neighbors)
+        })
+        .collect()
+}
+
+/// Composite fitness of every pixel of `frame`: the color score plus
+/// `gradient_weight` times the gradient score, in row-major order.
+pub fn grid_fitness(frame: &RgbImage, target: &RgbImage, metric: &dyn FitnessFunction, gradient_weight: f64) -> Vec<Fitness> {
+    frame
+        .pixels()
+        .zip(target.pixels())
+        .zip(gradient_map(frame, target))
+        .map(|((pixel, target_pixel), gradient)| metric.score(pixel.0, *target_pixel) + gradient_weight as Fitness * gradient)
+        .collect()
+}
+
+/// Per-pixel match of `frame` to `target` as brightness: white is exact and
+/// black the largest possible RMSE, so dark regions are where the GA fell short.
+pub fn fitness_map(frame: &RgbImage, target: &RgbImage) -> GrayImage {
+    GrayImage::from_fn(frame.width(), frame.height(), |x, y| {
+        let rmse = color_rmse(frame.get_pixel(x, y).0, target.get_pixel(x, y));
+        Luma([(normalized_fitness_from_rmse(rmse) * 255.0).round() as u8])
+    })
+}
+
+const SSIM_WINDOW: usize = 7;
+
+/// Mean structural similarity of the luma of `a` and `b` over every 7x7
+/// window (or the largest square that fits); 1 means identical.
+pub fn ssim(a: &RgbImage, b: &RgbImage) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let (width, height) = (a.width() as usize, a.height() as usize);
+    let win = SSIM_WINDOW.min(width).min(height);
+    if win == 0 {
+        return 1.0;
+    }
+
+    // Summed-area tables of x, y, x^2, y^2 and xy with a zero first row and column.
+    let stride = width + 1;
+    let mut sums = vec![[0.0f64; 5]; stride * (height + 1)];
+    for (x, y, pixel) in a.enumerate_pixels() {
+        let (x, y) = (x as usize, y as usize);
+        let p = luma(pixel.0) as f64;
+        let q = luma(b.get_pixel(x as u32, y as u32).0) as f64;
+        let (up, left, diagonal) = (sums[y * stride + x + 1], sums[(y + 1) * stride + x], sums[y * stride + x]);
+        let values = [p, q, p * p, q * q, p * q];
+        sums[(y + 1) * stride + x + 1] = std::array::from_fn(|k| values[k] + up[k] + left[k] - diagonal[k]);
+    }
+
+    let n = (win * win) as f64;
+    let mut total = 0.0;
+    for y in 0..=height - win {
+        for x in 0..=width - win {
+            let sum = |k: usize| {
+                sums[(y + win) * stride + x + win][k] - sums[y * stride + x + win][k] - sums[(y + win) * stride + x][k]
+                    + sums[y * stride + x][k]
+            };
+            let (mean_a, mean_b) = (sum(0) / n, sum(1) / n);
+            let var_a = sum(2) / n - mean_a * mean_a;
+            let var_b = sum(3) / n - mean_b * mean_b;
+            let covariance = sum(4) / n - mean_a * mean_b;
+            total += (2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2)
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+        }
+    }
+    total / ((width - win + 1) * (height - win + 1)) as f64
+}
+
+/// Quality of a finished image under every built-in metric at once, whatever
+/// metric drove the run. RMSEs are in 0-255 units and averaged per pixel;
+/// `psnr` is in dB over all channels, infinite for an exact copy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QualityReport {
+    pub rgb_rmse: f64,
+    pub max_rgb_rmse: f64,
+    pub psnr: f64,
+    /// Mean and largest CIEDE2000 color difference.
+    pub delta_e: f64,
+    pub max_delta_e: f64,
+    pub ssim: f64,
+    /// Mean per-pixel score under `RgbRmse` and `CieDe2000`, as those
+    /// metrics would have reported the run's average fitness.
+    pub rgb_fitness: f64,
+    pub ciede2000_fitness: f64,
+}
+
+/// Scores `frame` against `target`, both the same size, under every metric
+/// in `QualityReport`.
+pub fn quality_report(frame: &RgbImage, target: &RgbImage) -> QualityReport {
+    let pixels = (frame.width() * frame.height()).max(1) as f64;
+    let mean = |values: &[Fitness]| values.iter().map(|&v| v as f64).sum::<f64>() / pixels;
+    let largest = |values: &[f64]| values.iter().copied().fold(0.0, f64::max);
+
+    let rmse: Vec<f64> = frame.pixels().zip(target.pixels()).map(|(pixel, target)| color_rmse(pixel.0, target) as f64).collect();
+    let delta_e: Vec<f64> = frame.pixels().zip(target.pixels()).map(|(pixel, target)| delta_e_2000(rgb_to_lab(pixel.0), rgb_to_lab(target.0))).collect();
+    let mse = rmse.iter().map(|rmse| rmse * rmse).sum::<f64>() / pixels;
+    QualityReport {
+        rgb_rmse: rmse.iter().sum::<f64>() / pixels,
+        max_rgb_rmse: largest(&rmse),
+        psnr: 10.0 * (255.0 * 255.0 / mse).log10(),
+        delta_e: delta_e.iter().sum::<f64>() / pixels,
+        max_delta_e: largest(&delta_e),
+        ssim: ssim(frame, target),
+        rgb_fitness: mean(&grid_fitness(frame, target, &RgbRmse, 0.0)),
+        ciede2000_fitness: mean(&grid_fitness(frame, target, &CieDe2000, 0.0)),
+    }
+}
+
+/// A caller-supplied score for library users; higher is better.
+#[derive(Clone)]
+pub struct Custom(pub Arc<dyn Fn([u8; 3], Rgb<u8>) -> f64 + Send + Sync>);
+
+impl FitnessFunction for Custom {
+    fn score(&self, val: [u8; 3], target: Rgb<u8>) -> Fitness {
+        (self.0)(val, target) as Fitness
+    }
+}
+
+impl fmt::Debug for Custom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Custom(..)")
+    }
+}
+
+// Two custom metrics are equal only if they share the same closure.
+impl PartialEq for Custom {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// The metric each cell scores with, chosen at runtime. `Custom` cannot be
+/// saved in a checkpoint.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FitnessMetric {
+    RgbRmse,
+    CieDe2000,
+    Weighted { r: f64, g: f64, b: f64 },
+    #[serde(skip)]
+    Custom(Custom),
+}
+
+impl FitnessMetric {
+    pub fn custom(score: impl Fn([u8; 3], Rgb<u8>) -> f64 + Send + Sync + 'static) -> Self {
+        FitnessMetric::Custom(Custom(Arc::new(score)))
+    }
+
+    /// Scores the RMSE metrics through `curve` instead of the default one.
+    /// `CieDe2000` and `Custom` keep their own scale and ignore it.
+    pub fn score_with(&self, val: [u8; 3], target: Rgb<u8>, curve: FitnessCurve) -> Fitness {
+        match self {
+            FitnessMetric::RgbRmse => curve.score(color_rmse(val, &target)),
+            FitnessMetric::CieDe2000 => CieDe2000.score(val, target),
+            &FitnessMetric::Weighted { r, g, b } => curve.score(Weighted { r, g, b }.rmse(val, target)),
+            FitnessMetric::Custom(custom) => custom.score(val, target),
+        }
+    }
+
+    pub fn with_curve(&self, curve: FitnessCurve) -> CurvedMetric<'_> {
+        CurvedMetric { metric: self, curve }
+    }
+}
+
+impl FitnessFunction for FitnessMetric {
+    fn score(&self, val: [u8; 3], target: Rgb<u8>) -> Fitness {
+        self.score_with(val, target, FitnessCurve::default())
+    }
+}
+
+/// A metric paired with the curve its RMSE is scored through.
+pub struct CurvedMetric<'a> {
+    pub metric: &'a FitnessMetric,
+    pub curve: FitnessCurve,
+}
+
+impl FitnessFunction for CurvedMetric<'_> {
+    fn score(&self, val: [u8; 3], target: Rgb<u8>) -> Fitness {
+        self.metric.score_with(val, target, self.curve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_term_rewards_matching_steps_not_colors() {
+        let target = RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8 * 50, y as u8 * 50, 100]));
+        assert!(gradient_map(&target, &target).iter().all(|&g| g == 1.0));
+
+        // A uniformly shifted image has every step right but every color wrong.
+        let shifted = RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8 * 50 + 30, y as u8 * 50 + 30, 130]));
+        assert!(gradient_map(&shifted, &target).iter().all(|&g| g == 1.0));
+
+        let flat = RgbImage::from_pixel(3, 2, Rgb([50, 50, 100]));
+        let flat_map = gradient_map(&flat, &target);
+        assert!(flat_map[..5].iter().all(|&g| g < 1.0));
+        assert_eq!(flat_map[5], 1.0);
+
+        let color_only = grid_fitness(&shifted, &target, &RgbRmse, 0.0);
+        let with_gradient = grid_fitness(&shifted, &target, &RgbRmse, 0.5);
+        for (a, b) in color_only.iter().zip(&with_gradient) {
+            assert!((b - a - 0.5).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn equal_weights_match_rgb_rmse_and_custom_closures_are_called() {
+        let (val, target) = ([10, 200, 30], Rgb([40, 180, 90]));
+        assert_eq!(Weighted { r: 2.0, g: 2.0, b: 2.0 }.score(val, target), RgbRmse.score(val, target));
+        let green = Weighted { r: 0.0, g: 1.0, b: 0.0 };
+        assert!(green.score(val, target) > RgbRmse.score(val, target));
+        assert_eq!(green.score([0, 180, 0], target), green.score([255, 180, 255], target));
+
+        let metric = FitnessMetric::custom(|val, target| (val[0] == target[0]) as u8 as f64);
+        assert_eq!(metric.score([40, 0, 0], target), 1.0);
+        assert_eq!(metric.score(val, target), 0.0);
+    }
+
+    #[test]
+    fn fitness_map_is_white_for_exact_pixels_and_black_for_opposite_ones() {
+        let target = RgbImage::from_fn(2, 1, |x, _| if x == 0 { Rgb([0, 0, 0]) } else { Rgb([90, 90, 90]) });
+        let frame = RgbImage::from_fn(2, 1, |x, _| if x == 0 { Rgb([255, 255, 255]) } else { Rgb([90, 90, 90]) });
+        assert_eq!(fitness_map(&frame, &target).into_raw(), [0, 255]);
+    }
+
+    #[test]
+    fn ssim_is_one_for_identical_images_and_drops_with_structure_loss() {
+        let target = RgbImage::from_fn(12, 10, |x, y| Rgb([(x * 20) as u8, (y * 25) as u8, ((x + y) * 10) as u8]));
+        assert!((ssim(&target, &target) - 1.0).abs() < 1e-9);
+
+        let blurred = image::imageops::blur(&target, 2.0);
+        let flat = RgbImage::from_pixel(12, 10, Rgb([110, 110, 110]));
+        let (blur_score, flat_score) = (ssim(&blurred, &target), ssim(&flat, &target));
+        assert!(blur_score < 1.0);
+        assert!(flat_score < blur_score);
+    }
+
+    #[test]
+    fn default_curve_matches_the_original_fitness_and_temperature_sets_the_falloff() {
+        let curve = FitnessCurve::default();
+        for rmse in [0.0, 0.5, 1.0, 10.0, 80.0] {
+            assert_eq!(curve.score(rmse), fitness_from_rmse(rmse));
+        }
+        assert_eq!(curve.score(0.5), 2.0 * (-0.5f32 / 50.0).exp());
+
+        let (val, target) = ([10, 20, 30], Rgb([20, 30, 40]));
+        let sharp = FitnessCurve { temperature: 5.0, exact_match_bonus: 1.0, ..curve };
+        assert!(FitnessMetric::RgbRmse.score_with(val, target, sharp) < FitnessMetric::RgbRmse.score(val, target));
+        assert_eq!(FitnessMetric::RgbRmse.with_curve(sharp).score(target.0, target), 1.0);
+        assert_eq!(FitnessMetric::CieDe2000.score_with(val, target, sharp), CieDe2000.score(val, target));
+    }
+
+    #[test]
+    fn exact_match_bonus_applies_below_the_threshold_and_can_be_disabled() {
+        let wide = FitnessCurve { exact_match_bonus: 3.0, exact_match_threshold: 4.0, ..FitnessCurve::default() };
+        assert_eq!(wide.score(3.5), 3.0 * (-3.5f32 / 50.0).exp());
+        assert_eq!(wide.score(4.0), (-4.0f32 / 50.0).exp());
+
+        let continuous = FitnessCurve { exact_match_bonus: 1.0, ..FitnessCurve::default() };
+        assert!(continuous.score(0.999) - continuous.score(1.0) < 0.001);
+        assert_eq!(continuous.score(0.0), 1.0);
+        assert_eq!(FitnessCurve { exact_match_threshold: 0.0, ..FitnessCurve::default() }.score(0.0), 1.0);
+    }
+
+    #[test]
+    fn quality_report_scores_every_metric_regardless_of_the_driver() {
+        let target = RgbImage::from_fn(8, 8, |x, y| Rgb([x as u8 * 30, y as u8 * 30, 90]));
+        let exact = quality_report(&target, &target);
+        assert_eq!((exact.rgb_rmse, exact.delta_e, exact.ssim), (0.0, 0.0, 1.0));
+        assert!(exact.psnr.is_infinite());
+        assert_eq!((exact.rgb_fitness, exact.ciede2000_fitness), (2.0, 2.0));
+
+        let mut frame = target.clone();
+        frame.put_pixel(3, 3, Rgb([255, 255, 255]));
+        let report = quality_report(&frame, &target);
+        let rmse = color_rmse([255, 255, 255], target.get_pixel(3, 3)) as f64;
+        assert!((report.max_rgb_rmse - rmse).abs() < 1e-4);
+        assert!((report.rgb_rmse - rmse / 64.0).abs() < 1e-4);
+        assert!(report.psnr.is_finite() && report.max_delta_e > report.delta_e && report.ssim < 1.0);
+        assert!(report.rgb_fitness < 2.0 && report.ciede2000_fitness < 2.0);
+    }
+}
@@ -0,0 +1,669 @@
+#[cfg(feature = "native")]
+use image::codecs::gif::GifDecoder;
+#[cfg(feature = "native")]
+use image::codecs::png::PngDecoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Pixel, Rgb, RgbImage, RgbaImage};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+#[cfg(feature = "native")]
+use image::{AnimationDecoder, ColorType, ImageFormat};
+use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::fs::File;
+#[cfg(feature = "native")]
+use std::io::{BufReader, Cursor, Read};
+#[cfg(feature = "native")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "native")]
+use std::sync::OnceLock;
+
+use super::color::{rgb_to_ycbcr, ycbcr_to_rgb};
+use super::config::GaConfig;
+
+pub const DIFF_AMPLIFICATION: u32 = 4;
+
+/// The path that stands for stdin as an input and stdout as an output.
+pub const STDIO_PATH: &str = "-";
+
+pub type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
+
+/// How a target whose aspect ratio differs from the configured size is fitted.
+/// `Letterbox` pads with black bars to the full size; `Fit` shrinks the grid
+/// to the scaled image instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AspectMode {
+    Stretch,
+    Letterbox,
+    Fit,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LabelColor {
+    Auto,
+    Fixed([u8; 3]),
+}
+
+pub fn quantize_image(img: &mut RgbImage, levels: u32) {
+    let steps = (levels - 1) as f64;
+    for pixel in img.pixels_mut() {
+        for c in pixel.0.iter_mut() {
+            *c = ((*c as f64 / 255.0 * steps).round() / steps * 255.0).round() as u8;
+        }
+    }
+}
+
+/// Keeps each target pixel's luminance but takes its chroma from the palette
+/// entry of closest luminance, so the GA reproduces the target's structure
+/// in the palette's colors.
+pub fn recolor_target(target_image: &RgbImage, palette: &[[u8; 3]]) -> RgbImage {
+    let palette: Vec<(f64, f64, f64)> = palette.iter().map(|&c| rgb_to_ycbcr(c)).collect();
+    let mut recolored = target_image.clone();
+
+    for pixel in recolored.pixels_mut() {
+        let (y, _, _) = rgb_to_ycbcr(pixel.0);
+        let &(_, cb, cr) = palette
+            .iter()
+            .min_by(|a, b| (a.0 - y).abs().partial_cmp(&(b.0 - y).abs()).unwrap())
+            .unwrap();
+        *pixel = Rgb(ycbcr_to_rgb(y, cb, cr));
+    }
+
+    recolored
+}
+
+/// Median-cut quantization: repeatedly splits the color box with the widest
+/// channel range at its pixel-weighted median, then averages each box.
+pub fn median_cut_palette<'a>(pixels: impl IntoIterator<Item = &'a Rgb<u8>>, size: usize) -> Vec<[u8; 3]> {
+    let mut counts: HashMap<[u8; 3], u64> = HashMap::new();
+    for pixel in pixels {
+        *counts.entry(pixel.0).or_insert(0) += 1;
+    }
+
+    let range = |colors: &[([u8; 3], u64)], c: usize| {
+        let (min, max) = colors.iter().fold((255u8, 0u8), |(lo, hi), (color, _)| (lo.min(color[c]), hi.max(color[c])));
+        max.saturating_sub(min)
+    };
+
+    let mut boxes: Vec<Vec<([u8; 3], u64)>> = vec![counts.into_iter().collect()];
+    while boxes.len() < size {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(i, colors)| (i, (0..3).map(|c| (range(colors, c), c)).max().unwrap()))
+            .max_by_key(|&(_, (width, _))| width);
+        let Some((i, (_, channel))) = widest else { break };
+
+        let mut colors = boxes.swap_remove(i);
+        colors.sort_unstable_by_key(|(color, _)| color[channel]);
+        let half = colors.iter().map(|(_, n)| n).sum::<u64>() / 2;
+        let mut seen = 0;
+        let split = colors
+            .iter()
+            .position(|(_, n)| {
+                seen += n;
+                seen > half
+            })
+            .unwrap()
+            .clamp(1, colors.len() - 1);
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .filter(|colors| !colors.is_empty())
+        .map(|colors| {
+            let total: u64 = colors.iter().map(|(_, n)| n).sum();
+            let mut sums = [0u64; 3];
+            for (color, n) in colors {
+                for c in 0..3 {
+                    sums[c] += color[c] as u64 * n;
+                }
+            }
+            sums.map(|sum| ((sum + total / 2) / total) as u8)
+        })
+        .collect()
+}
+
+pub fn nearest_palette_index(palette: &[[u8; 3]], color: [u8; 3]) -> usize {
+    let distance = |entry: &[u8; 3]| -> i32 { (0..3).map(|c| (entry[c] as i32 - color[c] as i32).pow(2)).sum() };
+    (0..palette.len()).min_by_key(|&i| distance(&palette[i])).unwrap_or(0)
+}
+
+pub fn fit_to_size<P: Pixel + 'static>(
+    img: &ImageBuffer<P, Vec<P::Subpixel>>,
+    width: u32,
+    height: u32,
+    aspect: AspectMode,
+    filter: FilterType,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    if img.dimensions() == (width, height) {
+        return img.clone();
+    }
+    if aspect == AspectMode::Stretch {
+        return image::imageops::resize(img, width, height, filter);
+    }
+
+    let scale = (width as f64 / img.width() as f64).min(height as f64 / img.height() as f64);
+    let w = ((img.width() as f64 * scale).round() as u32).clamp(1, width);
+    let h = ((img.height() as f64 * scale).round() as u32).clamp(1, height);
+    let resized = image::imageops::resize(img, w, h, filter);
+    if aspect == AspectMode::Fit {
+        return resized;
+    }
+
+    let mut canvas = ImageBuffer::new(width, height);
+    image::imageops::replace(&mut canvas, &resized, ((width - w) / 2) as i64, ((height - h) / 2) as i64);
+    canvas
+}
+
+/// `img` resized to the grid like `load_target_image`. Grayscale targets are
+/// converted to luma before resizing, then expanded back to RGB with R = G = B.
+pub fn fit_target(img: &DynamicImage, width: u32, height: u32, aspect: AspectMode, filter: FilterType, grayscale: bool, quantize_levels: Option<u32>) -> RgbImage {
+    let mut resized = if grayscale {
+        DynamicImage::ImageLuma8(fit_to_size(&img.to_luma8(), width, height, aspect, filter)).to_rgb8()
+    } else {
+        fit_to_size(&img.to_rgb8(), width, height, aspect, filter)
+    };
+    if let Some(levels) = quantize_levels {
+        quantize_image(&mut resized, levels);
+    }
+    resized
+}
+
+/// `img` with every pixel replaced by its luminance.
+pub fn to_grayscale(img: &RgbImage) -> RgbImage {
+    DynamicImage::ImageLuma8(DynamicImage::ImageRgb8(img.clone()).to_luma8()).to_rgb8()
+}
+
+/// `img` fitted to the grid `config` describes, like a loaded target; with
+/// `keep_native_size` it keeps its own size.
+pub fn target_from_buffer(img: &RgbImage, config: &GaConfig) -> RgbImage {
+    let (width, height) = match config.keep_native_size {
+        true => img.dimensions(),
+        false => (config.width as u32, config.height as u32),
+    };
+    fit_target(&DynamicImage::ImageRgb8(img.clone()), width, height, config.aspect, config.resize_filter, config.grayscale, None)
+}
+
+/// Decodes an encoded image held in memory (any format `image` reads, e.g.
+/// an uploaded PNG), without resizing it.
+pub fn load_target_bytes(bytes: &[u8]) -> Result<RgbImage, Box<dyn std::error::Error>> {
+    Ok(image::load_from_memory(bytes)?.to_rgb8())
+}
+
+/// The bytes of the file at `path`, or of stdin for `STDIO_PATH`. Stdin is
+/// read once and kept, so every loader in a run sees the same image.
+#[cfg(feature = "native")]
+pub fn read_source(path: &str) -> std::io::Result<Vec<u8>> {
+    static STDIN: OnceLock<Vec<u8>> = OnceLock::new();
+    if path != STDIO_PATH {
+        return std::fs::read(path);
+    }
+    if let Some(bytes) = STDIN.get() {
+        return Ok(bytes.clone());
+    }
+    let mut bytes = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut bytes)?;
+    Ok(STDIN.get_or_init(|| bytes).clone())
+}
+
+#[cfg(feature = "native")]
+pub fn load_target_image(path: &str, width: u32, height: u32, aspect: AspectMode, filter: FilterType, grayscale: bool, quantize_levels: Option<u32>) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn std::error::Error>> {
+    Ok(load_target_image_with_color(path, width, height, aspect, filter, grayscale, quantize_levels)?.0)
+}
+
+/// Like `load_target_image`, plus the color type the source decoded to
+/// before it was flattened to RGB, e.g. `L8` for a grayscale PNG. Indexed
+/// PNGs decode to `Rgb8` or `Rgba8`.
+#[cfg(feature = "native")]
+pub fn load_target_image_with_color(path: &str, width: u32, height: u32, aspect: AspectMode, filter: FilterType, grayscale: bool, quantize_levels: Option<u32>) -> Result<(RgbImage, ColorType), Box<dyn std::error::Error>> {
+    let img = image::load_from_memory(&read_source(path)?)?;
+    let color = img.color();
+    Ok((fit_target(&DynamicImage::ImageRgb8(img.to_rgb8()), width, height, aspect, filter, grayscale, quantize_levels), color))
+}
+
+/// Like `load_target_image` but keeps 16 bits per channel.
+#[cfg(feature = "native")]
+pub fn load_target_image16(path: &str, width: u32, height: u32, aspect: AspectMode, filter: FilterType) -> Result<Rgb16Image, Box<dyn std::error::Error>> {
+    let img = image::load_from_memory(&read_source(path)?)?;
+    Ok(fit_to_size(&img.to_rgb16(), width, height, aspect, filter))
+}
+
+/// The top byte of every channel, matching what 16-bit chromosomes decode to.
+pub fn top_byte(img: &Rgb16Image) -> RgbImage {
+    RgbImage::from_fn(img.width(), img.height(), |x, y| Rgb(img.get_pixel(x, y).0.map(|val| (val >> 8) as u8)))
+}
+
+/// `img` scaled to 16 bits per channel (each value times 257).
+pub fn widen(img: &RgbImage) -> Rgb16Image {
+    Rgb16Image::from_fn(img.width(), img.height(), |x, y| Rgb(img.get_pixel(x, y).0.map(|val| val as u16 * 257)))
+}
+
+/// Every frame of an animated GIF or APNG, or every PNG in a directory in file
+/// name order, fitted like `load_target_image`. Still images load as one frame.
+#[cfg(feature = "native")]
+pub fn load_target_sequence(path: &str, width: u32, height: u32, aspect: AspectMode, filter: FilterType, grayscale: bool, quantize_levels: Option<u32>) -> Result<Vec<RgbImage>, Box<dyn std::error::Error>> {
+    let frames: Vec<RgbImage> = if Path::new(path).is_dir() {
+        png_paths(path)?
+            .iter()
+            .map(|p| image::open(p).map(|img| img.to_rgb8()))
+            .collect::<Result<_, _>>()?
+    } else {
+        let reader = BufReader::new(File::open(path)?);
+        let animation = match ImageFormat::from_path(path)? {
+            ImageFormat::Gif => Some(GifDecoder::new(reader)?.into_frames()),
+            ImageFormat::Png => {
+                let decoder = PngDecoder::new(reader)?;
+                decoder.is_apng().then(|| decoder.apng().into_frames())
+            }
+            _ => None,
+        };
+        match animation {
+            Some(frames) => frames
+                .map(|frame| frame.map(|frame| DynamicImage::ImageRgba8(frame.into_buffer()).to_rgb8()))
+                .collect::<Result<_, _>>()?,
+            None => vec![image::open(path)?.to_rgb8()],
+        }
+    };
+
+    if frames.is_empty() {
+        return Err(format!("no target frames found in {}", path).into());
+    }
+
+    Ok(frames
+        .into_iter()
+        .map(|frame| fit_target(&DynamicImage::ImageRgb8(frame), width, height, aspect, filter, grayscale, quantize_levels))
+        .collect())
+}
+
+// PNG files in `dir`, in file name order.
+#[cfg(feature = "native")]
+fn png_paths(dir: &str) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Size of the image at `path`, or of the first frame of a directory
+/// sequence, without decoding it.
+#[cfg(feature = "native")]
+pub fn source_dimensions(path: &str) -> Result<(u32, u32), Box<dyn std::error::Error>> {
+    if path == STDIO_PATH {
+        return Ok(image::io::Reader::new(Cursor::new(read_source(path)?)).with_guessed_format()?.into_dimensions()?);
+    }
+    let first = if Path::new(path).is_dir() {
+        png_paths(path)?.into_iter().next().ok_or_else(|| format!("no target frames found in {}", path))?
+    } else {
+        PathBuf::from(path)
+    };
+    Ok(image::image_dimensions(first)?)
+}
+
+/// The target's alpha plane, fitted like `load_target_image`. Letterbox bars
+/// are transparent; images without alpha load as fully opaque.
+#[cfg(feature = "native")]
+pub fn load_target_alpha(path: &str, width: u32, height: u32, aspect: AspectMode, filter: FilterType) -> Result<GrayImage, Box<dyn std::error::Error>> {
+    Ok(fit_alpha(&image::load_from_memory(&read_source(path)?)?, width, height, aspect, filter))
+}
+
+/// A region-of-interest mask, fitted like `load_target_image`: each pixel's
+/// luminance over 255 is its weight, so black pixels are ignored.
+#[cfg(feature = "native")]
+pub fn load_roi_mask(path: &str, width: u32, height: u32, aspect: AspectMode, filter: FilterType) -> Result<GrayImage, Box<dyn std::error::Error>> {
+    Ok(fit_to_size(&image::open(path)?.to_luma8(), width, height, aspect, filter))
+}
+
+/// The alpha plane of `img`, fitted like `fit_target`.
+pub fn fit_alpha(img: &DynamicImage, width: u32, height: u32, aspect: AspectMode, filter: FilterType) -> GrayImage {
+    let img = img.to_rgba8();
+    let alpha = GrayImage::from_fn(img.width(), img.height(), |x, y| Luma([img.get_pixel(x, y)[3]]));
+    fit_to_size(&alpha, width, height, aspect, filter)
+}
+
+pub fn with_alpha(img: &RgbImage, alpha: &GrayImage) -> RgbaImage {
+    RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b] = img.get_pixel(x, y).0;
+        image::Rgba([r, g, b, alpha.get_pixel(x, y)[0]])
+    })
+}
+
+/// Synthetic targets for `create_sample_image`, from the smooth `Gradient`
+/// to high-frequency `Noise` and `Checkerboard`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SamplePattern {
+    #[default]
+    Gradient,
+    Solid([u8; 3]),
+    /// Uniformly random colors drawn from this seed.
+    Noise(u64),
+    /// Black and white squares of this side.
+    Checkerboard(u32),
+    /// Black and white rings of this width around the center.
+    Circles(u32),
+}
+
+pub fn create_sample_image(pattern: SamplePattern, width: u32, height: u32) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let mut img = RgbImage::new(width, height);
+    let mut rng = ChaCha12Rng::seed_from_u64(match pattern {
+        SamplePattern::Noise(seed) => seed,
+        _ => 0,
+    });
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        *pixel = match pattern {
+            SamplePattern::Gradient => {
+                let r = (x * 255 / width) as u8;
+                let g = (y * 255 / height) as u8;
+                let b = ((x + y) * 255 / (width + height)) as u8;
+                Rgb([r, g, b])
+            }
+            SamplePattern::Solid(color) => Rgb(color),
+            SamplePattern::Noise(_) => Rgb(rng.gen()),
+            SamplePattern::Checkerboard(size) => {
+                let size = size.max(1);
+                Rgb([if (x / size + y / size).is_multiple_of(2) { 255 } else { 0 }; 3])
+            }
+            SamplePattern::Circles(ring) => {
+                let radius = (x as f64 + 0.5 - cx).hypot(y as f64 + 0.5 - cy);
+                Rgb([if ((radius / ring.max(1) as f64) as u32).is_multiple_of(2) { 255 } else { 0 }; 3])
+            }
+        };
+    }
+
+    img
+}
+
+/// One pixel per entry of `first_match` (row-major): the generation it first
+/// matched the target as gray, brighter the later it was out of `generations`,
+/// and red where it never matched.
+pub fn convergence_map(first_match: &[Option<usize>], width: u32, height: u32, generations: usize) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| match first_match[(y * width + x) as usize] {
+        Some(generation) => {
+            let level = (generation as f64 / generations.max(1) as f64 * 255.0).round().min(255.0) as u8;
+            Rgb([level; 3])
+        }
+        None => Rgb([255, 0, 0]),
+    })
+}
+
+pub fn difference_image(frame: &RgbImage, target_image: &RgbImage) -> RgbImage {
+    let mut diff = RgbImage::new(frame.width(), frame.height());
+    for (x, y, pixel) in diff.enumerate_pixels_mut() {
+        let a = frame.get_pixel(x, y);
+        let b = target_image.get_pixel(x, y);
+        for c in 0..3 {
+            pixel[c] = (a[c].abs_diff(b[c]) as u32 * DIFF_AMPLIFICATION).min(255) as u8;
+        }
+    }
+    diff
+}
+
+const DIVIDER_WIDTH: u32 = 2;
+
+/// `target` and `result` side by side, optionally separated by a gray
+/// divider and with `caption` written in a black strip underneath.
+pub fn comparison_image(target: &RgbImage, result: &RgbImage, divider: bool, caption: Option<&str>) -> RgbImage {
+    let (width, height) = (target.width(), target.height());
+    let gap = if divider { DIVIDER_WIDTH } else { 0 };
+    let strip = if caption.is_some() { FONT_HEIGHT + 2 } else { 0 };
+    let mut image = RgbImage::from_fn(width * 2 + gap, height + strip, |x, y| match (x, y) {
+        (_, y) if y >= height => Rgb([0, 0, 0]),
+        (x, y) if x < width => *target.get_pixel(x, y),
+        (x, _) if x < width + gap => Rgb([128, 128, 128]),
+        (x, y) => *result.get_pixel(x - width - gap, y),
+    });
+    if let Some(caption) = caption {
+        draw_text(&mut image, caption, 1, height + 1, Rgb([255, 255, 255]));
+    }
+    image
+}
+
+/// Standard deviation of every pixel's 3x3 neighborhood (clamped at the
+/// edges), averaged over the channels, in row-major order. Flat regions are
+/// near 0; edges and texture score high.
+pub fn local_contrast(img: &RgbImage) -> Vec<f64> {
+    let (width, height) = (img.width() as i64, img.height() as i64);
+    let mut contrast = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0; 3];
+            let mut sum_sq = [0.0; 3];
+            let mut count = 0.0;
+            for ny in (y - 1).max(0)..=(y + 1).min(height - 1) {
+                for nx in (x - 1).max(0)..=(x + 1).min(width - 1) {
+                    let pixel = img.get_pixel(nx as u32, ny as u32);
+                    for c in 0..3 {
+                        sum[c] += pixel[c] as f64;
+                        sum_sq[c] += (pixel[c] as f64).powi(2);
+                    }
+                    count += 1.0;
+                }
+            }
+            let variance: f64 = (0..3).map(|c| (sum_sq[c] / count - (sum[c] / count).powi(2)).max(0.0)).sum();
+            contrast.push((variance / 3.0).sqrt());
+        }
+    }
+    contrast
+}
+
+/// `frame` with every pixel blended by `strength` toward the bilinear
+/// interpolation of the mean colors of the `block_size` blocks around it,
+/// which softens the edges between blocks. 1.0 replaces each block by a
+/// smooth ramp between its neighbors' means; single pixels are unchanged.
+pub fn smooth_blocks(frame: &RgbImage, block_size: u32, strength: f64) -> RgbImage {
+    let (width, height) = frame.dimensions();
+    let (columns, rows) = (width.div_ceil(block_size), height.div_ceil(block_size));
+    let mut sums = vec![([0.0; 3], 0.0); (columns * rows) as usize];
+    for (x, y, pixel) in frame.enumerate_pixels() {
+        let (sum, count) = &mut sums[((y / block_size) * columns + x / block_size) as usize];
+        for c in 0..3 {
+            sum[c] += pixel[c] as f64;
+        }
+        *count += 1.0;
+    }
+    let means: Vec<[f64; 3]> = sums.iter().map(|(sum, count)| sum.map(|total| total / count)).collect();
+
+    // The two blocks whose centers surround pixel `p` on one axis, and how
+    // far it lies from the first toward the second.
+    let axis = |p: u32, blocks: u32| {
+        let t = ((p as f64 + 0.5) / block_size as f64 - 0.5).clamp(0.0, (blocks - 1) as f64);
+        let first = t.floor() as u32;
+        (first, (first + 1).min(blocks - 1), t - first as f64)
+    };
+    RgbImage::from_fn(width, height, |x, y| {
+        let ((x0, x1, fx), (y0, y1, fy)) = (axis(x, columns), axis(y, rows));
+        let mean = |bx: u32, by: u32, c: usize| means[(by * columns + bx) as usize][c];
+        let pixel = frame.get_pixel(x, y);
+        Rgb(std::array::from_fn(|c| {
+            let top = mean(x0, y0, c) * (1.0 - fx) + mean(x1, y0, c) * fx;
+            let bottom = mean(x0, y1, c) * (1.0 - fx) + mean(x1, y1, c) * fx;
+            let smooth = top * (1.0 - fy) + bottom * fy;
+            (pixel[c] as f64 * (1.0 - strength) + smooth * strength).round() as u8
+        }))
+    })
+}
+
+pub fn changed_pixels(a: &RgbImage, b: &RgbImage) -> usize {
+    a.pixels().zip(b.pixels()).filter(|(p, q)| p != q).count()
+}
+
+/// The largest difference between `a` and `b` in any channel of any pixel.
+pub fn max_channel_delta(a: &RgbImage, b: &RgbImage) -> u8 {
+    a.pixels().zip(b.pixels()).flat_map(|(p, q)| (0..3).map(move |c| p[c].abs_diff(q[c]))).max().unwrap_or(0)
+}
+
+const FONT_WIDTH: u32 = 3;
+
+const FONT_HEIGHT: u32 = 5;
+
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'G' => [0b111, 0b100, 0b101, 0b101, 0b111],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        'e' => [0b010, 0b101, 0b111, 0b100, 0b011],
+        'n' => [0b000, 0b110, 0b101, 0b101, 0b101],
+        _ => [0; 5],
+    }
+}
+
+fn draw_text(frame: &mut RgbImage, text: &str, x: u32, y: u32, color: Rgb<u8>) {
+    for (n, c) in text.chars().enumerate() {
+        let left = x + n as u32 * (FONT_WIDTH + 1);
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..FONT_WIDTH {
+                let (px, py) = (left + col, y + row as u32);
+                if bits >> (FONT_WIDTH - 1 - col) & 1 == 1 && px < frame.width() && py < frame.height() {
+                    frame.put_pixel(px, py, color);
+                }
+            }
+        }
+    }
+}
+
+pub fn draw_generation_label(frame: &mut RgbImage, generation: usize, color: LabelColor) {
+    let text = format!("Gen {}", generation);
+    let (x, y) = (1, 1);
+    let width = (text.len() as u32 * (FONT_WIDTH + 1)).min(frame.width().saturating_sub(x));
+    let height = FONT_HEIGHT.min(frame.height().saturating_sub(y));
+
+    // Black and white are exact entries of the GIF palette, so the label
+    // survives quantization unchanged.
+    let color = match color {
+        LabelColor::Fixed(c) => Rgb(c),
+        LabelColor::Auto => {
+            let mut luma_sum = 0.0;
+            for py in y..y + height {
+                for px in x..x + width {
+                    let p = frame.get_pixel(px, py);
+                    luma_sum += 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64;
+                }
+            }
+            let mean = luma_sum / (width * height).max(1) as f64;
+            if mean > 127.0 { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) }
+        }
+    };
+
+    draw_text(frame, &text, x, y, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_bytes_decode_and_fit_to_the_configured_grid() {
+        let source = RgbImage::from_fn(8, 4, |x, y| Rgb([x as u8 * 30, y as u8 * 60, 90]));
+        let mut png = Vec::new();
+        source.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+        assert_eq!(load_target_bytes(&png).unwrap(), source);
+        assert!(load_target_bytes(b"not an image").is_err());
+
+        let config = GaConfig { width: 4, height: 2, resize_filter: FilterType::Nearest, ..GaConfig::default() };
+        assert_eq!(target_from_buffer(&source, &config).dimensions(), (4, 2));
+        let native = GaConfig { keep_native_size: true, ..config };
+        assert_eq!(target_from_buffer(&source, &native), source);
+    }
+
+    #[test]
+    fn loaded_targets_report_the_source_color_type() {
+        let dir = std::env::temp_dir().join(format!("ga-color-type-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (gray, color) = (dir.join("gray.png"), dir.join("color.png"));
+        GrayImage::from_fn(4, 2, |x, _| Luma([x as u8 * 60])).save(&gray).unwrap();
+        RgbImage::from_pixel(4, 2, Rgb([10, 20, 30])).save(&color).unwrap();
+        let load = |path: &Path| load_target_image_with_color(path.to_str().unwrap(), 4, 2, AspectMode::Stretch, FilterType::Nearest, false, None).unwrap();
+        let ((gray_image, gray_type), (_, color_type)) = (load(&gray), load(&color));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((gray_type, color_type), (ColorType::L8, ColorType::Rgb8));
+        assert_eq!(gray_image.get_pixel(3, 1).0, [180; 3]);
+    }
+
+    #[test]
+    fn convergence_map_brightens_with_later_matches_and_marks_misses_red() {
+        let map = convergence_map(&[Some(1), Some(10), None, Some(5)], 2, 2, 10);
+        assert_eq!(map.get_pixel(0, 0).0, [26; 3]);
+        assert_eq!(map.get_pixel(1, 0).0, [255; 3]);
+        assert_eq!(map.get_pixel(0, 1).0, [255, 0, 0]);
+        assert!(map.get_pixel(1, 1)[0] < map.get_pixel(1, 0)[0]);
+    }
+
+    #[test]
+    fn sample_patterns_have_their_defining_structure() {
+        assert_eq!(create_sample_image(SamplePattern::Gradient, 4, 4).get_pixel(0, 0).0, [0, 0, 0]);
+        let solid = create_sample_image(SamplePattern::Solid([9, 8, 7]), 3, 2);
+        assert!(solid.pixels().all(|pixel| pixel.0 == [9, 8, 7]));
+
+        let board = create_sample_image(SamplePattern::Checkerboard(2), 4, 4);
+        assert_eq!([board.get_pixel(1, 1).0, board.get_pixel(2, 1).0, board.get_pixel(2, 2).0], [[255; 3], [0; 3], [255; 3]]);
+
+        let circles = create_sample_image(SamplePattern::Circles(2), 8, 8);
+        assert_eq!((circles.get_pixel(4, 4).0, circles.get_pixel(7, 4).0), ([255; 3], [0; 3]));
+
+        let noise = create_sample_image(SamplePattern::Noise(3), 8, 8);
+        assert_eq!(noise, create_sample_image(SamplePattern::Noise(3), 8, 8));
+        assert_ne!(noise, create_sample_image(SamplePattern::Noise(4), 8, 8));
+    }
+
+    #[test]
+    fn comparison_places_target_and_result_side_by_side() {
+        let target = RgbImage::from_pixel(4, 3, Rgb([200, 10, 10]));
+        let result = RgbImage::from_pixel(4, 3, Rgb([10, 10, 200]));
+
+        let plain = comparison_image(&target, &result, false, None);
+        assert_eq!(plain.dimensions(), (8, 3));
+        assert_eq!((plain.get_pixel(3, 2).0, plain.get_pixel(4, 0).0), ([200, 10, 10], [10, 10, 200]));
+
+        let labeled = comparison_image(&target, &result, true, Some("RMSE 1.5"));
+        assert_eq!(labeled.dimensions(), (8 + DIVIDER_WIDTH, 3 + FONT_HEIGHT + 2));
+        assert_eq!(labeled.get_pixel(4, 0).0, [128, 128, 128]);
+        assert_eq!(labeled.get_pixel(4 + DIVIDER_WIDTH, 0).0, [10, 10, 200]);
+        assert!(labeled.rows().skip(3).flatten().any(|pixel| pixel.0 == [255; 3]));
+    }
+
+    #[test]
+    fn block_smoothing_ramps_across_block_edges() {
+        let frame = RgbImage::from_fn(8, 4, |x, _| Rgb([if x < 4 { 0 } else { 255 }; 3]));
+        assert_eq!(smooth_blocks(&frame, 4, 0.0), frame);
+
+        let row = |img: &RgbImage| (0..8).map(|x| img.get_pixel(x, 2)[0]).collect::<Vec<_>>();
+        assert_eq!(row(&smooth_blocks(&frame, 4, 1.0)), [0, 0, 32, 96, 159, 223, 255, 255]);
+        assert_eq!(row(&smooth_blocks(&frame, 4, 0.5)), [0, 0, 16, 48, 207, 239, 255, 255]);
+
+        let noise = create_sample_image(SamplePattern::Noise(1), 5, 3);
+        assert_eq!(smooth_blocks(&noise, 1, 1.0), noise);
+    }
+
+    #[test]
+    fn frame_differences_count_pixels_and_the_largest_channel_delta() {
+        let a = RgbImage::from_pixel(3, 2, Rgb([100, 100, 100]));
+        let mut b = a.clone();
+        assert_eq!((changed_pixels(&a, &b), max_channel_delta(&a, &b)), (0, 0));
+        b.put_pixel(0, 0, Rgb([100, 90, 100]));
+        b.put_pixel(2, 1, Rgb([130, 100, 99]));
+        assert_eq!((changed_pixels(&a, &b), max_channel_delta(&a, &b)), (2, 30));
+    }
+}
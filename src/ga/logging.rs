@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// Verbosity for the `error!` .. `trace!` macros, in the style of the `log`
+/// crate. Messages above the current level are dropped; info and finer go
+/// to stdout, warnings and errors to stderr.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level != Level::Off && level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Sends every message to stderr from now on, keeping stdout free for data
+/// such as a result image written to `-`.
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::Relaxed);
+}
+
+pub fn stdout_reserved() -> bool {
+    STDOUT_RESERVED.load(Ordering::Relaxed)
+}
+
+/// Parses a level name as used in `RUST_LOG` (case-insensitive).
+pub fn parse_level(name: &str) -> Option<Level> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "off" => Some(Level::Off),
+        "error" => Some(Level::Error),
+        "warn" => Some(Level::Warn),
+        "info" => Some(Level::Info),
+        "debug" => Some(Level::Debug),
+        "trace" => Some(Level::Trace),
+        _ => None,
+    }
+}
+
+/// The level named by `RUST_LOG`, or `default` if it is unset or invalid.
+pub fn level_from_env(default: Level) -> Level {
+    std::env::var("RUST_LOG").ok().and_then(|value| parse_level(&value)).unwrap_or(default)
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {{
+        let level = $level;
+        if $crate::ga::logging::enabled(level) {
+            if level <= $crate::ga::logging::Level::Warn || $crate::ga::logging::stdout_reserved() {
+                eprintln!($($arg)*);
+            } else {
+                println!($($arg)*);
+            }
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::log!($crate::ga::logging::Level::Error, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => { $crate::log!($crate::ga::logging::Level::Warn, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::log!($crate::ga::logging::Level::Info, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::log!($crate::ga::logging::Level::Debug, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => { $crate::log!($crate::ga::logging::Level::Trace, $($arg)*) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_parse_case_insensitively_and_order_by_verbosity() {
+        assert_eq!(parse_level("WARN"), Some(Level::Warn));
+        assert_eq!(parse_level(" debug "), Some(Level::Debug));
+        assert_eq!(parse_level("loud"), None);
+        assert!(Level::Error < Level::Info && Level::Info < Level::Trace);
+        assert!(!enabled(Level::Off));
+    }
+}
@@ -0,0 +1,16 @@
+pub mod checkpoint;
+pub mod chromosome;
+pub mod color;
+pub mod config;
+pub mod fitness;
+pub mod image;
+pub mod logging;
+#[cfg(feature = "native")]
+pub mod output;
+pub mod population;
+pub mod run;
+pub mod selection;
+
+pub const GENE_LENGTH: usize = 8;
+/// Bits per channel in 16-bit mode.
+pub const DEEP_GENE_LENGTH: usize = 16;
@@ -0,0 +1,755 @@
+use image::{GrayImage, Rgb, RgbImage};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Write};
+use std::process::{Command, Stdio};
+
+use crate::warn;
+
+use super::config::GaConfig;
+use super::fitness::QualityReport;
+use super::image::{draw_generation_label, median_cut_palette, nearest_palette_index, with_alpha, LabelColor};
+use super::run::{AnimationTiming, GenerationStats, Options, VideoFormat};
+use super::GENE_LENGTH;
+
+/// A recorded animation frame and the 1-based generation it shows.
+pub type Frame = (usize, RgbImage);
+
+// Positions in `frames` of at most about `max_frames` evenly spaced frames.
+fn sampled_frames(frames: &[Frame], max_frames: usize) -> impl Iterator<Item = (usize, &RgbImage)> {
+    let step = if frames.len() > max_frames { frames.len() / max_frames.max(1) } else { 1 };
+    // Always end on the final frame, even when the step skips past it.
+    let last = frames.len().saturating_sub(1);
+    let tail = (!last.is_multiple_of(step)).then(|| (last, &frames[last].1));
+    frames.iter().map(|(_, frame)| frame).enumerate().step_by(step).chain(tail)
+}
+
+// Inserts `steps` linear blends between each consecutive pair of frames. A
+// blended frame keeps the index of the frame it starts from.
+fn interpolated_frames<'a>(frames: impl Iterator<Item = (usize, &'a RgbImage)>, steps: usize) -> Vec<(usize, RgbImage)> {
+    let mut out = Vec::new();
+    let mut previous: Option<(usize, &RgbImage)> = None;
+    for (i, frame) in frames {
+        if let Some((previous_index, previous)) = previous {
+            for step in 1..=steps {
+                let t = step as f64 / (steps + 1) as f64;
+                let blended = RgbImage::from_fn(frame.width(), frame.height(), |x, y| {
+                    let (a, b) = (previous.get_pixel(x, y).0, frame.get_pixel(x, y).0);
+                    image::Rgb(std::array::from_fn(|c| (a[c] as f64 + (b[c] as f64 - a[c] as f64) * t).round() as u8))
+                });
+                out.push((previous_index, blended));
+            }
+        }
+        out.push((i, frame.clone()));
+        previous = Some((i, frame));
+    }
+    out
+}
+
+/// `alphas`, when given, holds one alpha plane per frame; fully transparent
+/// pixels map to a reserved transparent palette index. With `dither`, each
+/// frame is Floyd-Steinberg dithered onto the palette instead of snapped to
+/// the nearest colors, trading banding in gradients for noise.
+#[allow(clippy::too_many_arguments)]
+pub fn create_simple_gif_from_frames(frames: &[Frame], alphas: Option<&[GrayImage]>, output_path: &str, scale: u32, counter: Option<LabelColor>, palette_size: usize, dither: bool, timing: AnimationTiming) -> Result<(), Box<dyn std::error::Error>> {
+    if scale == 0 {
+        return Err("GIF scale must be a positive integer".into());
+    }
+    if !(2..=256).contains(&palette_size) {
+        return Err(format!("GIF palette size must be between 2 and 256, got {}", palette_size).into());
+    }
+
+    let (width, height) = frames.first().map_or((1, 1), |(_, frame)| frame.dimensions());
+    if width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err(format!("{}x{} frames exceed the GIF size limit of {} pixels", width, height, u16::MAX).into());
+    }
+
+    let max_scale = u16::MAX as u32 / width.max(height);
+    let scale = if scale > max_scale {
+        warn!("GIF scale {} exceeds the GIF size limit, capping to {}", scale, max_scale);
+        max_scale
+    } else {
+        scale
+    };
+    let (gif_width, gif_height) = ((width * scale) as u16, (height * scale) as u16);
+
+    let labeled: Vec<(RgbImage, Option<&GrayImage>)> = interpolated_frames(sampled_frames(frames, timing.max_frames), timing.interpolation_steps)
+        .into_iter()
+        .map(|(i, mut frame)| {
+            if let Some(color) = counter {
+                draw_generation_label(&mut frame, frames[i].0, color);
+            }
+            (frame, alphas.map(|alphas| &alphas[i]))
+        })
+        .collect();
+
+    let transparent = alphas.is_some();
+    let colors = median_cut_palette(
+        labeled.iter().flat_map(|(frame, _)| frame.pixels()),
+        palette_size - transparent as usize,
+    );
+    let transparent_index = transparent.then_some(colors.len() as u8);
+    let mut palette: Vec<u8> = colors.iter().flatten().copied().collect();
+    while palette.len() < 768 {
+        palette.push(0);
+    }
+
+    let file = File::create(output_path)?;
+    let mut encoder = gif::Encoder::new(BufWriter::new(file), gif_width, gif_height, &palette)?;
+    encoder.set_repeat(timing.loop_count.map_or(gif::Repeat::Infinite, gif::Repeat::Finite))?;
+
+    let mut index_of: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut previous: Option<Vec<u8>> = None;
+
+    for (frame, alpha) in &labeled {
+        let mut index = |color: [u8; 3]| *index_of.entry(color).or_insert_with(|| nearest_palette_index(&colors, color) as u8);
+        let mut indices: Vec<u8> = match dither {
+            true => dithered_indices(frame, &colors, index),
+            false => frame.pixels().map(|pixel| index(pixel.0)).collect(),
+        };
+        if let (Some(alpha), Some(index)) = (alpha, transparent_index) {
+            for (i, a) in indices.iter_mut().zip(alpha.pixels()) {
+                if a[0] == 0 {
+                    *i = index;
+                }
+            }
+        }
+
+        let indices = if scale > 1 {
+            let (w, scale) = (width as usize, scale as usize);
+            (0..gif_height as usize)
+                .flat_map(|y| (0..gif_width as usize).map(move |x| (y / scale) * w + x / scale))
+                .map(|i| indices[i])
+                .collect()
+        } else {
+            indices
+        };
+
+        // Opaque frames only store the rectangle that changed since the previous
+        // one (a single pixel if nothing did) and keep the rest. A pixel cannot
+        // turn transparent that way, so RGBA animations still write whole frames.
+        let region = match &previous {
+            Some(previous) if !transparent => changed_region(previous, &indices, gif_width as usize).or(Some((0, 0, 1, 1))),
+            _ => None,
+        };
+        let mut gif_frame = match region {
+            Some((left, top, w, h)) => {
+                let row = |y: usize| &indices[y * gif_width as usize + left..][..w];
+                let pixels: Vec<u8> = (top..top + h).flat_map(row).copied().collect();
+                let mut gif_frame = gif::Frame::from_indexed_pixels(w as u16, h as u16, pixels, None);
+                (gif_frame.left, gif_frame.top) = (left as u16, top as u16);
+                gif_frame
+            }
+            None => gif::Frame::from_indexed_pixels(gif_width, gif_height, indices.clone(), transparent_index),
+        };
+        gif_frame.delay = timing.frame_delay;
+        gif_frame.dispose = if transparent { gif::DisposalMethod::Background } else { gif::DisposalMethod::Keep };
+        encoder.write_frame(&gif_frame)?;
+        previous = Some(indices);
+    }
+
+    Ok(())
+}
+
+// Floyd-Steinberg: each pixel takes the palette color nearest to its value
+// plus the error carried from earlier pixels, and passes its own error on to
+// the unvisited neighbors (7/16 right, 3/16 below left, 5/16 below, 1/16 below right).
+fn dithered_indices(frame: &RgbImage, colors: &[[u8; 3]], mut index: impl FnMut([u8; 3]) -> u8) -> Vec<u8> {
+    let (width, height) = (frame.width() as usize, frame.height() as usize);
+    let mut values: Vec<[f32; 3]> = frame.pixels().map(|pixel| pixel.0.map(f32::from)).collect();
+    let mut indices = Vec::with_capacity(values.len());
+    for y in 0..height {
+        for x in 0..width {
+            let value = values[y * width + x];
+            let i = index(value.map(|v| v.round().clamp(0.0, 255.0) as u8));
+            let chosen = colors[i as usize];
+            indices.push(i);
+
+            let error: [f32; 3] = std::array::from_fn(|c| value[c] - chosen[c] as f32);
+            for (dx, dy, weight) in [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)] {
+                let (nx, ny) = (x as isize + dx, y + dy);
+                if nx >= 0 && (nx as usize) < width && ny < height {
+                    let neighbor = &mut values[ny * width + nx as usize];
+                    (0..3).for_each(|c| neighbor[c] += error[c] * weight / 16.0);
+                }
+            }
+        }
+    }
+    indices
+}
+
+// Bounding box (left, top, width, height) of the pixels that differ between
+// two frames of row length `width`, or `None` if they are identical.
+fn changed_region(previous: &[u8], next: &[u8], width: usize) -> Option<(usize, usize, usize, usize)> {
+    let changed: Vec<usize> = previous.iter().zip(next).enumerate().filter(|(_, (a, b))| a != b).map(|(i, _)| i).collect();
+    let (xs, ys) = (changed.iter().map(|i| i % width), changed.iter().map(|i| i / width));
+    let (left, right) = (xs.clone().min()?, xs.max()?);
+    let (top, bottom) = (ys.clone().min()?, ys.max()?);
+    Some((left, top, right - left + 1, bottom - top + 1))
+}
+
+/// Writes the sampled frames losslessly as an animated PNG, with the GIF's
+/// frame delay and loop count. With `alphas` (one plane per frame) the frames
+/// are RGBA.
+pub fn create_apng_from_frames(frames: &[Frame], alphas: Option<&[GrayImage]>, output_path: &str, scale: u32, counter: Option<LabelColor>, timing: AnimationTiming) -> Result<(), Box<dyn std::error::Error>> {
+    if scale == 0 {
+        return Err("APNG scale must be a positive integer".into());
+    }
+    let (width, height) = match frames.first() {
+        Some((_, frame)) => (frame.width().checked_mul(scale), frame.height().checked_mul(scale)),
+        None => return Err("no frames to encode".into()),
+    };
+    let (Some(width), Some(height)) = (width, height) else {
+        return Err(format!("APNG scale {} is too large", scale).into());
+    };
+
+    let labeled = interpolated_frames(sampled_frames(frames, timing.max_frames), timing.interpolation_steps);
+    let (color, channels) = if alphas.is_some() { (png::ColorType::Rgba, 4) } else { (png::ColorType::Rgb, 3) };
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(output_path)?), width, height);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    // A GIF loop count is the repetitions after the first play; 0 plays loop forever.
+    encoder.set_animated(labeled.len() as u32, timing.loop_count.map_or(0, |n| n as u32 + 1))?;
+    encoder.set_frame_delay(timing.frame_delay, 100)?;
+    let mut writer = encoder.write_header()?;
+
+    for (i, mut frame) in labeled {
+        if let Some(color) = counter {
+            draw_generation_label(&mut frame, frames[i].0, color);
+        }
+        let data = match alphas {
+            Some(alphas) => with_alpha(&frame, &alphas[i]).into_raw(),
+            None => frame.into_raw(),
+        };
+        let data = match scale {
+            1 => data,
+            _ => {
+                let row = width as usize / scale as usize * channels;
+                let source = |x: u32, y: u32| (y / scale) as usize * row + (x / scale) as usize * channels;
+                (0..height).flat_map(|y| (0..width).flat_map(move |x| (0..channels).map(move |c| source(x, y) + c))).map(|k| data[k]).collect()
+            }
+        };
+        writer.write_image_data(&data)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Pipes the sampled frames as raw RGB into `ffmpeg`, at the GIF's frame rate.
+/// MP4 is H.264 (yuv420p, padded to even dimensions); WebM is lossless VP9.
+pub fn create_video_from_frames(frames: &[Frame], output_path: &str, format: VideoFormat, scale: u32, counter: Option<LabelColor>, timing: AnimationTiming) -> Result<(), Box<dyn std::error::Error>> {
+    if scale == 0 {
+        return Err("video scale must be a positive integer".into());
+    }
+    if timing.frame_delay == 0 {
+        return Err("video frame delay must be positive".into());
+    }
+    let (width, height) = match frames.first() {
+        Some((_, frame)) => (frame.width() * scale, frame.height() * scale),
+        None => return Err("no frames to encode".into()),
+    };
+
+    let codec: &[&str] = match format {
+        VideoFormat::Mp4 => &["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", "-c:v", "libx264", "-crf", "18", "-pix_fmt", "yuv420p"],
+        VideoFormat::WebM => &["-c:v", "libvpx-vp9", "-lossless", "1", "-pix_fmt", "yuv444p"],
+        VideoFormat::Gif | VideoFormat::Apng => return Err("GIF and APNG output is written without ffmpeg".into()),
+    };
+
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+        .args(["-s", &format!("{}x{}", width, height), "-framerate", &format!("100/{}", timing.frame_delay), "-i", "-"])
+        .args(codec)
+        .arg(output_path);
+    let frames = interpolated_frames(sampled_frames(frames, timing.max_frames), timing.interpolation_steps).into_iter().map(|(i, mut frame)| {
+        if let Some(color) = counter {
+            draw_generation_label(&mut frame, frames[i].0, color);
+        }
+        if scale > 1 {
+            frame = image::imageops::resize(&frame, width, height, image::imageops::FilterType::Nearest);
+        }
+        frame
+    });
+    pipe_to_ffmpeg(command, frames)
+}
+
+// Writes `frames` to the stdin of `command` and waits for it. If it exits
+// early, its exit status is reported rather than the broken pipe.
+fn pipe_to_ffmpeg(mut command: Command, frames: impl Iterator<Item = RgbImage>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut ffmpeg = command.stdin(Stdio::piped()).spawn().map_err(|e| format!("could not run ffmpeg: {}", e))?;
+
+    let mut stdin = ffmpeg.stdin.take().ok_or("could not open ffmpeg stdin")?;
+    let mut written = Ok(());
+    for frame in frames {
+        written = stdin.write_all(frame.as_raw());
+        if written.is_err() {
+            break;
+        }
+    }
+    drop(stdin);
+
+    let status = ffmpeg.wait()?;
+    if !status.success() {
+        return Err(format!("ffmpeg exited with {}", status).into());
+    }
+    Ok(written?)
+}
+
+/// Checks that `ffmpeg` runs, so a video run fails before evolving rather
+/// than once every generation is done.
+pub fn check_ffmpeg() -> Result<(), String> {
+    check_program("ffmpeg")
+}
+
+fn check_program(program: &str) -> Result<(), String> {
+    let status = Command::new(program)
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("Video output needs {} on the PATH: {}", program, e))?;
+    if !status.success() {
+        return Err(format!("{} -version exited with {}", program, status));
+    }
+    Ok(())
+}
+
+pub fn create_sprite_sheet(frames: &[Frame], output_path: &str, cols: u32, labels: bool, max_frames: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let tiles: Vec<(usize, &RgbImage)> = sampled_frames(frames, max_frames).collect();
+    if tiles.is_empty() {
+        return Err("no frames to tile".into());
+    }
+
+    let cols = cols.min(tiles.len() as u32);
+    let rows = (tiles.len() as u32).div_ceil(cols);
+    let (width, height) = tiles[0].1.dimensions();
+    let mut sheet = RgbImage::new(cols * width, rows * height);
+
+    for (n, (i, frame)) in tiles.into_iter().enumerate() {
+        let (x, y) = ((n as u32 % cols) * width, (n as u32 / cols) * height);
+        if labels {
+            let mut labeled = frame.clone();
+            draw_generation_label(&mut labeled, frames[i].0, LabelColor::Auto);
+            image::imageops::replace(&mut sheet, &labeled, x as i64, y as i64);
+        } else {
+            image::imageops::replace(&mut sheet, frame, x as i64, y as i64);
+        }
+    }
+
+    sheet.save(output_path)?;
+    Ok(())
+}
+
+pub fn write_stats_csv(stats: &[GenerationStats], output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = BufWriter::new(File::create(output_path)?);
+
+    writeln!(file, "generation,avg_fitness,perfect_matches,match_percent,ssim,avg_rmse,max_rmse")?;
+    for (i, s) in stats.iter().enumerate() {
+        writeln!(file, "{},{:.6},{},{:.4},{:.6},{:.4},{:.4}", i + 1, s.avg_fitness, s.perfect_matches, s.match_percent, s.ssim, s.avg_rmse, s.max_rmse)?;
+    }
+    file.flush()?;
+
+    Ok(())
+}
+
+const FITNESS_PLOT_WIDTH: u32 = 400;
+
+const FITNESS_PLOT_HEIGHT: u32 = 160;
+
+// Sets every pixel on the segment from `a` to `b`.
+fn draw_line(image: &mut RgbImage, a: (f64, f64), b: (f64, f64), color: Rgb<u8>) {
+    let steps = (b.0 - a.0).abs().max((b.1 - a.1).abs()).ceil().max(1.0) as usize;
+    for i in 0..=steps {
+        let t = i as f64 / steps as f64;
+        let (x, y) = ((a.0 + (b.0 - a.0) * t).round(), (a.1 + (b.1 - a.1) * t).round());
+        if x >= 0.0 && y >= 0.0 && (x as u32) < image.width() && (y as u32) < image.height() {
+            image.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+/// Line chart of the grid-average fitness over the generations, scaled so
+/// the highest value plotted reaches the top. With `band`, the range between
+/// the lowest and highest pixel fitness is shaded behind the line.
+pub fn create_fitness_plot(stats: &[GenerationStats], band: bool) -> RgbImage {
+    let (width, height) = (FITNESS_PLOT_WIDTH, FITNESS_PLOT_HEIGHT);
+    let mut plot = RgbImage::from_pixel(width, height, Rgb([17, 17, 17]));
+    draw_line(&mut plot, (0.0, height as f64 - 1.0), (width as f64 - 1.0, height as f64 - 1.0), Rgb([136, 136, 136]));
+    draw_line(&mut plot, (0.0, 0.0), (0.0, height as f64 - 1.0), Rgb([136, 136, 136]));
+
+    let top = stats.iter().map(|s| if band { s.max_fitness } else { s.avg_fitness }).fold(1e-9, f64::max);
+    let x = |i: usize| match stats.len() {
+        1 => 0.0,
+        n => i as f64 * (width - 1) as f64 / (n - 1) as f64,
+    };
+    let y = |value: f64| (height - 1) as f64 * (1.0 - value.max(0.0) / top);
+    if band {
+        for (i, pair) in stats.windows(2).enumerate() {
+            let (a, b) = (&pair[0], &pair[1]);
+            let (x0, x1) = (x(i).round() as u32, x(i + 1).round() as u32);
+            for column in x0..=x1 {
+                let t = if x1 > x0 { (column - x0) as f64 / (x1 - x0) as f64 } else { 0.0 };
+                let low = a.min_fitness + (b.min_fitness - a.min_fitness) * t;
+                let high = a.max_fitness + (b.max_fitness - a.max_fitness) * t;
+                draw_line(&mut plot, (column as f64, y(low)), (column as f64, y(high)), Rgb([38, 77, 40]));
+            }
+        }
+    }
+
+    let points: Vec<(f64, f64)> = stats.iter().enumerate().map(|(i, s)| (x(i), y(s.avg_fitness))).collect();
+    match points.as_slice() {
+        [] => {}
+        [only] => draw_line(&mut plot, *only, (width as f64 - 1.0, only.1), Rgb([76, 175, 80])),
+        points => points.windows(2).for_each(|pair| draw_line(&mut plot, pair[0], pair[1], Rgb([76, 175, 80]))),
+    }
+    plot
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+pub fn write_html_viewer(frames: &[Frame], stats: &[GenerationStats], output_path: &str, max_frames: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let mut encoded_frames = Vec::new();
+    let mut frame_generations = Vec::new();
+
+    for (i, frame) in sampled_frames(frames, max_frames) {
+        let mut png = Cursor::new(Vec::new());
+        frame.write_to(&mut png, image::ImageOutputFormat::Png)?;
+        encoded_frames.push(format!("\"{}\"", encode_base64(png.get_ref())));
+        frame_generations.push(frames[i].0.to_string());
+    }
+
+    let fitness: Vec<String> = stats.iter().map(|s| format!("{:.4}", s.avg_fitness)).collect();
+    let normalized: Vec<String> = stats.iter().map(|s| format!("{:.4}", s.normalized_fitness)).collect();
+    let matches: Vec<String> = stats.iter().map(|s| format!("{:.2}", s.match_percent)).collect();
+
+    let mut file = BufWriter::new(File::create(output_path)?);
+    writeln!(file, "<!DOCTYPE html>")?;
+    writeln!(file, "<html><head><meta charset=\"utf-8\"><title>GA evolution</title><style>")?;
+    writeln!(file, "body{{font-family:sans-serif;background:#1e1e1e;color:#ddd;margin:2em}}")?;
+    writeln!(file, "#frame{{width:400px;image-rendering:pixelated;display:block}}")?;
+    writeln!(file, "#slider{{width:400px}}canvas{{background:#111;display:block;margin-top:1em}}")?;
+    writeln!(file, "</style></head><body>")?;
+    writeln!(file, "<img id=\"frame\" alt=\"best image\">")?;
+    writeln!(file, "<input id=\"slider\" type=\"range\" min=\"0\" max=\"{}\" value=\"{}\">",
+             encoded_frames.len().saturating_sub(1), encoded_frames.len().saturating_sub(1))?;
+    writeln!(file, "<p id=\"label\"></p>")?;
+    writeln!(file, "<canvas id=\"chart\" width=\"400\" height=\"160\"></canvas>")?;
+    writeln!(file, "<p><span style=\"color:#4caf50\">avg fitness</span> / <span style=\"color:#2196f3\">perfect matches %</span></p>")?;
+    writeln!(file, "<script>")?;
+    writeln!(file, "const frames=[{}];", encoded_frames.join(","))?;
+    writeln!(file, "const gens=[{}];", frame_generations.join(","))?;
+    writeln!(file, "const fitness=[{}];", fitness.join(","))?;
+    writeln!(file, "const normalized=[{}];", normalized.join(","))?;
+    writeln!(file, "const matches=[{}];", matches.join(","))?;
+    writeln!(file, r##"const img=document.getElementById("frame"),slider=document.getElementById("slider"),label=document.getElementById("label");
+const chart=document.getElementById("chart"),ctx=chart.getContext("2d");
+const maxFit=Math.max(...fitness,1e-9);
+function line(values,scale,color){{
+  ctx.strokeStyle=color;ctx.beginPath();
+  values.forEach((v,i)=>{{const x=values.length>1?i*(chart.width-1)/(values.length-1):0,y=chart.height-1-v/scale*(chart.height-1);i?ctx.lineTo(x,y):ctx.moveTo(x,y);}});
+  ctx.stroke();
+}}
+function show(i){{
+  const g=gens[i];
+  img.src="data:image/png;base64,"+frames[i];
+  label.textContent="Generation "+g+" - avg fitness "+fitness[g-1]+" (normalized "+normalized[g-1]+") - perfect matches "+matches[g-1]+"%";
+  ctx.clearRect(0,0,chart.width,chart.height);
+  line(fitness,maxFit,"#4caf50");line(matches,100,"#2196f3");
+  const x=fitness.length>1?(g-1)*(chart.width-1)/(fitness.length-1):0;
+  ctx.strokeStyle="#888";ctx.beginPath();ctx.moveTo(x,0);ctx.lineTo(x,chart.height);ctx.stroke();
+}}
+slider.addEventListener("input",()=>show(+slider.value));
+if(frames.length)show(+slider.value);"##)?;
+    writeln!(file, "</script></body></html>")?;
+    file.flush()?;
+
+    Ok(())
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_rates(rates: Option<[f64; 3]>) -> String {
+    rates.map_or("null".to_string(), |[r, g, b]| format!("[{}, {}, {}]", r, g, b))
+}
+
+pub fn write_manifest(artifacts: &[(String, &str)], config: &GaConfig, options: &Options, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = BufWriter::new(File::create(output_path)?);
+
+    writeln!(file, "{{")?;
+    writeln!(file, "  \"artifacts\": [")?;
+    for (n, (path, kind)) in artifacts.iter().enumerate() {
+        let size = std::fs::metadata(path)?.len();
+        let separator = if n + 1 < artifacts.len() { "," } else { "" };
+        writeln!(file, "    {{ \"path\": {}, \"type\": {}, \"size\": {} }}{}",
+                 json_string(path), json_string(kind), size, separator)?;
+    }
+    writeln!(file, "  ],")?;
+    writeln!(file, "  \"seed\": {},", config.seed.map_or("null".to_string(), |seed| seed.to_string()))?;
+    write_config_json(&mut file, config, options)?;
+    writeln!(file, "}}")?;
+    file.flush()?;
+
+    Ok(())
+}
+
+/// Writes `report` as JSON, with the metric that drove the run for reference.
+/// An infinite PSNR (an exact copy) is written as null.
+pub fn write_quality_report(report: &QualityReport, config: &GaConfig, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = BufWriter::new(File::create(output_path)?);
+    let psnr = if report.psnr.is_finite() { report.psnr.to_string() } else { "null".to_string() };
+
+    writeln!(file, "{{")?;
+    writeln!(file, "  \"driving_fitness\": {},", json_string(&format!("{:?}", config.fitness)))?;
+    writeln!(file, "  \"rgb_rmse\": {},", report.rgb_rmse)?;
+    writeln!(file, "  \"max_rgb_rmse\": {},", report.max_rgb_rmse)?;
+    writeln!(file, "  \"psnr\": {},", psnr)?;
+    writeln!(file, "  \"delta_e\": {},", report.delta_e)?;
+    writeln!(file, "  \"max_delta_e\": {},", report.max_delta_e)?;
+    writeln!(file, "  \"ssim\": {},", report.ssim)?;
+    writeln!(file, "  \"rgb_fitness\": {},", report.rgb_fitness)?;
+    writeln!(file, "  \"ciede2000_fitness\": {}", report.ciede2000_fitness)?;
+    writeln!(file, "}}")?;
+    file.flush()?;
+
+    Ok(())
+}
+
+fn write_config_json(file: &mut impl Write, config: &GaConfig, options: &Options) -> std::io::Result<()> {
+    writeln!(file, "  \"config\": {{")?;
+    writeln!(file, "    \"width\": {},", config.width)?;
+    writeln!(file, "    \"height\": {},", config.height)?;
+    writeln!(file, "    \"alpha\": {},", config.alpha)?;
+    writeln!(file, "    \"grayscale\": {},", config.grayscale)?;
+    writeln!(file, "    \"deep_color\": {},", config.deep_color)?;
+    writeln!(file, "    \"aspect\": {},", json_string(&format!("{:?}", config.aspect)))?;
+    writeln!(file, "    \"resize_filter\": {},", json_string(&format!("{:?}", config.resize_filter)))?;
+    writeln!(file, "    \"keep_native_size\": {},", config.keep_native_size)?;
+    writeln!(file, "    \"algorithm\": {},", json_string(&format!("{:?}", config.algorithm)))?;
+    writeln!(file, "    \"block_size\": {},", config.block_size)?;
+    writeln!(file, "    \"population\": {},", json_string(&format!("{:?}", config.population)))?;
+    writeln!(file, "    \"islands\": {},", config.islands)?;
+    writeln!(file, "    \"migration_interval\": {},", config.migration_interval)?;
+    writeln!(file, "    \"iterations\": {},", config.iterations)?;
+    writeln!(file, "    \"pyramid_levels\": {},", config.pyramid_levels)?;
+    writeln!(file, "    \"mutation_rate\": {},", config.mutation_rate)?;
+    writeln!(file, "    \"mutation_schedule\": {},", json_string(&format!("{:?}", config.mutation_schedule)))?;
+    writeln!(file, "    \"extra_flip_rate\": {},", config.extra_flip_rate)?;
+    writeln!(file, "    \"crossover_rate\": {},", config.crossover_rate)?;
+    writeln!(file, "    \"crossover_schedule\": {},", json_string(&format!("{:?}", config.crossover_schedule)))?;
+    writeln!(file, "    \"channel_mutation_rates\": {},", json_rates(config.channel_mutation_rates))?;
+    writeln!(file, "    \"channel_crossover_rates\": {},", json_rates(config.channel_crossover_rates))?;
+    writeln!(file, "    \"encoding\": {},", json_string(&format!("{:?}", config.encoding)))?;
+    writeln!(file, "    \"encoding_space\": {},", json_string(&format!("{:?}", config.encoding_space)))?;
+    writeln!(file, "    \"crossover\": {},", json_string(&format!("{:?}", config.crossover)))?;
+    writeln!(file, "    \"gene_length\": {},", GENE_LENGTH)?;
+    writeln!(file, "    \"selection\": {},", json_string(&format!("{:?}", config.selection)))?;
+    writeln!(file, "    \"tournament_size\": {},", json_string(&format!("{:?}", config.tournament_size)))?;
+    writeln!(file, "    \"elite_size\": {},", json_string(&format!("{:?}", config.elite_size)))?;
+    writeln!(file, "    \"selection_pressure\": {},", config.selection_pressure)?;
+    writeln!(file, "    \"centroid_rate\": {},", config.centroid_rate)?;
+    writeln!(file, "    \"spatial_crossover_rate\": {},", config.spatial_crossover_rate)?;
+    writeln!(file, "    \"freeze_converged\": {},", config.freeze_converged)?;
+    writeln!(file, "    \"max_cell_generations\": {},", config.max_cell_generations.map_or("null".to_string(), |n| n.to_string()))?;
+    writeln!(file, "    \"patience\": {},", config.patience.map_or("null".to_string(), |n| n.to_string()))?;
+    writeln!(file, "    \"min_delta\": {},", config.min_delta)?;
+    writeln!(file, "    \"immigrant_fraction\": {},", config.immigrant_fraction)?;
+    writeln!(file, "    \"diversity_threshold\": {},", config.diversity_threshold.map_or("null".to_string(), |t| t.to_string()))?;
+    writeln!(file, "    \"diversity_patience\": {},", config.diversity_patience)?;
+    writeln!(file, "    \"fitness\": {},", json_string(&format!("{:?}", config.fitness)))?;
+    writeln!(file, "    \"fitness_temperature\": {},", config.fitness_temperature)?;
+    writeln!(file, "    \"exact_match_bonus\": {},", config.exact_match_bonus)?;
+    writeln!(file, "    \"exact_match_threshold\": {},", config.exact_match_threshold)?;
+    writeln!(file, "    \"gradient_weight\": {},", config.gradient_weight)?;
+    let palette = config.palette.as_ref().map_or("null".to_string(), |colors| {
+        let hex: Vec<String> = colors.iter().map(|c| json_string(&format!("{:02x}{:02x}{:02x}", c[0], c[1], c[2]))).collect();
+        format!("[{}]", hex.join(", "))
+    });
+    writeln!(file, "    \"palette\": {},", palette)?;
+    writeln!(file, "    \"mutation\": {},", json_string(&format!("{:?}", config.mutation)))?;
+    writeln!(file, "    \"init\": {},", json_string(&format!("{:?}", config.init)))?;
+    let warm_start = config.warm_start.as_ref().map_or("null".to_string(), |path| json_string(&path.to_string_lossy()));
+    writeln!(file, "    \"warm_start\": {},", warm_start)?;
+    writeln!(file, "    \"threads\": {},", config.threads.map_or("null".to_string(), |n| n.to_string()))?;
+    writeln!(file, "    \"gif_scale\": {},", options.gif_scale)?;
+    writeln!(file, "    \"gif_colors\": {},", options.gif_colors)?;
+    writeln!(file, "    \"dither\": {},", options.dither)?;
+    writeln!(file, "    \"frame_delay\": {},", options.frame_delay)?;
+    writeln!(file, "    \"loop_count\": {},", options.loop_count.map_or("null".to_string(), |n| n.to_string()))?;
+    writeln!(file, "    \"max_frames\": {},", options.max_frames)?;
+    writeln!(file, "    \"interpolation_steps\": {},", options.interpolation_steps)?;
+    writeln!(file, "    \"frame_stride\": {},", options.frame_stride)?;
+    writeln!(file, "    \"snapshot_interval\": {},", options.snapshot_interval.map_or("null".to_string(), |n| n.to_string()))?;
+    writeln!(file, "    \"video\": {},", json_string(options.video.extension()))?;
+    writeln!(file, "    \"max_evaluations\": {},", options.max_evaluations.map_or("null".to_string(), |n| n.to_string()))?;
+    writeln!(file, "    \"blend_hsv\": {},", options.blend_hsv.map_or("null".to_string(), |w| w.to_string()))?;
+    writeln!(file, "    \"block_smoothing\": {},", options.block_smoothing.map_or("null".to_string(), |s| s.to_string()))?;
+    writeln!(file, "    \"quantize_target\": {},", options.quantize_target.map_or("null".to_string(), |n| n.to_string()))?;
+    writeln!(file, "    \"roi_mask\": {}", options.roi_mask.as_deref().map_or("null".to_string(), json_string))?;
+    writeln!(file, "  }}")
+}
+
+pub fn write_sidecar(output_path: &str, source: &str, source_sha256: Option<&str>, config: &GaConfig, options: &Options) -> Result<String, Box<dyn std::error::Error>> {
+    let sidecar_path = format!("{}.meta.json", output_path);
+    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+
+    let mut file = BufWriter::new(File::create(&sidecar_path)?);
+    writeln!(file, "{{")?;
+    writeln!(file, "  \"output\": {},", json_string(output_path))?;
+    writeln!(file, "  \"source\": {},", json_string(source))?;
+    writeln!(file, "  \"source_sha256\": {},", source_sha256.map_or("null".to_string(), json_string))?;
+    writeln!(file, "  \"timestamp\": {},", timestamp)?;
+    writeln!(file, "  \"version\": {},", json_string(env!("CARGO_PKG_VERSION")))?;
+    writeln!(file, "  \"seed\": {},", config.seed.map_or("null".to_string(), |seed| seed.to_string()))?;
+    write_config_json(&mut file, config, options)?;
+    writeln!(file, "}}")?;
+    file.flush()?;
+
+    Ok(sidecar_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_always_keeps_the_final_frame() {
+        let frames: Vec<Frame> = (1..=103).map(|gen| (gen, RgbImage::new(1, 1))).collect();
+        let indices: Vec<usize> = sampled_frames(&frames, 50).map(|(i, _)| i).collect();
+        assert_eq!(indices.first(), Some(&0));
+        assert_eq!(indices.last(), Some(&102));
+        assert_eq!(indices[indices.len() - 2], 100);
+
+        let indices: Vec<usize> = sampled_frames(&frames[..100], 50).map(|(i, _)| i).collect();
+        assert_eq!(indices.len(), 51);
+        assert_eq!(indices.last(), Some(&99));
+    }
+
+    #[test]
+    fn interpolation_blends_linearly_between_sampled_frames() {
+        let frames = [RgbImage::from_pixel(1, 1, image::Rgb([0, 100, 200])), RgbImage::from_pixel(1, 1, image::Rgb([30, 100, 50]))];
+        let blended = interpolated_frames(frames.iter().enumerate(), 2);
+        let indices: Vec<usize> = blended.iter().map(|(i, _)| *i).collect();
+        let pixels: Vec<[u8; 3]> = blended.iter().map(|(_, frame)| frame.get_pixel(0, 0).0).collect();
+        assert_eq!(indices, [0, 0, 0, 1]);
+        assert_eq!(pixels, [[0, 100, 200], [10, 100, 150], [20, 100, 100], [30, 100, 50]]);
+        assert_eq!(interpolated_frames(frames.iter().enumerate(), 0).len(), 2);
+    }
+
+    #[test]
+    fn apng_keeps_every_sampled_frame_in_true_color() {
+        let frames: Vec<Frame> = (1..=3).map(|gen| (gen, RgbImage::from_pixel(2, 1, image::Rgb([gen as u8, 101, 202])))).collect();
+        let timing = AnimationTiming { loop_count: Some(1), ..AnimationTiming::default() };
+        let path = std::env::temp_dir().join(format!("ga-apng-{}.apng", std::process::id()));
+        let path = path.to_str().unwrap();
+        create_apng_from_frames(&frames, None, path, 2, None, timing).unwrap();
+
+        let mut reader = png::Decoder::new(File::open(path).unwrap()).read_info().unwrap();
+        std::fs::remove_file(path).unwrap();
+        let control = reader.info().animation_control.unwrap();
+        assert_eq!((control.num_frames, control.num_plays), (3, 2));
+        assert_eq!((reader.info().width, reader.info().height), (4, 2));
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        for gen in 1..=3 {
+            reader.next_frame(&mut pixels).unwrap();
+            assert!(pixels.chunks(3).all(|pixel| pixel == [gen, 101, 202]));
+        }
+    }
+
+    #[test]
+    fn dithering_mixes_palette_colors_to_match_the_average() {
+        let frame = RgbImage::from_pixel(8, 8, image::Rgb([64, 64, 64]));
+        let colors = [[0, 0, 0], [255, 255, 255]];
+        let index = |color: [u8; 3]| nearest_palette_index(&colors, color) as u8;
+        let indices = dithered_indices(&frame, &colors, index);
+        let white = indices.iter().filter(|&&i| i == 1).count();
+        assert!((12..=20).contains(&white), "{} of 64 pixels white", white);
+        assert!(frame.pixels().all(|pixel| index(pixel.0) == 0));
+    }
+
+    #[test]
+    fn changed_region_bounds_every_differing_pixel() {
+        let previous = [0u8; 12];
+        let mut next = previous;
+        assert_eq!(changed_region(&previous, &next, 4), None);
+        next[5] = 1;
+        next[11] = 2;
+        assert_eq!(changed_region(&previous, &next, 4), Some((1, 1, 3, 2)));
+    }
+
+    #[test]
+    fn fitness_plot_draws_the_average_inside_the_band() {
+        let stats: Vec<GenerationStats> = (0..3)
+            .map(|gen| GenerationStats {
+                avg_fitness: 10.0 * gen as f64,
+                min_fitness: 5.0 * gen as f64,
+                max_fitness: 20.0 * gen as f64,
+                normalized_fitness: 0.0,
+                perfect_matches: 0,
+                match_percent: 0.0,
+                weighted_pixels: 1,
+                ssim: 0.0,
+                avg_rmse: 0.0,
+                max_rmse: 0.0,
+                quantization_rmse: 0.0,
+                frozen_pixels: 0,
+            })
+            .collect();
+        let line = Rgb([76, 175, 80]);
+        let plot = create_fitness_plot(&stats, false);
+        assert_eq!(plot.dimensions(), (FITNESS_PLOT_WIDTH, FITNESS_PLOT_HEIGHT));
+        assert_eq!(*plot.get_pixel(FITNESS_PLOT_WIDTH - 1, 0), line);
+
+        // With the band the scale reaches the highest pixel fitness, so the
+        // final average sits halfway up, above shaded columns.
+        let plot = create_fitness_plot(&stats, true);
+        let middle = ((FITNESS_PLOT_HEIGHT - 1) as f64 / 2.0).round() as u32;
+        assert_eq!(*plot.get_pixel(FITNESS_PLOT_WIDTH - 1, middle), line);
+        assert_eq!(*plot.get_pixel(FITNESS_PLOT_WIDTH - 1, 2), Rgb([38, 77, 40]));
+        assert_eq!(create_fitness_plot(&stats[..1], true).dimensions(), (FITNESS_PLOT_WIDTH, FITNESS_PLOT_HEIGHT));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn encoder_failures_report_the_exit_status() {
+        assert!(check_program("ga-image-no-such-encoder").is_err());
+        assert!(check_program("false").is_err());
+
+        let frames = || (0..8).map(|_| RgbImage::new(256, 256));
+        let early_exit = pipe_to_ffmpeg(Command::new("false"), frames()).unwrap_err().to_string();
+        assert!(early_exit.contains("exited with"), "{}", early_exit);
+
+        let mut drain = Command::new("sh");
+        drain.args(["-c", "cat > /dev/null"]);
+        assert!(pipe_to_ffmpeg(drain, frames()).is_ok());
+    }
+}
@@ -0,0 +1,1046 @@
+use image::{ImageBuffer, Rgb, RgbImage};
+use rand::prelude::*;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+
+use super::chromosome::Chromosome;
+use super::color::ColorSpace;
+use super::config::{Algorithm, GaConfig};
+use super::fitness::{alpha_fitness, gradient_neighbors, gradient_score, Fitness, FitnessCurve, FitnessMetric};
+use super::selection::{RankBased, RouletteWheel, Selection, SelectionMethod, Tournament};
+
+pub const DEFAULT_INIT_NOISE: f64 = 0.1;
+pub const DEFAULT_INIT_BLUR: f32 = 2.0;
+
+/// Work a cell, or a whole run, has done: chromosome colors actually scored,
+/// fitness cache lookups and hits, and diversity restarts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FitnessCounts {
+    pub evaluations: u64,
+    pub cache_lookups: u64,
+    pub cache_hits: u64,
+    pub restarts: u64,
+}
+
+impl std::ops::Add for FitnessCounts {
+    type Output = FitnessCounts;
+
+    fn add(self, other: FitnessCounts) -> FitnessCounts {
+        FitnessCounts {
+            evaluations: self.evaluations + other.evaluations,
+            cache_lookups: self.cache_lookups + other.cache_lookups,
+            cache_hits: self.cache_hits + other.cache_hits,
+            restarts: self.restarts + other.restarts,
+        }
+    }
+}
+
+impl std::iter::Sum for FitnessCounts {
+    fn sum<I: Iterator<Item = FitnessCounts>>(iter: I) -> FitnessCounts {
+        iter.fold(FitnessCounts::default(), |total, counts| total + counts)
+    }
+}
+
+/// How each pixel's initial pool is built. `noise` is the per-bit flip
+/// probability applied to the seed color (0.5 is equivalent to `Random`).
+/// `TargetBlur` seeds from the target after a Gaussian blur of `sigma`
+/// pixels, so the run starts from a recognizable blur.
+#[derive(Clone, Debug)]
+pub enum InitStrategy {
+    Random,
+    FromTarget { noise: f64 },
+    TargetBlur { sigma: f32, noise: f64 },
+    FromImage { path: String, noise: f64 },
+    Solid { color: [u8; 3] },
+}
+
+const FITNESS_CACHE_SIZE: usize = 16;
+
+// Fitness depends only on the decoded color and the cell's target pixel,
+// so chromosomes that decode to the same color share one entry.
+// A pool only holds a handful of distinct colors at a time, so a small
+// ring buffer scanned linearly beats hashing.
+#[derive(Default, Serialize, Deserialize)]
+struct FitnessCache {
+    #[serde(skip)]
+    entries: RefCell<Vec<(u64, Fitness)>>,
+    #[serde(skip)]
+    next: Cell<usize>,
+    // Target pixel the entries were scored against; a new one clears them.
+    #[serde(skip)]
+    target: Cell<Option<[u8; 3]>>,
+    target_alpha: Option<u8>,
+    // 16-bit target color, scored instead of the 8-bit target image in 16-bit mode.
+    target_deep: Option<[u16; 3]>,
+    // Counted since the cell was built or loaded; `restarts` stays 0 here.
+    #[serde(skip)]
+    counts: Cell<FitnessCounts>,
+}
+
+impl FitnessCache {
+    fn fitness(&self, chr: &Chromosome, target: [u8; 3], metric: &FitnessMetric, curve: FitnessCurve) -> Fitness {
+        let [r, g, b, a] = chr.get_rgba();
+        let key = match self.target_deep {
+            Some(_) => {
+                let [r, g, b] = chr.get_deep_val();
+                (r as u64) << 32 | (g as u64) << 16 | b as u64
+            }
+            None => u32::from_be_bytes([a, r, g, b]) as u64,
+        };
+
+        if self.target.get() != Some(target) {
+            self.entries.borrow_mut().clear();
+            self.next.set(0);
+            self.target.set(Some(target));
+        }
+
+        let mut counts = self.counts.get();
+        counts.cache_lookups += 1;
+        if let Some(&(_, fitness)) = self.entries.borrow().iter().find(|(k, _)| *k == key) {
+            counts.cache_hits += 1;
+            self.counts.set(counts);
+            return fitness;
+        }
+        counts.evaluations += 1;
+        self.counts.set(counts);
+
+        let mut fitness = match self.target_deep {
+            Some(target) => chr.get_deep_fitness(target, curve),
+            None => chr.get_fitness_against(Rgb(target), &metric.with_curve(curve)),
+        };
+        if let Some(target_alpha) = self.target_alpha {
+            fitness *= alpha_fitness(a, target_alpha);
+        }
+        let mut entries = self.entries.borrow_mut();
+        if entries.len() < FITNESS_CACHE_SIZE {
+            entries.push((key, fitness));
+        } else {
+            let slot = self.next.get();
+            entries[slot] = (key, fitness);
+            self.next.set((slot + 1) % FITNESS_CACHE_SIZE);
+        }
+        fitness
+    }
+}
+
+/// A genome `Population` can evolve. `Chromosome`, the color of a pixel or
+/// block, is the one the image GA uses; any other type implementing this
+/// trait gets the same selection, elitism, islands and migration.
+pub trait Genome: Clone {
+    /// Two children of `self` and `other`, bred with `config.crossover` with
+    /// probability `rate`, normally `config.crossover_rate_at(generation)`.
+    fn crossover<R: Rng>(&self, other: &Self, config: &GaConfig, rate: f64, rng: &mut R) -> (Self, Self);
+    /// Mutates in place at the per-gene `rate`.
+    fn mutate<R: Rng>(&mut self, config: &GaConfig, rate: f64, rng: &mut R);
+    /// A random genome of the same shape, for diversity restarts.
+    fn random_like<R: Rng>(&self, rng: &mut R) -> Self;
+    /// How many genes differ; the pool's mean is its diversity.
+    fn distance(&self, other: &Self) -> usize;
+    /// The average of `parents`, bred at `config.centroid_rate`.
+    fn centroid(parents: &[Self]) -> Self;
+}
+
+// Scratch space reused by `Population::step` across generations.
+struct StepBuffers<G> {
+    // The previous generation's genomes, overwritten by the next one.
+    spare: Vec<G>,
+    fitness: Vec<Fitness>,
+    order: Vec<usize>,
+}
+
+impl<G> Default for StepBuffers<G> {
+    fn default() -> Self {
+        StepBuffers { spare: Vec::new(), fitness: Vec::new(), order: Vec::new() }
+    }
+}
+
+// Stores `genome` at `pool[index]`, growing the pool by one if needed.
+fn put<G>(pool: &mut Vec<G>, index: usize, genome: G) {
+    match pool.get_mut(index) {
+        Some(slot) => *slot = genome,
+        None => pool.push(genome),
+    }
+}
+
+/// The GA engine: a pool of genomes in equal-sized islands, bred each step by
+/// selection, elitism, crossover and mutation from its own RNG. It knows
+/// nothing about images; `step` takes a function that scores a batch of
+/// genomes into a vector, higher being better.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "G: Serialize", deserialize = "G: Deserialize<'de>"))]
+pub struct Population<G> {
+    pub pool: Vec<G>,
+    // Genomes per island; see `GaConfig::population`.
+    island_size: usize,
+    // Consecutive steps the pool's diversity was below the threshold.
+    low_diversity: usize,
+    // Diversity restarts since the population was built or loaded.
+    #[serde(skip)]
+    restarts: u64,
+    // Genomes from outside the pool that spatial crossover breeds with.
+    #[serde(skip)]
+    donors: Vec<G>,
+    #[serde(skip)]
+    buffers: StepBuffers<G>,
+    rng: RefCell<ChaCha12Rng>,
+}
+
+impl<G: Genome> Population<G> {
+    /// Splits `pool` into islands of `island_size` genomes.
+    pub fn new(pool: Vec<G>, island_size: usize, rng: ChaCha12Rng) -> Self {
+        Population { pool, island_size, low_diversity: 0, restarts: 0, donors: Vec::new(), buffers: StepBuffers::default(), rng: RefCell::new(rng) }
+    }
+
+    pub fn island_size(&self) -> usize {
+        self.island_size
+    }
+
+    // Keeps the first `size` genomes of every island.
+    fn shrink_islands(&mut self, size: usize) {
+        self.pool = self.pool.chunks(self.island_size).flat_map(|island| island[..size].to_vec()).collect();
+        self.island_size = size;
+    }
+
+    /// Sets the genomes spatial crossover picks its second parent from.
+    pub fn set_donors(&mut self, donors: impl IntoIterator<Item = G>) {
+        self.donors.clear();
+        self.donors.extend(donors);
+    }
+
+    pub fn donors(&self) -> &[G] {
+        &self.donors
+    }
+
+    /// How many times `restart_if_collapsed` has restarted the pool.
+    pub fn restarts(&self) -> u64 {
+        self.restarts
+    }
+
+    /// `fitness[i]` must be the fitness of `pool[i]`.
+    pub fn select(&self, fitness: &[Fitness], config: &GaConfig) -> &G {
+        self.select_from(&self.pool, fitness, config)
+    }
+
+    fn select_from<'a>(&self, pool: &'a [G], fitness: &[Fitness], config: &GaConfig) -> &'a G {
+        let mut rng = self.rng.borrow_mut();
+
+        match config.selection {
+            SelectionMethod::Tournament => {
+                Tournament { size: config.tournament_count(self.island_size), pressure: config.selection_pressure }
+                    .select(pool, fitness, &mut *rng)
+            }
+            SelectionMethod::RouletteWheel => RouletteWheel.select(pool, fitness, &mut *rng),
+            SelectionMethod::RankBased => RankBased.select(pool, fitness, &mut *rng),
+        }
+    }
+
+    /// Breeds the next generation of every island, then migrates between
+    /// islands every `config.migration_interval` generations.
+    pub fn step(&mut self, config: &GaConfig, generation: usize, score_batch: impl Fn(&[G], &mut Vec<Fitness>)) {
+        let rates = (config.crossover_rate_at(generation), config.mutation_rate_at(generation));
+        let mut pool = std::mem::take(&mut self.pool);
+        let mut buffers = std::mem::take(&mut self.buffers);
+        let mut next = std::mem::take(&mut buffers.spare);
+        let mut filled = 0;
+        for island in pool.chunks_mut(self.island_size) {
+            filled = self.evolve_island(island, &mut next, filled, &mut buffers, config, rates, &score_batch);
+        }
+        next.truncate(filled);
+
+        if next.len() > self.island_size && (generation + 1).is_multiple_of(config.migration_interval) {
+            self.migrate(&mut next, &score_batch);
+        }
+        buffers.spare = pool;
+        self.buffers = buffers;
+        self.pool = next;
+    }
+
+    /// Mean distance between every pair of genomes in the pool.
+    pub fn diversity(&self) -> f64 {
+        let n = self.pool.len();
+        if n < 2 {
+            return 0.0;
+        }
+
+        let mut total = 0;
+        for (i, a) in self.pool.iter().enumerate() {
+            for b in &self.pool[i + 1..] {
+                total += a.distance(b);
+            }
+        }
+        total as f64 / (n * (n - 1) / 2) as f64
+    }
+
+    /// Replaces every island's non-elites with random genomes once the
+    /// diversity has stayed below `threshold` for `config.diversity_patience`
+    /// calls in a row. A pool that is `solved` is left alone. Each island's
+    /// elites sit at its front after `step`.
+    pub fn restart_if_collapsed(&mut self, config: &GaConfig, threshold: f64, solved: impl FnOnce(&[G]) -> bool) {
+        if self.diversity() >= threshold || solved(&self.pool) {
+            self.low_diversity = 0;
+            return;
+        }
+
+        self.low_diversity += 1;
+        if self.low_diversity < config.diversity_patience {
+            return;
+        }
+
+        let mut rng = self.rng.borrow_mut();
+        for island in self.pool.chunks_mut(self.island_size) {
+            for genome in island.iter_mut().skip(config.elite_count(self.island_size)) {
+                *genome = genome.random_like(&mut *rng);
+            }
+        }
+        self.low_diversity = 0;
+        self.restarts += 1;
+    }
+
+    /// Replaces `fraction` of every island's non-elites, rounded, with random
+    /// genomes. The elites sit at each island's front after `step`, so the
+    /// immigrants take the places at its back.
+    pub fn inject_immigrants(&mut self, config: &GaConfig, fraction: f64) {
+        let elites = config.elite_count(self.island_size);
+        let mut rng = self.rng.borrow_mut();
+        for island in self.pool.chunks_mut(self.island_size) {
+            let non_elites = island.len().saturating_sub(elites);
+            let immigrants = (non_elites as f64 * fraction).round() as usize;
+            for genome in island.iter_mut().rev().take(immigrants) {
+                *genome = genome.random_like(&mut *rng);
+            }
+        }
+    }
+
+    // Sorts `island` best-first in place, then writes the next generation into
+    // `next[start..]`, overwriting recycled genomes where there are any, at
+    // this generation's crossover and mutation `rates`. Returns the end of
+    // what was written.
+    #[allow(clippy::too_many_arguments)]
+    fn evolve_island(
+        &self,
+        island: &mut [G],
+        next: &mut Vec<G>,
+        start: usize,
+        buffers: &mut StepBuffers<G>,
+        config: &GaConfig,
+        (crossover_rate, mutation_rate): (f64, f64),
+        score_batch: &impl Fn(&[G], &mut Vec<Fitness>),
+    ) -> usize {
+        let StepBuffers { fitness, order, .. } = buffers;
+        fitness.clear();
+        score_batch(island, fitness);
+        order.clear();
+        order.extend(0..island.len());
+        order.sort_by(|&a, &b| fitness[b].partial_cmp(&fitness[a]).unwrap());
+        // Apply the permutation in place: position i takes the element at order[i].
+        for i in 0..order.len() {
+            let mut j = order[i];
+            while j < i {
+                j = order[j];
+            }
+            island.swap(i, j);
+            fitness.swap(i, j);
+        }
+
+        let mut end = start;
+
+        let elite_count = config.elite_count(self.island_size).min(island.len());
+        for elite in &island[..elite_count] {
+            match next.get_mut(end) {
+                Some(slot) => slot.clone_from(elite),
+                None => next.push(elite.clone()),
+            }
+            end += 1;
+        }
+
+        while end - start < self.island_size {
+            if config.centroid_rate > 0.0 && elite_count >= 2 && self.rng.borrow_mut().gen::<f64>() < config.centroid_rate {
+                let centroid = G::centroid(&next[start..start + elite_count]);
+                put(next, end, centroid);
+                end += 1;
+                continue;
+            }
+
+            let parent1 = self.select_from(island, fitness, config);
+            let spatial = !self.donors.is_empty()
+                && config.spatial_crossover_rate > 0.0
+                && self.rng.borrow_mut().gen::<f64>() < config.spatial_crossover_rate;
+            let parent2 = if spatial {
+                let donor = self.rng.borrow_mut().gen_range(0..self.donors.len());
+                &self.donors[donor]
+            } else {
+                self.select_from(island, fitness, config)
+            };
+
+            let mut rng = self.rng.borrow_mut();
+
+            let (mut child1, mut child2) = parent1.crossover(parent2, config, crossover_rate, &mut *rng);
+
+            child1.mutate(config, mutation_rate, &mut *rng);
+            child2.mutate(config, mutation_rate, &mut *rng);
+
+            put(next, end, child1);
+            end += 1;
+            if end - start < self.island_size {
+                put(next, end, child2);
+                end += 1;
+            }
+        }
+
+        end
+    }
+
+    // Ring migration: island i's best replaces island i + 1's worst. All
+    // migrants are picked before any island is changed.
+    fn migrate(&self, pool: &mut [G], score_batch: &impl Fn(&[G], &mut Vec<Fitness>)) {
+        let fitness = |genome: &G| {
+            let mut fitness = Vec::with_capacity(1);
+            score_batch(std::slice::from_ref(genome), &mut fitness);
+            fitness[0]
+        };
+        let migrants: Vec<G> = pool
+            .chunks(self.island_size)
+            .map(|island| island.iter().max_by(|a, b| fitness(a).partial_cmp(&fitness(b)).unwrap()).unwrap().clone())
+            .collect();
+
+        let count = migrants.len();
+        for (i, migrant) in migrants.into_iter().enumerate() {
+            let island = &mut pool.chunks_mut(self.island_size).nth((i + 1) % count).unwrap();
+            let worst = (0..island.len()).min_by(|&a, &b| fitness(&island[a]).partial_cmp(&fitness(&island[b])).unwrap()).unwrap();
+            island[worst] = migrant;
+        }
+    }
+}
+
+// How a cell scores chromosomes: against the target pixels of its block,
+// plus the gradient term against the previous frame.
+#[derive(Serialize, Deserialize)]
+struct PixelScorer {
+    // Top-left pixel of the cell's block.
+    pos: (usize, usize),
+    // Rows and columns of pixels the cell covers; (1, 1) outside block mode.
+    block: (usize, usize),
+    cache: FitnessCache,
+    metric: FitnessMetric,
+    curve: FitnessCurve,
+    gradient_weight: f64,
+    // Previous best image, read for neighbors outside the cell's block.
+    #[serde(skip)]
+    neighbors: Option<Arc<RgbImage>>,
+}
+
+impl PixelScorer {
+    fn score(&self, chr: &Chromosome, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Fitness {
+        self.with_target_colors(target_image, |colors| self.fitness(chr, target_image, colors))
+    }
+
+    fn score_batch(&self, chrs: &[Chromosome], target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>, out: &mut Vec<Fitness>) {
+        self.with_target_colors(target_image, |colors| out.extend(chrs.iter().map(|chr| self.fitness(chr, target_image, colors))));
+    }
+
+    fn best<'a>(&self, pool: &'a [Chromosome], target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> &'a Chromosome {
+        self.with_target_colors(target_image, |colors| {
+            pool.iter()
+                .map(|chr| (self.fitness(chr, target_image, colors), chr))
+                .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+                .unwrap()
+                .1
+        })
+    }
+
+    // Calls `f` with the target colors of the cell's block, row-major. Block
+    // rows are read as slices of the image buffer.
+    fn with_target_colors<R>(&self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>, f: impl FnOnce(&[[u8; 3]]) -> R) -> R {
+        let ((i, j), (block_height, block_width)) = (self.pos, self.block);
+        if self.block == (1, 1) {
+            return f(&[target_image.get_pixel(j as u32, i as u32).0]);
+        }
+        let stride = target_image.width() as usize * 3;
+        let raw = target_image.as_raw();
+        let colors: Vec<[u8; 3]> = (i..i + block_height)
+            .flat_map(|y| raw[y * stride + j * 3..][..block_width * 3].chunks_exact(3).map(|pixel| [pixel[0], pixel[1], pixel[2]]))
+            .collect();
+        f(&colors)
+    }
+
+    // `colors` are the block's target colors (`with_target_colors`).
+    fn fitness(&self, chr: &Chromosome, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>, colors: &[[u8; 3]]) -> Fitness {
+        let color = self.color_fitness(chr, colors);
+        match &self.neighbors {
+            Some(frame) if self.gradient_weight > 0.0 => {
+                color + self.gradient_weight as Fitness * self.gradient_fitness(chr, target_image, frame)
+            }
+            _ => color,
+        }
+    }
+
+    // Neighbors inside the block come from the chromosome itself.
+    fn gradient_fitness(&self, chr: &Chromosome, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>, frame: &RgbImage) -> Fitness {
+        let vals = chr.get_block_vals();
+        let (width, height) = target_image.dimensions();
+        let total: Fitness = vals
+            .iter()
+            .enumerate()
+            .map(|(k, &val)| {
+                let (x, y) = ((self.pos.1 + k % self.block.1) as u32, (self.pos.0 + k / self.block.1) as u32);
+                let neighbors: Vec<([u8; 3], [u8; 3])> = gradient_neighbors(x, y, width, height)
+                    .map(|(nx, ny)| {
+                        let (bx, by) = (nx as usize - self.pos.1, ny as usize - self.pos.0);
+                        let neighbor = if bx < self.block.1 && by < self.block.0 {
+                            vals[by * self.block.1 + bx]
+                        } else {
+                            frame.get_pixel(nx, ny).0
+                        };
+                        (neighbor, target_image.get_pixel(nx, ny).0)
+                    })
+                    .collect();
+                gradient_score(val, target_image.get_pixel(x, y).0, &neighbors)
+            })
+            .sum();
+        total / vals.len() as Fitness
+    }
+
+    // A block's fitness is its pixels' mean score, so it stays on the same
+    // scale as a single pixel's.
+    fn color_fitness(&self, chr: &Chromosome, colors: &[[u8; 3]]) -> Fitness {
+        if self.block == (1, 1) {
+            return self.cache.fitness(chr, colors[0], &self.metric, self.curve);
+        }
+
+        let vals = chr.get_block_vals();
+        let mut counts = self.cache.counts.get();
+        counts.evaluations += vals.len() as u64;
+        self.cache.counts.set(counts);
+        let total: Fitness = vals.iter().zip(colors).map(|(&val, &target)| self.metric.score_with(val, Rgb(target), self.curve)).sum();
+        total / vals.len() as Fitness
+    }
+
+    fn matches_target(&self, chr: &Chromosome, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> bool {
+        let alpha_matches = self.cache.target_alpha.is_none_or(|alpha| chr.get_alpha() == alpha);
+        alpha_matches
+            && chr.get_block_vals().iter().enumerate().all(|(k, val)| {
+                let (x, y) = (self.pos.1 + k % self.block.1, self.pos.0 + k / self.block.1);
+                target_image.get_pixel(x as u32, y as u32).0 == *val
+            })
+    }
+}
+
+/// Seed of stream `stream` under the run seed `seed`. Both go through
+/// SplitMix64, so seeds or streams a bit apart, such as neighboring cells
+/// or `--seed 0` and `--seed 1`, get unrelated RNGs.
+pub fn stream_seed(seed: u64, stream: u64) -> u64 {
+    splitmix64(splitmix64(seed) ^ stream)
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// One cell of the image grid: a `Population` of chromosomes for the pixel,
+/// or block of pixels, at `pos`, scored against the target there.
+#[derive(Serialize, Deserialize)]
+pub struct SimpleGA {
+    pub population: Population<Chromosome>,
+    scorer: PixelScorer,
+    frozen: bool,
+}
+
+impl SimpleGA {
+    pub fn new(pos: (usize, usize), config: &GaConfig, reference: &ImageBuffer<Rgb<u8>, Vec<u8>>, space: ColorSpace) -> Self {
+        // Every cell (and the HSV twin of a cell) draws from its own stream.
+        let mut rng = match config.seed {
+            Some(seed) => {
+                let cell = ((space == ColorSpace::Hsv) as u64) << 63 | (pos.0 as u64) << 32 | pos.1 as u64;
+                ChaCha12Rng::seed_from_u64(stream_seed(seed, cell))
+            }
+            None => ChaCha12Rng::from_entropy(),
+        };
+
+        let block = (
+            config.block_size.min(reference.height() as usize - pos.0),
+            config.block_size.min(reference.width() as usize - pos.1),
+        );
+        let pixels = block.0 * block.1;
+        let reference_colors = || -> Vec<[u8; 3]> {
+            (0..pixels)
+                .map(|k| reference.get_pixel((pos.1 + k % block.1) as u32, (pos.0 + k / block.1) as u32).0)
+                .collect()
+        };
+
+        let palette = config.palette.as_ref().map(|colors| Arc::new(colors.iter().map(|c| c.0).collect::<Vec<_>>()));
+        let seeded = |colors: &[[u8; 3]]| match &palette {
+            Some(palette) => Chromosome::from_palette_colors(pos, colors, Arc::clone(palette)),
+            None if config.deep_color => Chromosome::from_deep_color(pos, colors[0].map(|val| val as u16 * 257)),
+            None => Chromosome::from_colors(pos, colors, space),
+        };
+
+        // In RGBA mode, seeded strategies start fully opaque.
+        let population_size = config.population.size(1.0);
+        let pool_size = match config.algorithm {
+            Algorithm::Ga => population_size * config.islands,
+            Algorithm::HillClimb => 1,
+        };
+        let pool = (0..pool_size)
+            .map(|k| match &config.init {
+                // One exact copy per island, which elitism then holds on to.
+                _ if config.warm_start.is_some() => {
+                    let mut chr = seeded(&reference_colors()).with_encoding(config.encoding);
+                    if config.alpha {
+                        chr.push_alpha(255);
+                    }
+                    if k % population_size != 0 {
+                        chr.flip_bits(DEFAULT_INIT_NOISE, &mut rng);
+                    }
+                    chr
+                }
+                InitStrategy::Random => {
+                    let chr = match &palette {
+                        Some(palette) => Chromosome::random_palette(pos, pixels, Arc::clone(palette), &mut rng),
+                        None if config.deep_color => Chromosome::random_deep(pos, &mut rng),
+                        None => Chromosome::random_block(pos, pixels, space, &mut rng),
+                    };
+                    let mut chr = chr.with_encoding(config.encoding);
+                    if config.alpha {
+                        chr.push_alpha(rng.gen());
+                    }
+                    chr
+                }
+                InitStrategy::FromTarget { noise }
+                | InitStrategy::TargetBlur { noise, .. }
+                | InitStrategy::FromImage { noise, .. } => {
+                    let mut chr = seeded(&reference_colors()).with_encoding(config.encoding);
+                    if config.alpha {
+                        chr.push_alpha(255);
+                    }
+                    chr.flip_bits(*noise, &mut rng);
+                    chr
+                }
+                InitStrategy::Solid { color } => {
+                    let mut chr = seeded(&vec![*color; pixels]).with_encoding(config.encoding);
+                    if config.alpha {
+                        chr.push_alpha(255);
+                    }
+                    chr
+                }
+            })
+            .collect();
+
+        SimpleGA {
+            population: Population::new(pool, population_size, rng),
+            scorer: PixelScorer {
+                pos,
+                block,
+                cache: FitnessCache::default(),
+                metric: config.fitness.clone(),
+                curve: config.fitness_curve(),
+                gradient_weight: config.gradient_weight,
+                neighbors: None,
+            },
+            frozen: false,
+        }
+    }
+
+    /// Scores chromosomes on alpha as well, against this cell's target alpha.
+    pub fn with_target_alpha(mut self, alpha: u8) -> Self {
+        self.scorer.cache.target_alpha = Some(alpha);
+        self
+    }
+
+    /// Scores chromosomes against this 16-bit color instead of the 8-bit
+    /// target image, for 16-bit mode.
+    pub fn with_target_deep(mut self, color: [u16; 3]) -> Self {
+        self.scorer.cache.target_deep = Some(color);
+        self
+    }
+
+    /// Shrinks every island to the pool size `config.population` gives a
+    /// cell of `difficulty` in [0, 1]; pools start at the largest size.
+    pub fn with_difficulty(mut self, difficulty: f64, config: &GaConfig) -> Self {
+        let size = config.population.size(difficulty);
+        if config.algorithm == Algorithm::Ga && size < self.population.island_size() {
+            self.population.shrink_islands(size);
+        }
+        self
+    }
+
+    /// Top-left pixel of the cell's block.
+    pub fn pos(&self) -> (usize, usize) {
+        self.scorer.pos
+    }
+
+    pub fn population_size(&self) -> usize {
+        self.population.island_size()
+    }
+
+    pub fn block(&self) -> (usize, usize) {
+        self.scorer.block
+    }
+
+    /// Sets the image the gradient term compares neighboring pixels against,
+    /// normally the previous generation's best image.
+    pub fn set_neighbors(&mut self, frame: Arc<RgbImage>) {
+        self.scorer.neighbors = Some(frame);
+    }
+
+    /// Sets the chromosomes spatial crossover breeds with, normally the
+    /// adjacent cells' current best. Donors of another shape are skipped.
+    pub fn set_donors(&mut self, donors: impl IntoIterator<Item = Chromosome>) {
+        let shape = |chr: &Chromosome| chr.gene.iter().map(|channel| channel.len()).collect::<Vec<_>>();
+        let own_shape = shape(&self.population.pool[0]);
+        let pos = self.scorer.pos;
+        self.population.set_donors(donors.into_iter().filter(|donor| shape(donor) == own_shape).map(|mut donor| {
+            donor.pos = pos;
+            donor
+        }));
+    }
+
+    /// One chromosome's fitness as this cell scores it. Scoring many at once
+    /// is cheaper with `score_batch`.
+    pub fn score(&self, chr: &Chromosome, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Fitness {
+        self.scorer.score(chr, target_image)
+    }
+
+    /// Scores every chromosome in `chrs` into `out`, reading the cell's
+    /// target pixels once for the whole batch rather than once per chromosome.
+    pub fn score_batch(&self, chrs: &[Chromosome], target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>, out: &mut Vec<Fitness>) {
+        self.scorer.score_batch(chrs, target_image, out);
+    }
+
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Fitness work done by this cell since it was built or loaded.
+    pub fn fitness_counts(&self) -> FitnessCounts {
+        FitnessCounts { restarts: self.population.restarts(), ..self.scorer.cache.counts.get() }
+    }
+
+    /// `Population::inject_immigrants` for a cell that is still stepped.
+    pub fn inject_immigrants(&mut self, config: &GaConfig, fraction: f64) {
+        if !self.frozen {
+            self.population.inject_immigrants(config, fraction);
+        }
+    }
+
+    /// Whether the whole pool is one chromosome that matches the target
+    /// exactly and survives the next step (as an elite, or as the hill
+    /// climber's incumbent), so that stepping can no longer change the
+    /// cell's best color.
+    pub fn is_converged(&self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>, config: &GaConfig) -> bool {
+        let pool = &self.population.pool;
+        let keeps_best = config.algorithm == Algorithm::HillClimb || config.elite_count(self.population.island_size()) > 0;
+        keeps_best && pool.iter().all(|chr| chr == &pool[0]) && self.scorer.matches_target(&pool[0], target_image)
+    }
+
+    /// `fitness[i]` must be the fitness of `pool[i]`.
+    pub fn select(&self, fitness: &[Fitness], config: &GaConfig) -> &Chromosome {
+        self.population.select(fitness, config)
+    }
+
+    pub fn get_fitness_stats(&self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> (Fitness, Fitness, Fitness) {
+        let mut fitnesses = Vec::with_capacity(self.population.pool.len());
+        self.score_batch(&self.population.pool, target_image, &mut fitnesses);
+        
+        let avg = fitnesses.iter().sum::<Fitness>() / fitnesses.len() as Fitness;
+        let max = fitnesses.iter().fold(0.0, |a: Fitness, &b| a.max(b));
+        let min = fitnesses.iter().fold(Fitness::INFINITY, |a, &b| a.min(b));
+        
+        (avg, max, min)
+    }
+
+    pub fn step(&mut self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>, config: &GaConfig, generation: usize) {
+        if self.frozen {
+            return;
+        }
+
+        let scorer = &self.scorer;
+        self.population.step(config, generation, |chrs, out| scorer.score_batch(chrs, target_image, out));
+        if let Some(threshold) = config.diversity_threshold {
+            self.population.restart_if_collapsed(config, threshold, |pool| {
+                scorer.matches_target(scorer.best(pool, target_image), target_image)
+            });
+        }
+    }
+
+    /// Mean Hamming distance between every pair of chromosomes in the pool.
+    pub fn diversity(&self) -> f64 {
+        self.population.diversity()
+    }
+
+    /// One (1+1) step: mutate a copy of `pool[0]` and keep it if it scores higher.
+    pub fn hill_climb_step(&mut self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>, config: &GaConfig, generation: usize) {
+        if self.frozen {
+            return;
+        }
+
+        let pool = &mut self.population.pool;
+        let mut candidate = pool[0].clone();
+        candidate.mutate(config, config.mutation_rate_at(generation), &mut *self.population.rng.borrow_mut());
+        if self.scorer.score(&candidate, target_image) > self.scorer.score(&pool[0], target_image) {
+            pool[0] = candidate;
+        }
+    }
+
+    /// Fitness of `get_best`, as the cell scores it.
+    pub fn best_fitness(&self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Fitness {
+        self.score(self.get_best(target_image), target_image)
+    }
+
+    pub fn get_best(&self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> &Chromosome {
+        self.scorer.best(&self.population.pool, target_image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ga::config::{PopulationStrategy, Share};
+
+    // A genome with nothing to do with images: a bit string, scored by how
+    // many of its bits are set.
+    #[derive(Clone)]
+    struct Bits(Vec<bool>);
+
+    impl Bits {
+        fn ones(&self) -> usize {
+            self.0.iter().filter(|&&bit| bit).count()
+        }
+    }
+
+    impl Genome for Bits {
+        fn crossover<R: Rng>(&self, other: &Self, _: &GaConfig, rate: f64, rng: &mut R) -> (Self, Self) {
+            if rng.gen::<f64>() >= rate {
+                return (self.clone(), other.clone());
+            }
+            let cut = rng.gen_range(0..self.0.len());
+            let splice = |a: &Bits, b: &Bits| Bits(a.0[..cut].iter().chain(&b.0[cut..]).copied().collect());
+            (splice(self, other), splice(other, self))
+        }
+
+        fn mutate<R: Rng>(&mut self, _: &GaConfig, rate: f64, rng: &mut R) {
+            for bit in &mut self.0 {
+                *bit ^= rng.gen::<f64>() < rate;
+            }
+        }
+
+        fn random_like<R: Rng>(&self, rng: &mut R) -> Self {
+            Bits(self.0.iter().map(|_| rng.gen()).collect())
+        }
+
+        fn distance(&self, other: &Self) -> usize {
+            self.0.iter().zip(&other.0).filter(|(a, b)| a != b).count()
+        }
+
+        fn centroid(parents: &[Self]) -> Self {
+            parents[0].clone()
+        }
+    }
+
+    #[test]
+    fn population_evolves_genomes_other_than_chromosomes() {
+        let config = GaConfig { mutation_rate: 0.02, ..GaConfig::default() };
+        let mut rng = ChaCha12Rng::seed_from_u64(3);
+        let pool: Vec<Bits> = (0..20).map(|_| Bits(vec![false; 32]).random_like(&mut rng)).collect();
+        let mut population = Population::new(pool, 10, rng);
+        let best = |population: &Population<Bits>| population.pool.iter().map(Bits::ones).max().unwrap();
+        let before = best(&population);
+
+        let score_batch = |genomes: &[Bits], out: &mut Vec<Fitness>| out.extend(genomes.iter().map(|bits| bits.ones() as Fitness));
+        (0..60).for_each(|gen| population.step(&config, gen, score_batch));
+        assert_eq!(population.pool.len(), 20);
+        assert!(best(&population) >= before + 6, "{} -> {}", before, best(&population));
+    }
+
+    #[test]
+    fn warm_start_keeps_an_exact_copy_of_the_previous_result_per_island() {
+        let previous = RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8 * 80, y as u8 * 120, 33]));
+        let config = GaConfig { islands: 2, warm_start: Some("previous.png".into()), seed: Some(5), ..GaConfig::default() };
+        let ga = SimpleGA::new((1, 2), &config, &previous, ColorSpace::Rgb);
+
+        let pool = &ga.population.pool;
+        let island = ga.population.island_size();
+        assert_eq!(pool[0].get_val(), [160, 120, 33]);
+        assert_eq!(pool[island].get_val(), [160, 120, 33]);
+        assert!(pool.iter().any(|chr| chr.get_val() != [160, 120, 33]));
+    }
+
+    #[test]
+    fn batched_scores_match_per_call_scores() {
+        let target = RgbImage::from_fn(7, 5, |x, y| Rgb([x as u8 * 30, y as u8 * 50, 77]));
+        for block_size in [1, 3] {
+            let config = GaConfig { block_size, seed: Some(9), ..GaConfig::default() };
+            // The bottom-right block is clipped to 1x2 pixels.
+            for pos in [(0, 0), (3, 3), (3, 6)] {
+                let ga = SimpleGA::new(pos, &config, &target, ColorSpace::Rgb);
+                let mut batched = Vec::new();
+                ga.score_batch(&ga.population.pool, &target, &mut batched);
+                let per_call: Vec<Fitness> = ga.population.pool.iter().map(|chr| ga.score(chr, &target)).collect();
+                assert_eq!(batched, per_call);
+            }
+        }
+    }
+
+    #[test]
+    fn collapsed_pool_is_restarted_after_patience_generations() {
+        let config = GaConfig {
+            mutation_rate: 0.0,
+            extra_flip_rate: 0.0,
+            init: InitStrategy::Solid { color: [0, 0, 0] },
+            diversity_threshold: Some(1.0),
+            diversity_patience: 2,
+            seed: Some(4),
+            ..GaConfig::default()
+        };
+        let target = RgbImage::from_pixel(1, 1, Rgb([200, 100, 50]));
+        let mut ga = SimpleGA::new((0, 0), &config, &target, ColorSpace::Rgb);
+        assert_eq!(ga.diversity(), 0.0);
+
+        ga.step(&target, &config, 0);
+        assert_eq!(ga.diversity(), 0.0);
+
+        ga.step(&target, &config, 1);
+        assert!(ga.diversity() > 0.0);
+        assert!(ga.population.pool[..config.elite_count(6)].iter().all(|chr| chr.get_val() == [0, 0, 0]));
+    }
+
+    #[test]
+    fn immigrants_replace_a_share_of_each_islands_non_elites() {
+        let config = GaConfig { islands: 2, init: InitStrategy::Solid { color: [0, 0, 0] }, seed: Some(8), ..GaConfig::default() };
+        let target = RgbImage::from_pixel(1, 1, Rgb([200, 100, 50]));
+        let mut ga = SimpleGA::new((0, 0), &config, &target, ColorSpace::Rgb);
+        let elites = config.elite_count(6);
+        ga.inject_immigrants(&config, 0.5);
+
+        let immigrants = ((6 - elites) as f64 * 0.5).round() as usize;
+        for island in ga.population.pool.chunks(6) {
+            let (kept, replaced) = island.split_at(6 - immigrants);
+            assert!(kept.iter().all(|chr| chr.get_val() == [0, 0, 0]));
+            assert!(replaced.iter().all(|chr| chr.get_val() != [0, 0, 0]));
+        }
+
+        let mut frozen = SimpleGA::new((0, 0), &config, &target, ColorSpace::Rgb);
+        frozen.freeze();
+        frozen.inject_immigrants(&config, 1.0);
+        assert!(frozen.population.pool.iter().all(|chr| chr.get_val() == [0, 0, 0]));
+    }
+
+    #[test]
+    fn frozen_cells_keep_their_best_through_steps_and_migration() {
+        let config = GaConfig { islands: 2, migration_interval: 1, seed: Some(2), ..GaConfig::default() };
+        let target = RgbImage::from_pixel(1, 1, Rgb([200, 100, 50]));
+        let mut ga = SimpleGA::new((0, 0), &config, &target, ColorSpace::Rgb);
+        (0..3).for_each(|gen| ga.step(&target, &config, gen));
+        ga.freeze();
+
+        let pool = ga.population.pool.clone();
+        let best = ga.get_best(&target).clone();
+        (3..10).for_each(|gen| ga.step(&target, &config, gen));
+        assert_eq!(ga.population.pool, pool);
+        assert_eq!(ga.get_best(&target), &best);
+    }
+
+    #[test]
+    fn nearby_seeds_and_cells_get_unrelated_streams() {
+        let config = GaConfig { seed: Some(0), ..GaConfig::default() };
+        let target = RgbImage::from_pixel(2, 1, Rgb([90, 90, 90]));
+        let colors = |pos, seed| {
+            let ga = SimpleGA::new(pos, &GaConfig { seed: Some(seed), ..config.clone() }, &target, ColorSpace::Rgb);
+            ga.population.pool.iter().map(Chromosome::to_hex).collect::<Vec<_>>()
+        };
+        assert_ne!(colors((0, 1), 0), colors((0, 0), 1));
+        assert_eq!(colors((0, 1), 0), colors((0, 1), 0));
+        assert_ne!(stream_seed(0, 1), stream_seed(1, 0));
+        assert_ne!(stream_seed(2, 3), stream_seed(3, 2));
+    }
+
+    #[test]
+    fn centroid_offspring_lie_between_the_elites() {
+        let config = GaConfig { centroid_rate: 1.0, seed: Some(3), ..GaConfig::default() };
+        let target = RgbImage::from_pixel(1, 1, Rgb([200, 100, 50]));
+        let mut ga = SimpleGA::new((0, 0), &config, &target, ColorSpace::Rgb);
+        ga.step(&target, &config, 0);
+
+        let (elites, offspring) = ga.population.pool.split_at(config.elite_count(6));
+        let (a, b) = (elites[0].get_val(), elites[1].get_val());
+        assert_ne!(a, b);
+        for child in offspring {
+            let val = child.get_val();
+            assert!((0..3).all(|c| a[c].min(b[c]) <= val[c] && val[c] <= a[c].max(b[c])), "{:?} from {:?} and {:?}", val, a, b);
+        }
+    }
+
+    #[test]
+    fn deep_cells_score_the_low_byte() {
+        let config = GaConfig { deep_color: true, seed: Some(5), ..GaConfig::default() };
+        let target = RgbImage::from_pixel(1, 1, Rgb([0x12, 0x34, 0x56]));
+        let ga = SimpleGA::new((0, 0), &config, &target, ColorSpace::Rgb).with_target_deep([0x1280, 0x3480, 0x5680]);
+        assert!(ga.population.pool.iter().all(|chr| chr.is_deep()));
+
+        // Both decode to the target's 8-bit color; only the low bytes differ.
+        let near = Chromosome::from_deep_color((0, 0), [0x1281, 0x3480, 0x5680]);
+        let far = Chromosome::from_deep_color((0, 0), [0x12ff, 0x3400, 0x56ff]);
+        assert_eq!(near.get_val(), far.get_val());
+        assert!(ga.score(&near, &target) > ga.score(&far, &target));
+    }
+
+    #[test]
+    fn adaptive_islands_shrink_with_difficulty_and_keep_evolving() {
+        let population = PopulationStrategy::Adaptive { min: 2, max: 10 };
+        let config = GaConfig { population, islands: 2, seed: Some(6), ..GaConfig::default() };
+        let target = RgbImage::from_pixel(1, 1, Rgb([10, 20, 30]));
+        let new = || SimpleGA::new((0, 0), &config, &target, ColorSpace::Rgb);
+
+        assert_eq!(new().with_difficulty(1.0, &config).population.pool.len(), 20);
+        let mut easy = new().with_difficulty(0.0, &config);
+        assert_eq!((easy.population_size(), easy.population.pool.len()), (2, 4));
+        easy.step(&target, &config, 0);
+        assert_eq!(easy.population.pool.len(), 4);
+    }
+
+    #[test]
+    fn elite_and_tournament_fractions_scale_with_the_population() {
+        let config = GaConfig { elite_size: Share::Fraction(0.2), tournament_size: Share::Fraction(0.1), ..GaConfig::default() };
+        assert_eq!((config.elite_count(10), config.elite_count(50)), (2, 10));
+        assert_eq!((config.tournament_count(5), config.tournament_count(50)), (1, 5));
+
+        // An all-elite island still leaves one slot for offspring.
+        let config = GaConfig { elite_size: Share::Fraction(1.0), seed: Some(8), ..GaConfig::default() };
+        assert_eq!(config.elite_count(6), 5);
+        let target = RgbImage::from_pixel(1, 1, Rgb([10, 20, 30]));
+        let mut ga = SimpleGA::new((0, 0), &config, &target, ColorSpace::Rgb);
+        let before: Vec<[u8; 3]> = ga.population.pool.iter().map(|chr| chr.get_val()).collect();
+        (0..5).for_each(|gen| ga.step(&target, &config, gen));
+        assert!(ga.population.pool.iter().any(|chr| !before.contains(&chr.get_val())));
+    }
+
+    #[test]
+    fn spatial_crossover_borrows_genes_from_donors() {
+        let config = GaConfig {
+            mutation_rate: 0.0,
+            extra_flip_rate: 0.0,
+            init: InitStrategy::Solid { color: [0, 0, 0] },
+            spatial_crossover_rate: 0.5,
+            seed: Some(9),
+            ..GaConfig::default()
+        };
+        let target = RgbImage::from_pixel(2, 1, Rgb([200, 100, 50]));
+        let mut ga = SimpleGA::new((0, 1), &config, &target, ColorSpace::Rgb);
+        let block = Chromosome::from_colors((0, 0), &[[200, 100, 50]; 2], ColorSpace::Rgb);
+        ga.set_donors([Chromosome::from_color((0, 0), [200, 100, 50], ColorSpace::Rgb), block]);
+        assert_eq!(ga.population.donors().len(), 1);
+        assert_eq!(ga.population.donors()[0].pos, (0, 1));
+
+        // Without mutation, the all-black pool can only improve through the donor.
+        (0..20).for_each(|gen| ga.step(&target, &config, gen));
+        assert_eq!(ga.get_best(&target).get_val(), [200, 100, 50]);
+    }
+}
@@ -0,0 +1,2714 @@
+use image::{imageops, DynamicImage, GrayImage, Luma, Rgb, RgbImage};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+#[cfg(feature = "native")]
+use std::sync::atomic::Ordering;
+#[cfg(feature = "native")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+#[cfg(feature = "native")]
+use image::ColorType;
+#[cfg(feature = "native")]
+use sha2::{Digest, Sha256};
+#[cfg(feature = "native")]
+use std::io::{IsTerminal, Write};
+#[cfg(feature = "native")]
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+#[cfg(feature = "native")]
+use std::time::Instant;
+
+#[cfg(feature = "native")]
+use crate::{debug, info, log, warn};
+
+use super::checkpoint::Checkpoint;
+use super::chromosome::{color_rmse, normalized_fitness_from_rmse, Chromosome, CrossoverKind};
+#[cfg(feature = "native")]
+use super::chromosome::Channel;
+use super::color::{blend_colors, ColorSpace, EncodingSpace};
+use super::config::{Algorithm, GaConfig, PopulationStrategy};
+use super::fitness::{alpha_fitness, FitnessMetric, gradient_map, grid_fitness, ssim, Fitness};
+use super::image::{fit_alpha, fit_target, local_contrast, to_grayscale, widen, with_alpha, LabelColor, Rgb16Image, SamplePattern, STDIO_PATH};
+use super::population::{stream_seed, FitnessCounts, InitStrategy, SimpleGA};
+#[cfg(feature = "native")]
+use super::{
+    checkpoint::{load_checkpoint, save_checkpoint},
+    fitness::{fitness_map, quality_report},
+    image::{
+        changed_pixels, comparison_image, max_channel_delta, convergence_map, create_sample_image, difference_image, load_target_alpha, load_target_image, load_target_image16, load_target_image_with_color, load_target_sequence,
+        load_roi_mask, read_source,
+        recolor_target, smooth_blocks, source_dimensions, top_byte, AspectMode,
+    },
+    logging::{self, Level},
+    output::{
+        check_ffmpeg, create_apng_from_frames, create_fitness_plot, create_simple_gif_from_frames, create_sprite_sheet, create_video_from_frames, write_html_viewer, write_manifest, write_quality_report, write_sidecar,
+        write_stats_csv, Frame,
+    },
+};
+
+/// Set to make `run_ga_with_output` stop after the current generation and
+/// write what it has so far; the binary sets it on Ctrl-C.
+pub static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Playback settings shared by the GIF and video writers.
+#[derive(Clone, Copy, Debug)]
+pub struct AnimationTiming {
+    /// Delay between frames in hundredths of a second.
+    pub frame_delay: u16,
+    /// Extra plays after the first; `None` loops forever and `Some(0)` plays once.
+    pub loop_count: Option<u16>,
+    /// Frames are sampled down to roughly this many.
+    pub max_frames: usize,
+    /// Blended frames inserted between each pair of sampled frames, to smooth
+    /// out short runs. Presentation only; the GA never sees them.
+    pub interpolation_steps: usize,
+}
+
+impl Default for AnimationTiming {
+    fn default() -> Self {
+        AnimationTiming { frame_delay: 20, loop_count: None, max_frames: 50, interpolation_steps: 0 }
+    }
+}
+
+/// Format of the evolution animation. APNG and the video formats are true
+/// color; the videos are encoded by an external `ffmpeg`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VideoFormat {
+    Gif,
+    Apng,
+    Mp4,
+    WebM,
+}
+
+impl VideoFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            VideoFormat::Gif => "gif",
+            VideoFormat::Apng => "apng",
+            VideoFormat::Mp4 => "mp4",
+            VideoFormat::WebM => "webm",
+        }
+    }
+
+    /// Whether the format is encoded by an external `ffmpeg`.
+    pub fn uses_ffmpeg(self) -> bool {
+        matches!(self, VideoFormat::Mp4 | VideoFormat::WebM)
+    }
+}
+
+pub struct Options {
+    /// Target image; without one, target.png is used if it exists and a
+    /// generated sample otherwise.
+    pub input: Option<String>,
+    /// The generated target used when there is no input image.
+    pub sample_pattern: SamplePattern,
+    pub output: String,
+    /// Animation path; `result.<ext>` for the chosen video format by default.
+    pub animation_output: Option<String>,
+    /// Write the result image, the animation and target_sample.png. Without
+    /// the animation (or an HTML viewer or sprite sheet), only the final
+    /// frame is kept in memory.
+    pub save_png: bool,
+    pub save_gif: bool,
+    pub save_target: bool,
+    pub gif_scale: u32,
+    pub gif_counter: Option<LabelColor>,
+    pub gif_colors: usize,
+    /// Floyd-Steinberg dither GIF frames onto their palette.
+    pub dither: bool,
+    pub frame_delay: u16,
+    pub loop_count: Option<u16>,
+    pub max_frames: usize,
+    pub interpolation_steps: usize,
+    /// Keep the frame of every Nth generation for the animations and viewers;
+    /// the final generation's frame is always kept.
+    pub frame_stride: usize,
+    pub video: VideoFormat,
+    pub output_html: Option<String>,
+    pub stats_csv: Option<String>,
+    pub max_evaluations: Option<u64>,
+    pub blend_hsv: Option<f64>,
+    /// Blend rendered frames this far toward a bilinear ramp between block
+    /// colors (`image::smooth_blocks`); the GA still scores the blocks as evolved.
+    pub block_smoothing: Option<f64>,
+    pub quantize_target: Option<u32>,
+    pub manifest: Option<String>,
+    pub sprite_sheet: Option<(String, u32)>,
+    pub sprite_sheet_labels: bool,
+    pub diff_gif: Option<String>,
+    /// Grayscale image of each pixel's final match to the target (`fitness::fitness_map`).
+    pub fitness_map: Option<String>,
+    /// Image of the generation each pixel first matched the target exactly
+    /// (`image::convergence_map`).
+    pub convergence_map: Option<String>,
+    /// The target and the final frame side by side (`image::comparison_image`),
+    /// optionally with a divider and the final RMSE and SSIM as a caption.
+    pub comparison: Option<String>,
+    pub comparison_divider: bool,
+    pub comparison_labels: bool,
+    /// Chart of the average fitness per generation (`output::create_fitness_plot`),
+    /// optionally over a band of the lowest to highest pixel fitness.
+    pub fitness_plot: Option<String>,
+    pub fitness_plot_band: bool,
+    /// JSON of the final frame's quality under every built-in metric
+    /// (`fitness::quality_report`), also printed, whatever metric drove the run.
+    pub quality_report: Option<String>,
+    pub recolor_palette: Option<Vec<[u8; 3]>>,
+    pub sidecar: bool,
+    /// Stop once at most `.0` pixels changed between consecutive frames for
+    /// `.1` generations in a row.
+    pub settle: Option<(usize, usize)>,
+    pub target_sequence: Option<String>,
+    pub target_loop: bool,
+    /// Grayscale image weighting each pixel's share of the reported fitness
+    /// (see `render_grid`); black pixels are frozen after one generation.
+    pub roi_mask: Option<String>,
+    pub checkpoint: Option<(String, usize)>,
+    /// Also save the best image every this many generations, next to the
+    /// result as `<output>_gen<N>.png` (see `snapshot_path`).
+    pub snapshot_interval: Option<usize>,
+    pub batch: Option<(String, String)>,
+    /// Load the target, report the settings and check the outputs can be
+    /// written, without evolving.
+    pub dry_run: bool,
+    /// Evolve grayscale sources in grayscale mode (see `auto_mode`).
+    pub auto_mode: bool,
+    /// Evolve a small copy of the target twice with one seed, and fail if the
+    /// results differ (see `DETERMINISM_CHECK_SIZE`).
+    pub determinism_check: bool,
+    /// Evolve a small copy of the target once per crossover operator with
+    /// one seed and log a comparison table instead of running (see
+    /// `compare_crossover`).
+    pub compare_crossover: bool,
+    /// Evolve this many independently seeded trials (see `run_trials`) and
+    /// write the outputs of the one with the best final average fitness.
+    pub trials: usize,
+    /// Largest grid, in pixels, a run starts without `force` (see
+    /// `check_grid_size`).
+    pub max_pixels: usize,
+    pub force: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            input: None,
+            sample_pattern: SamplePattern::Gradient,
+            output: "result.png".to_string(),
+            animation_output: None,
+            save_png: true,
+            save_gif: true,
+            save_target: true,
+            gif_scale: 1,
+            gif_counter: None,
+            gif_colors: 256,
+            dither: false,
+            frame_delay: AnimationTiming::default().frame_delay,
+            loop_count: None,
+            max_frames: AnimationTiming::default().max_frames,
+            interpolation_steps: 0,
+            frame_stride: 1,
+            video: VideoFormat::Gif,
+            output_html: None,
+            stats_csv: None,
+            max_evaluations: None,
+            blend_hsv: None,
+            block_smoothing: None,
+            quantize_target: None,
+            manifest: None,
+            sprite_sheet: None,
+            sprite_sheet_labels: false,
+            diff_gif: None,
+            fitness_map: None,
+            convergence_map: None,
+            comparison: None,
+            comparison_divider: false,
+            comparison_labels: false,
+            fitness_plot: None,
+            fitness_plot_band: false,
+            quality_report: None,
+            recolor_palette: None,
+            sidecar: false,
+            settle: None,
+            target_sequence: None,
+            target_loop: false,
+            roi_mask: None,
+            checkpoint: None,
+            snapshot_interval: None,
+            batch: None,
+            dry_run: false,
+            auto_mode: false,
+            determinism_check: false,
+            compare_crossover: false,
+            trials: 1,
+            max_pixels: 1_000_000,
+            force: false,
+        }
+    }
+}
+
+impl Options {
+    /// The pixel budget `check_grid_size` enforces; none with `force`.
+    pub fn pixel_budget(&self) -> Option<usize> {
+        (!self.force).then_some(self.max_pixels)
+    }
+
+    /// Whether any output needs every recorded frame rather than the last.
+    pub fn records_frames(&self) -> bool {
+        self.save_gif || self.output_html.is_some() || self.sprite_sheet.is_some()
+    }
+}
+
+#[derive(Clone)]
+pub struct GenerationStats {
+    pub avg_fitness: f64,
+    /// Lowest and highest single-pixel fitness, over the pixels with a
+    /// nonzero region-of-interest weight.
+    pub min_fitness: f64,
+    pub max_fitness: f64,
+    pub normalized_fitness: f64,
+    /// Pixels that match the target exactly or, in palette mode, show a
+    /// palette color that scores as well as the nearest one can.
+    pub perfect_matches: usize,
+    pub match_percent: f64,
+    /// Pixels with a nonzero region-of-interest weight: all of them without
+    /// a mask. The run is solved once each of these matches.
+    pub weighted_pixels: usize,
+    /// Structural similarity of the frame to the target (see `fitness::ssim`).
+    pub ssim: f64,
+    /// Mean and largest per-pixel RGB RMSE to the target, in 0-255 units,
+    /// whatever the fitness metric.
+    pub avg_rmse: f64,
+    pub max_rmse: f64,
+    /// In palette mode, the mean RGB RMSE from each target pixel to its
+    /// nearest palette color: the error no run can go below. 0 otherwise.
+    pub quantization_rmse: f64,
+    /// Pixels in cells that are no longer stepped: converged pools, cells
+    /// frozen by `freeze_converged` and cells outside the region of interest.
+    pub frozen_pixels: usize,
+}
+
+// Redrawn in place on stderr while a generation is computed and cleared
+// before anything else is printed, so it never interleaves with stdout.
+#[cfg(feature = "native")]
+struct ProgressBar {
+    start: Instant,
+    first: usize,
+    total: usize,
+}
+
+#[cfg(feature = "native")]
+impl ProgressBar {
+    const WIDTH: usize = 30;
+
+    fn draw(&self, generation: usize, avg_fitness: Option<f64>) {
+        eprint!("\r{}", self.line(generation, avg_fitness));
+        let _ = std::io::stderr().flush();
+    }
+
+    // A resumed run can start past `total`, so the bar is clamped to it.
+    fn line(&self, generation: usize, avg_fitness: Option<f64>) -> String {
+        let generation = generation.min(self.total);
+        let filled = generation * Self::WIDTH / self.total.max(1);
+        let done = generation.saturating_sub(self.first);
+        let eta = match done {
+            0 => "--".to_string(),
+            _ => {
+                let secs = self.start.elapsed().as_secs_f64() / done as f64 * (self.total - generation) as f64;
+                format!("{}s", secs.round())
+            }
+        };
+        let fitness = avg_fitness.map_or("--".to_string(), |f| format!("{:.4}", f));
+        format!(
+            "[{}{}] {}/{} avg fitness {} ETA {}",
+            "#".repeat(filled),
+            "-".repeat(Self::WIDTH - filled),
+            generation,
+            self.total,
+            fitness,
+            eta
+        )
+    }
+
+    fn clear(&self) {
+        eprint!("\r\x1b[2K");
+    }
+}
+
+/// One generation yielded by `Evolution`: the grid's best image, with
+/// per-pixel fitness in row-major order, and its statistics.
+pub struct Generation {
+    /// 0-based; resumed and coarse-to-fine runs start past 0.
+    pub generation: usize,
+    /// Which target (of a sequence) the generation was scored against.
+    pub target_index: usize,
+    pub frame: RgbImage,
+    pub alpha: Option<GrayImage>,
+    pub cell_fitness: Vec<Fitness>,
+    /// Which pixels count as matched (see `GenerationStats::perfect_matches`).
+    pub matched: Vec<bool>,
+    pub stats: GenerationStats,
+    /// Every pixel matches the target, so the run stops here.
+    pub solved: bool,
+    /// Average fitness stopped improving for `patience` generations, so the
+    /// run stops here.
+    pub plateaued: bool,
+    /// Average fitness stopped improving for `patience` generations and
+    /// random immigrants were injected after this one instead of stopping
+    /// (see `GaConfig::immigrant_fraction`).
+    pub immigrated: bool,
+}
+
+// The grid's current best image, with per-pixel fitness and whether each
+// pixel counts as matched, in row-major order.
+struct Rendered {
+    frame: RgbImage,
+    alpha: Option<GrayImage>,
+    cell_fitness: Vec<Fitness>,
+    matched: Vec<bool>,
+    stats: GenerationStats,
+}
+
+// A pixel's weight in the region-of-interest mask, in [0, 1]; 1 without one.
+fn roi_weight(roi: Option<&GrayImage>, x: usize, y: usize) -> f64 {
+    roi.map_or(1.0, |roi| roi.get_pixel(x as u32, y as u32)[0] as f64 / 255.0)
+}
+
+// The highest mask weight among the pixels a cell covers.
+fn block_weight(roi: Option<&GrayImage>, ga: &SimpleGA) -> f64 {
+    let ((i, j), (block_height, block_width)) = (ga.pos(), ga.block());
+    (i..i + block_height).flat_map(|y| (j..j + block_width).map(move |x| roi_weight(roi, x, y))).fold(0.0, f64::max)
+}
+
+// For each target pixel, row-major, the best color fitness any palette entry
+// scores there and that entry's RGB RMSE. An exact match may not exist in
+// palette mode, so reaching this score is what counts as a match.
+fn palette_floor(target_image: &RgbImage, palette: &[Rgb<u8>], config: &GaConfig) -> Vec<(Fitness, Fitness)> {
+    let curve = config.fitness_curve();
+    let mut nearest = HashMap::new();
+    target_image
+        .pixels()
+        .map(|&pixel| {
+            *nearest.entry(pixel).or_insert_with(|| {
+                palette
+                    .iter()
+                    .map(|entry| (config.fitness.score_with(entry.0, pixel, curve), color_rmse(entry.0, &pixel)))
+                    .fold((Fitness::NEG_INFINITY, 0.0), |best, candidate| if candidate.0 > best.0 { candidate } else { best })
+            })
+        })
+        .collect()
+}
+
+// Also freezes blocks whose pixels all match when `freeze_converged` is set.
+// With a region-of-interest mask, the averages are weighted by it and only
+// pixels with a nonzero weight count towards the perfect matches. With a
+// `palette_floor`, a pixel matches once it reaches the floor's score.
+#[allow(clippy::too_many_arguments)]
+fn render_grid(
+    grid: &mut [Vec<SimpleGA>],
+    mut hsv_grid: Option<&mut [Vec<SimpleGA>]>,
+    target_image: &RgbImage,
+    target_alpha: Option<&GrayImage>,
+    roi: Option<&GrayImage>,
+    palette_floor: Option<&[(Fitness, Fitness)]>,
+    config: &GaConfig,
+    blend_hsv: Option<f64>,
+) -> Rendered {
+    let (width, height) = (target_image.width() as usize, target_image.height() as usize);
+    let mut frame = RgbImage::new(width as u32, height as u32);
+    let mut alpha_frame = target_alpha.map(|_| GrayImage::new(width as u32, height as u32));
+    let mut total_fitness = 0.0;
+    let mut total_normalized_fitness = 0.0;
+    let (mut total_rmse, mut max_rmse) = (0.0, 0.0f64);
+    let (mut total_weight, mut weighted_pixels) = (0.0, 0);
+    let (mut perfect_matches, mut total_floor_rmse) = (0, 0.0);
+    let mut cell_fitness = vec![0.0; width * height];
+    let mut matched = vec![false; width * height];
+    let mut frozen_pixels = 0;
+
+    for (bi, row) in grid.iter_mut().enumerate() {
+        for (bj, ga) in row.iter_mut().enumerate() {
+            if ga.is_frozen() {
+                frozen_pixels += ga.block().0 * ga.block().1;
+            }
+            let best = ga.get_best(target_image);
+            let mut vals = best.get_block_vals();
+            let alpha = best.get_alpha();
+            let mut hsv_cell = hsv_grid.as_mut().map(|grid| &mut grid[bi][bj]);
+            if let (Some(hsv_ga), Some(weight)) = (&hsv_cell, blend_hsv) {
+                for (val, hsv_val) in vals.iter_mut().zip(hsv_ga.get_best(target_image).get_block_vals()) {
+                    *val = blend_colors(*val, hsv_val, weight);
+                }
+            }
+
+            let (pos, block_width) = (ga.pos(), ga.block().1);
+            let mut block_matches = true;
+            for (k, val) in vals.into_iter().enumerate() {
+                let (i, j) = (pos.0 + k / block_width, pos.1 + k % block_width);
+                frame.put_pixel(j as u32, i as u32, Rgb([val[0], val[1], val[2]]));
+
+                let target_pixel = target_image.get_pixel(j as u32, i as u32);
+                let rmse = color_rmse(val, target_pixel);
+                let mut fitness = config.fitness.score_with(val, *target_pixel, config.fitness_curve());
+                let mut alpha_matches = true;
+                if let (Some(alpha_frame), Some(target_alpha)) = (alpha_frame.as_mut(), target_alpha) {
+                    let target_alpha = target_alpha.get_pixel(j as u32, i as u32)[0];
+                    alpha_frame.put_pixel(j as u32, i as u32, Luma([alpha]));
+                    fitness *= alpha_fitness(alpha, target_alpha);
+                    alpha_matches = alpha == target_alpha;
+                }
+                let weight = roi_weight(roi, j, i);
+                total_fitness += weight * fitness as f64;
+                cell_fitness[i * width + j] = fitness;
+                total_normalized_fitness += weight * normalized_fitness_from_rmse(rmse) as f64;
+                total_rmse += weight * rmse as f64;
+                total_weight += weight;
+                if weight > 0.0 {
+                    weighted_pixels += 1;
+                    max_rmse = max_rmse.max(rmse as f64);
+                }
+
+                let matches = match palette_floor {
+                    Some(floor) => {
+                        let (best_fitness, floor_rmse) = floor[i * width + j];
+                        total_floor_rmse += weight * floor_rmse as f64;
+                        fitness >= best_fitness
+                    }
+                    None => val[0] == target_pixel[0] && val[1] == target_pixel[1] && val[2] == target_pixel[2] && alpha_matches,
+                };
+                if matches && weight > 0.0 {
+                    perfect_matches += 1;
+                }
+                matched[i * width + j] = matches;
+                block_matches &= matches;
+            }
+
+            if block_matches && config.freeze_converged {
+                ga.freeze();
+                if let Some(hsv_ga) = hsv_cell.as_mut() {
+                    hsv_ga.freeze();
+                }
+            }
+        }
+    }
+
+    if config.gradient_weight > 0.0 {
+        let weight = config.gradient_weight as Fitness;
+        for (k, (fitness, gradient)) in cell_fitness.iter_mut().zip(gradient_map(&frame, target_image)).enumerate() {
+            *fitness += weight * gradient;
+            total_fitness += roi_weight(roi, k % width, k / width) * (weight * gradient) as f64;
+        }
+    }
+
+    let (mut min_fitness, mut max_fitness) = (f64::INFINITY, f64::NEG_INFINITY);
+    for (k, &fitness) in cell_fitness.iter().enumerate() {
+        if roi_weight(roi, k % width, k / width) > 0.0 {
+            min_fitness = min_fitness.min(fitness as f64);
+            max_fitness = max_fitness.max(fitness as f64);
+        }
+    }
+
+    let total_weight = total_weight.max(f64::EPSILON);
+    let stats = GenerationStats {
+        avg_fitness: total_fitness / total_weight,
+        min_fitness: if weighted_pixels > 0 { min_fitness } else { 0.0 },
+        max_fitness: if weighted_pixels > 0 { max_fitness } else { 0.0 },
+        normalized_fitness: total_normalized_fitness / total_weight,
+        perfect_matches,
+        match_percent: perfect_matches as f64 / weighted_pixels.max(1) as f64 * 100.0,
+        weighted_pixels,
+        ssim: ssim(&frame, target_image),
+        avg_rmse: total_rmse / total_weight,
+        max_rmse,
+        quantization_rmse: total_floor_rmse / total_weight,
+        frozen_pixels,
+    };
+    Rendered { frame, alpha: alpha_frame, cell_fitness, matched, stats }
+}
+
+/// Average, highest, lowest and standard deviation of every cell's best
+/// fitness. A high deviation means convergence is patchy: some regions of the
+/// image lag well behind the rest.
+pub fn grid_fitness_stats(grid: &[Vec<SimpleGA>], target_image: &RgbImage) -> (f64, f64, f64, f64) {
+    let fitness: Vec<f64> = grid.iter().flatten().map(|ga| ga.best_fitness(target_image) as f64).collect();
+    let n = fitness.len().max(1) as f64;
+    let avg = fitness.iter().sum::<f64>() / n;
+    let max = fitness.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let min = fitness.iter().copied().fold(f64::INFINITY, f64::min);
+    let variance = fitness.iter().map(|f| (f - avg).powi(2)).sum::<f64>() / n;
+    (avg, max, min, variance.sqrt())
+}
+
+// Every cell's best chromosome at full 16-bit precision.
+#[cfg(feature = "native")]
+fn render_deep(grid: &[Vec<SimpleGA>], target_image: &RgbImage) -> Rgb16Image {
+    let mut frame = Rgb16Image::new(target_image.width(), target_image.height());
+    for ga in grid.iter().flatten() {
+        let (i, j) = ga.pos();
+        frame.put_pixel(j as u32, i as u32, Rgb(ga.get_best(target_image).get_deep_val()));
+    }
+    frame
+}
+
+#[cfg(feature = "native")]
+fn load_init_image(config: &GaConfig, width: u32, height: u32) -> Result<Option<RgbImage>, Box<dyn Error>> {
+    if let Some(path) = &config.warm_start {
+        let path = path.to_string_lossy();
+        let image = load_target_image(&path, width, height, AspectMode::Stretch, config.resize_filter, false, None)
+            .map_err(|e| format!("Failed to load warm start image {}: {}", path, e))?;
+        return Ok(Some(image));
+    }
+    match &config.init {
+        InitStrategy::FromImage { path, .. } => Ok(Some(
+            load_target_image(path, width, height, AspectMode::Stretch, config.resize_filter, false, None)
+                .map_err(|e| format!("Failed to load init image {}: {}", path, e))?,
+        )),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(not(feature = "native"))]
+fn load_init_image(config: &GaConfig, _: u32, _: u32) -> Result<Option<RgbImage>, Box<dyn Error>> {
+    if config.warm_start.is_some() {
+        return Err("Warm starts need the native feature".into());
+    }
+    match config.init {
+        InitStrategy::FromImage { .. } => Err("Init images need the native feature".into()),
+        _ => Ok(None),
+    }
+}
+
+/// Upscaling a source by more than this on either side is reported: the GA
+/// can only reproduce the detail the resize invented.
+pub const UPSCALE_WARNING_RATIO: f64 = 2.0;
+
+/// Rejects a `width` x `height` grid over `max_pixels`, before any target is
+/// resized or population allocated for it, and warns when it upscales a
+/// `source` of the given dimensions by more than `UPSCALE_WARNING_RATIO`.
+#[cfg(feature = "native")]
+pub fn check_grid_size(width: usize, height: usize, source: Option<(u32, u32)>, max_pixels: Option<usize>) -> Result<(), String> {
+    let pixels = width.saturating_mul(height);
+    if let Some(max_pixels) = max_pixels.filter(|&max_pixels| pixels > max_pixels) {
+        return Err(format!(
+            "A {}x{} grid is {} pixels, over the budget of {}; lower the size, raise --max-pixels or pass --force",
+            width, height, pixels, max_pixels
+        ));
+    }
+    if let Some((source_width, source_height)) = source.filter(|&(w, h)| w > 0 && h > 0) {
+        let ratio = (width as f64 / source_width as f64).max(height as f64 / source_height as f64);
+        if ratio > UPSCALE_WARNING_RATIO {
+            warn!("Upscaling the {}x{} target {:.1}x to {}x{}; the GA can only match the detail the resize invents",
+                  source_width, source_height, ratio, width, height);
+        }
+    }
+    Ok(())
+}
+
+// Rejects settings that cannot be combined, before any grid is built.
+fn check_modes(config: &GaConfig, options: &Options, targets: &[RgbImage]) -> Result<(), Box<dyn Error>> {
+    config.validate()?;
+    if targets.first().is_none_or(|target| target.width() == 0 || target.height() == 0) {
+        return Err("Target image is empty".into());
+    }
+    if config.block_size > 1 && config.alpha {
+        return Err("Block chromosomes do not support an alpha channel".into());
+    }
+    if options.block_smoothing.is_some() && config.block_size <= 1 {
+        return Err("Block smoothing needs --block-size above 1".into());
+    }
+    if options.output == STDIO_PATH && options.snapshot_interval.is_some() {
+        return Err("Snapshots are named after the output file and need --output to be a path, not -".into());
+    }
+    if config.palette.is_some() && (config.alpha || options.blend_hsv.is_some()) {
+        return Err("Palette mode does not support --alpha or --blend-hsv".into());
+    }
+    if config.palette.as_ref().is_some_and(|palette| palette.is_empty() || palette.len() > 256) {
+        return Err("Palette must have between 1 and 256 colors".into());
+    }
+    if config.grayscale && (config.alpha || config.palette.is_some() || options.blend_hsv.is_some()) {
+        return Err("Grayscale mode does not support --alpha, --palette or --blend-hsv".into());
+    }
+    if config.deep_color
+        && (config.alpha || config.palette.is_some() || config.grayscale || config.block_size > 1 || options.blend_hsv.is_some())
+    {
+        return Err("16-bit mode does not support --rgba, --palette, --grayscale, --block-size or --blend-hsv".into());
+    }
+    if config.deep_color && (targets.len() > 1 || options.quantize_target.is_some() || options.recolor_palette.is_some()) {
+        return Err("16-bit mode does not support --target-sequence, --quantize-target or --recolor-palette".into());
+    }
+    if config.deep_color && config.fitness != FitnessMetric::RgbRmse {
+        return Err("16-bit mode only supports the rgb fitness metric".into());
+    }
+    if (config.channel_mutation_rates.is_some() || config.channel_crossover_rates.is_some()) && (config.grayscale || config.palette.is_some()) {
+        return Err("Per-channel rates do not support --grayscale or --palette".into());
+    }
+    if config.pyramid_levels > 1
+        && (config.alpha || config.deep_color || targets.len() > 1 || options.blend_hsv.is_some())
+    {
+        return Err("Pyramid levels do not support --rgba, --16bit, --target-sequence or --blend-hsv".into());
+    }
+    if config.encoding_space == EncodingSpace::Hsv
+        && (config.palette.is_some() || config.grayscale || config.deep_color || options.blend_hsv.is_some())
+    {
+        return Err("HSV genes do not support --palette, --grayscale, --16bit or --blend-hsv".into());
+    }
+    Ok(())
+}
+
+// The blurred init reference for `InitStrategy::TargetBlur`, `None` otherwise.
+fn blurred_init_reference(config: &GaConfig, init_reference: &RgbImage) -> Option<RgbImage> {
+    match config.init {
+        InitStrategy::TargetBlur { sigma, .. } => Some(imageops::blur(init_reference, sigma)),
+        _ => None,
+    }
+}
+
+// One cell per block of the target. With an adaptive population, a cell's
+// difficulty is the highest local contrast in its block relative to the
+// whole target's, scaled by its region-of-interest weight.
+fn build_grid(
+    config: &GaConfig,
+    target: &RgbImage,
+    init_reference: &RgbImage,
+    target_alpha: Option<&GrayImage>,
+    target_deep: Option<&Rgb16Image>,
+    roi: Option<&GrayImage>,
+    space: ColorSpace,
+) -> Vec<Vec<SimpleGA>> {
+    let block_size = config.block_size.max(1);
+    let (width, height) = (target.width() as usize, target.height() as usize);
+    let contrast = matches!(config.population, PopulationStrategy::Adaptive { .. }).then(|| local_contrast(target));
+    let max_contrast = contrast.iter().flatten().fold(0.0, |a: f64, &b| a.max(b));
+    (0..height.div_ceil(block_size))
+        .map(|bi| {
+            (0..width.div_ceil(block_size))
+                .map(|bj| {
+                    let (i, j) = (bi * block_size, bj * block_size);
+                    let ga = SimpleGA::new((i, j), config, init_reference, space);
+                    let ga = match &contrast {
+                        Some(contrast) => {
+                            let block = (i..(i + block_size).min(height)).flat_map(|y| (j..(j + block_size).min(width)).map(move |x| y * width + x));
+                            let difficulty = block.map(|k| contrast[k]).fold(0.0, f64::max) / max_contrast.max(f64::EPSILON);
+                            let weight = block_weight(roi, &ga);
+                            ga.with_difficulty(difficulty * weight, config)
+                        }
+                        None => ga,
+                    };
+                    let ga = match target_deep {
+                        Some(target) => ga.with_target_deep(target.get_pixel(j as u32, i as u32).0),
+                        None => ga,
+                    };
+                    match target_alpha {
+                        Some(alpha) => ga.with_target_alpha(alpha.get_pixel(j as u32, i as u32)[0]),
+                        None => ga,
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Where `step_cells` runs: in order on the calling thread, or on a rayon
+// pool, the global one unless `GaConfig::threads` sizes it. Without the
+// `native` feature everything is sequential.
+enum Workers {
+    Sequential,
+    #[cfg(feature = "native")]
+    Pool(Option<rayon::ThreadPool>),
+}
+
+impl Workers {
+    fn new(threads: Option<usize>) -> Result<Self, Box<dyn Error>> {
+        match threads {
+            Some(1) => Ok(Workers::Sequential),
+            #[cfg(feature = "native")]
+            Some(n) => Ok(Workers::Pool(Some(rayon::ThreadPoolBuilder::new().num_threads(n).build()?))),
+            #[cfg(feature = "native")]
+            None => Ok(Workers::Pool(None)),
+            #[cfg(not(feature = "native"))]
+            _ => Ok(Workers::Sequential),
+        }
+    }
+}
+
+// Advances every cell by one generation. `neighbors` is the previous frame,
+// which the gradient term compares against.
+fn step_cells<'a>(
+    workers: &Workers,
+    cells: impl Iterator<Item = &'a mut SimpleGA>,
+    target_image: &RgbImage,
+    config: &GaConfig,
+    gen: usize,
+    neighbors: Option<&Arc<RgbImage>>,
+) {
+    let step = |ga: &mut SimpleGA| {
+        if let Some(frame) = neighbors {
+            ga.set_neighbors(Arc::clone(frame));
+        }
+        match config.algorithm {
+            Algorithm::Ga => ga.step(target_image, config, gen),
+            Algorithm::HillClimb => ga.hill_climb_step(target_image, config, gen),
+        }
+    };
+    match workers {
+        Workers::Sequential => cells.for_each(step),
+        #[cfg(feature = "native")]
+        Workers::Pool(pool) => {
+            let cells: Vec<&mut SimpleGA> = cells.collect();
+            let run = || cells.into_par_iter().for_each(step);
+            match pool {
+                Some(pool) => pool.install(run),
+                None => run(),
+            }
+        }
+    }
+}
+
+// The lower levels of a coarse-to-fine run: evolves the target at half the
+// resolution, with one level fewer, for the generations before
+// `pyramid_start`, and returns the result upscaled to the target's size.
+fn evolve_coarse(config: &GaConfig, target: &RgbImage, init_reference: &RgbImage) -> Result<(RgbImage, FitnessCounts), Box<dyn Error>> {
+    let (full_width, full_height) = target.dimensions();
+    let (width, height) = (full_width.div_ceil(2), full_height.div_ceil(2));
+    let coarse_config = GaConfig {
+        width: width as usize,
+        height: height as usize,
+        iterations: config.pyramid_start(),
+        pyramid_levels: config.pyramid_levels - 1,
+        max_cell_generations: None,
+        ..config.clone()
+    };
+    let target = imageops::resize(target, width, height, config.resize_filter);
+    let init_reference = imageops::resize(init_reference, width, height, config.resize_filter);
+    let mut evolution = Evolution::start(&coarse_config, &Options::default(), vec![target], None, None, None, &init_reference, None)?;
+    let coarse = evolution.finish();
+    Ok((imageops::resize(&coarse, full_width, full_height, imageops::FilterType::Nearest), evolution.fitness_counts()))
+}
+
+// Hands every cell the current best chromosomes of its four adjacent cells,
+// for spatial crossover.
+fn share_donors(grid: &mut [Vec<SimpleGA>], target_image: &RgbImage) {
+    let best: Vec<Vec<Chromosome>> =
+        grid.iter().map(|row| row.iter().map(|ga| ga.get_best(target_image).clone()).collect()).collect();
+    for (i, row) in grid.iter_mut().enumerate() {
+        for (j, ga) in row.iter_mut().enumerate() {
+            let adjacent = [(i.wrapping_sub(1), j), (i + 1, j), (i, j.wrapping_sub(1)), (i, j + 1)];
+            ga.set_donors(adjacent.iter().filter_map(|&(y, x)| best.get(y).and_then(|row| row.get(x)).cloned()));
+        }
+    }
+}
+
+/// The generation loop as an iterator: each `next()` advances every cell by
+/// one generation and yields the grid's best image. It ends after
+/// `config.iterations` generations (or, with `max_cell_generations`, once
+/// the cells still stepped have used up the budget frozen ones left), on a
+/// full match (single targets only) or
+/// once the average fitness plateaus for `patience` generations; callers stop
+/// earlier by dropping it. It owns its state, so it can run on another thread.
+pub struct Evolution {
+    config: GaConfig,
+    targets: Vec<RgbImage>,
+    target_alpha: Option<GrayImage>,
+    roi: Option<GrayImage>,
+    target_loop: bool,
+    blend_hsv: Option<f64>,
+    palette_floors: Option<Vec<Vec<(Fitness, Fitness)>>>,
+    grid: Vec<Vec<SimpleGA>>,
+    hsv_grid: Option<Vec<Vec<SimpleGA>>>,
+    workers: Workers,
+    start_gen: usize,
+    generation: usize,
+    neighbors: Option<Arc<RgbImage>>,
+    skip_converged: bool,
+    best_avg_fitness: f64,
+    stale_generations: usize,
+    // Immigrants were injected since the average fitness last improved.
+    immigrated: bool,
+    // Cell steps left with `max_cell_generations`.
+    cell_steps_left: Option<usize>,
+    // Work done before the grid was built: by the coarser levels, or before
+    // the checkpoint this run resumed from.
+    counted: FitnessCounts,
+    finished: bool,
+}
+
+impl Evolution {
+    /// Evolves `target` (already at the grid size) as `run_ga` does. The
+    /// coarser levels of a coarse-to-fine run are evolved here, before the
+    /// first generation is yielded.
+    pub fn new(target: &RgbImage, config: &GaConfig) -> Result<Self, Box<dyn Error>> {
+        let target_alpha = config.alpha.then(|| GrayImage::from_pixel(target.width(), target.height(), Luma([255])));
+        let init_image = load_init_image(config, target.width(), target.height())?;
+        let init_reference = init_image.as_ref().unwrap_or(target);
+        Self::start(config, &Options::default(), vec![target.clone()], target_alpha, None, None, init_reference, None)
+    }
+
+    // Sets up the grid shared by `run_ga` and `run_ga_with_output`. In 16-bit
+    // mode `target_deep` defaults to the widened first target. Cells entirely
+    // outside `roi` are frozen after the first generation. A fresh
+    // coarse-to-fine run starts at `pyramid_start`, with every pool seeded
+    // from the coarser levels' result and its bits flipped at the mutation
+    // rate. Cells whose pools converge on an exact match stop being stepped
+    // whenever that cannot change the output: not when the target changes,
+    // neighbors feed into a cell's fitness or 16-bit colors could still improve.
+    #[allow(clippy::too_many_arguments)]
+    fn start(
+        config: &GaConfig,
+        options: &Options,
+        targets: Vec<RgbImage>,
+        target_alpha: Option<GrayImage>,
+        target_deep: Option<&Rgb16Image>,
+        roi: Option<GrayImage>,
+        init_reference: &RgbImage,
+        resume: Option<Checkpoint>,
+    ) -> Result<Self, Box<dyn Error>> {
+        check_modes(config, options, &targets)?;
+        let widened: Rgb16Image;
+        let target_deep = match target_deep {
+            None if config.deep_color => {
+                widened = widen(&targets[0]);
+                Some(&widened)
+            }
+            _ => target_deep,
+        };
+        // Loaders already convert grayscale targets; this covers callers of `run_ga`.
+        let gray_reference: RgbImage;
+        let (targets, init_reference) = if config.grayscale {
+            gray_reference = to_grayscale(init_reference);
+            (targets.iter().map(to_grayscale).collect(), &gray_reference)
+        } else {
+            (targets, init_reference)
+        };
+        let pyramid = match &resume {
+            None if config.pyramid_levels > 1 => {
+                let seeded = GaConfig { init: InitStrategy::FromTarget { noise: config.mutation_rate }, ..config.clone() };
+                Some((seeded, evolve_coarse(config, &targets[0], init_reference)?))
+            }
+            _ => None,
+        };
+        let blurred_reference = blurred_init_reference(config, init_reference);
+        let init_reference = blurred_reference.as_ref().unwrap_or(init_reference);
+        let (width, height) = (targets[0].width() as usize, targets[0].height() as usize);
+        let block_size = config.block_size.max(1);
+        let (grid_width, grid_height) = (width.div_ceil(block_size), height.div_ceil(block_size));
+        let build_grid = |config: &GaConfig, reference: &RgbImage, space: ColorSpace| {
+            build_grid(config, &targets[0], reference, target_alpha.as_ref(), target_deep, roi.as_ref(), space)
+        };
+
+        let (start_gen, grid, hsv_grid, counted) = match resume {
+            Some(checkpoint) => {
+                let grid_matches =
+                    checkpoint.grid.len() == grid_height && checkpoint.grid.iter().all(|row| row.len() == grid_width);
+                // Every cell's genes must be laid out the way this config builds them.
+                let layout_matches = |grid: &[Vec<SimpleGA>], space: ColorSpace| {
+                    grid.iter().enumerate().all(|(bi, row)| {
+                        row.iter().enumerate().all(|(bj, ga)| {
+                            let pixels = block_size.min(height - bi * block_size) * block_size.min(width - bj * block_size);
+                            ga.population.pool.iter().all(|chr| chr.has_layout(config, pixels, space))
+                        })
+                    })
+                };
+                let grid_matches = grid_matches
+                    && layout_matches(&checkpoint.grid, config.color_space())
+                    && checkpoint.hsv_grid.as_deref().is_none_or(|grid| layout_matches(grid, ColorSpace::Hsv));
+                if !grid_matches || checkpoint.hsv_grid.is_some() != options.blend_hsv.is_some() {
+                    return Err("Checkpoint does not match the current grid settings".into());
+                }
+                let counted = FitnessCounts { evaluations: checkpoint.fitness_evaluations, ..FitnessCounts::default() };
+                (checkpoint.generation, checkpoint.grid, checkpoint.hsv_grid, counted)
+            }
+            None => match &pyramid {
+                Some((seeded, (coarse, counted))) => {
+                    (config.pyramid_start(), build_grid(seeded, coarse, config.color_space()), None, *counted)
+                }
+                None => (
+                    0,
+                    build_grid(config, init_reference, config.color_space()),
+                    options.blend_hsv.map(|_| build_grid(config, init_reference, ColorSpace::Hsv)),
+                    FitnessCounts::default(),
+                ),
+            },
+        };
+
+        let palette_floors = config
+            .palette
+            .as_ref()
+            .map(|palette| targets.iter().map(|target| palette_floor(target, palette, config)).collect());
+        let targets_len = targets.len();
+        let grid_cells = grid.iter().chain(hsv_grid.iter().flatten()).map(Vec::len).sum::<usize>();
+        let mut evolution = Evolution {
+            config: config.clone(),
+            palette_floors,
+            targets,
+            target_alpha,
+            roi,
+            target_loop: options.target_loop,
+            blend_hsv: options.blend_hsv,
+            grid,
+            hsv_grid,
+            workers: Workers::new(config.threads)?,
+            start_gen,
+            generation: start_gen,
+            neighbors: None,
+            skip_converged: targets_len == 1 && config.gradient_weight == 0.0 && config.spatial_crossover_rate == 0.0 && !config.deep_color,
+            best_avg_fitness: f64::NEG_INFINITY,
+            stale_generations: 0,
+            immigrated: false,
+            cell_steps_left: config.max_cell_generations.map(|_| grid_cells * config.iterations.saturating_sub(start_gen)),
+            counted,
+            finished: false,
+        };
+        if config.gradient_weight > 0.0 && start_gen < config.generation_limit() {
+            evolution.neighbors = Some(Arc::new(evolution.render(start_gen).frame));
+        }
+        Ok(evolution)
+    }
+
+    // Without looping, each target frame is held for an equal share of the
+    // generations (or frames are skipped if there are more than generations).
+    fn target_index(&self, gen: usize) -> usize {
+        if self.target_loop {
+            gen % self.targets.len()
+        } else {
+            (gen * self.targets.len() / self.config.iterations.max(1)).min(self.targets.len() - 1)
+        }
+    }
+
+    fn render(&mut self, gen: usize) -> Rendered {
+        let index = self.target_index(gen);
+        let target_image = &self.targets[index];
+        let palette_floor = self.palette_floors.as_ref().map(|floors| floors[index].as_slice());
+        render_grid(&mut self.grid, self.hsv_grid.as_deref_mut(), target_image, self.target_alpha.as_ref(), self.roi.as_ref(), palette_floor, &self.config, self.blend_hsv)
+    }
+
+    /// Fitness work this run has done so far, over every cell, including
+    /// coarser levels and the run a checkpoint was saved from.
+    pub fn fitness_counts(&self) -> FitnessCounts {
+        let cells = self.grid.iter().chain(self.hsv_grid.iter().flatten()).flatten();
+        self.counted + cells.map(SimpleGA::fitness_counts).sum()
+    }
+
+    /// Runs the remaining generations and returns the last one's best image,
+    /// or the current grid's if none were left.
+    pub fn finish(&mut self) -> RgbImage {
+        match self.by_ref().last() {
+            Some(generation) => generation.frame,
+            None => {
+                let gen = self.generation.saturating_sub(1).max(self.start_gen).min(self.config.generation_limit().saturating_sub(1));
+                self.render(gen).frame
+            }
+        }
+    }
+}
+
+impl Iterator for Evolution {
+    type Item = Generation;
+
+    fn next(&mut self) -> Option<Generation> {
+        if self.finished || self.generation >= self.config.generation_limit() || self.cell_steps_left == Some(0) {
+            return None;
+        }
+        let gen = self.generation;
+        if let Some(left) = self.cell_steps_left.as_mut() {
+            let stepped = self.grid.iter().chain(self.hsv_grid.iter().flatten()).flatten().filter(|ga| !ga.is_frozen()).count();
+            *left = left.saturating_sub(stepped);
+        }
+        let target_index = self.target_index(gen);
+        let target_image = &self.targets[target_index];
+
+        if self.config.spatial_crossover_rate > 0.0 {
+            share_donors(&mut self.grid, target_image);
+            if let Some(hsv_grid) = self.hsv_grid.as_mut() {
+                share_donors(hsv_grid, target_image);
+            }
+        }
+        let cells = self.grid.iter_mut().chain(self.hsv_grid.iter_mut().flatten()).flatten();
+        step_cells(&self.workers, cells, target_image, &self.config, gen, self.neighbors.as_ref());
+        if self.roi.is_some() && gen == self.start_gen {
+            for ga in self.grid.iter_mut().chain(self.hsv_grid.iter_mut().flatten()).flatten() {
+                if block_weight(self.roi.as_ref(), ga) == 0.0 {
+                    ga.freeze();
+                }
+            }
+        }
+        if self.skip_converged {
+            let target_image = &self.targets[target_index];
+            for ga in self.grid.iter_mut().chain(self.hsv_grid.iter_mut().flatten()).flatten() {
+                if !ga.is_frozen() && ga.is_converged(target_image, &self.config) {
+                    ga.freeze();
+                }
+            }
+        }
+
+        let rendered = self.render(gen);
+        if self.neighbors.is_some() {
+            self.neighbors = Some(Arc::new(rendered.frame.clone()));
+        }
+        let avg_fitness = rendered.stats.avg_fitness;
+
+        let mut plateaued = match self.config.patience {
+            Some(patience) => {
+                if avg_fitness > self.best_avg_fitness + self.config.min_delta {
+                    self.best_avg_fitness = avg_fitness;
+                    self.stale_generations = 0;
+                    self.immigrated = false;
+                } else {
+                    self.stale_generations += 1;
+                }
+                self.stale_generations >= patience
+            }
+            None => false,
+        };
+        let solved = rendered.stats.perfect_matches == rendered.stats.weighted_pixels && self.targets.len() == 1;
+        let immigrated = plateaued && !solved && !self.immigrated && self.config.immigrant_fraction > 0.0;
+        if immigrated {
+            for ga in self.grid.iter_mut().chain(self.hsv_grid.iter_mut().flatten()).flatten() {
+                ga.inject_immigrants(&self.config, self.config.immigrant_fraction);
+            }
+            self.stale_generations = 0;
+            self.immigrated = true;
+            plateaued = false;
+        }
+        self.generation += 1;
+        self.finished = solved || plateaued;
+
+        Some(Generation {
+            generation: gen,
+            target_index,
+            frame: rendered.frame,
+            alpha: rendered.alpha,
+            cell_fitness: rendered.cell_fitness,
+            matched: rendered.matched,
+            stats: rendered.stats,
+            solved,
+            plateaued,
+            immigrated,
+        })
+    }
+}
+
+/// Evolves `target` (already at the grid size) without printing or writing
+/// files. `on_generation` gets the generation index, the best image so far
+/// and its average fitness after every generation; the final best image is
+/// returned. Output options such as HSV blending are not applied.
+pub fn run_ga(
+    target: &RgbImage,
+    config: &GaConfig,
+    mut on_generation: impl FnMut(usize, &RgbImage, f64),
+) -> Result<RgbImage, Box<dyn Error>> {
+    let mut evolution = Evolution::new(target, config)?;
+    let mut last_frame = None;
+    for generation in evolution.by_ref() {
+        on_generation(generation.generation, &generation.frame, generation.stats.avg_fitness);
+        last_frame = Some(generation.frame);
+    }
+    Ok(last_frame.unwrap_or_else(|| evolution.finish()))
+}
+
+/// Like `run_ga` without a callback: returns the reconstructed image and its
+/// final average fitness.
+pub fn reconstruct_image(target: &RgbImage, config: &GaConfig) -> Result<(RgbImage, f64), Box<dyn Error>> {
+    let mut final_fitness = None;
+    let result = run_ga(target, config, |_, _, avg_fitness| final_fitness = Some(avg_fitness))?;
+    // With zero generations nothing is reported; score the initial image instead.
+    let final_fitness = final_fitness.unwrap_or_else(|| {
+        let fitness = grid_fitness(&result, target, &config.fitness.with_curve(config.fitness_curve()), config.gradient_weight);
+        fitness.iter().map(|&f| f as f64).sum::<f64>() / fitness.len() as f64
+    });
+    Ok((result, final_fitness))
+}
+
+/// Seed of trial `index` in a best-of-N search, derived from the master
+/// seed so any trial can be rerun alone with that seed.
+pub fn trial_seed(master: u64, index: usize) -> u64 {
+    stream_seed(master, index as u64)
+}
+
+/// One run of a best-of-N search (see `run_trials`).
+#[derive(Clone, Debug)]
+pub struct Trial {
+    pub seed: u64,
+    pub image: RgbImage,
+    /// Average fitness of the final generation.
+    pub avg_fitness: f64,
+}
+
+/// Index of the trial with the highest final average fitness; ties go to
+/// the earlier trial, so the choice does not depend on which finished first.
+pub fn best_trial(trials: &[Trial]) -> Option<usize> {
+    (0..trials.len()).reduce(|best, i| if trials[i].avg_fitness > trials[best].avg_fitness { i } else { best })
+}
+
+/// Evolves `target` `trials` times as `reconstruct_image` does, trial `i`
+/// seeded with `trial_seed(master, i)` where the master seed is
+/// `config.seed` or a random one. Trials run concurrently on the
+/// `config.threads` workers and come back in trial order; as every trial
+/// is seeded, the results do not depend on the thread count.
+#[cfg(feature = "native")]
+pub fn run_trials(target: &RgbImage, config: &GaConfig, trials: usize) -> Result<Vec<Trial>, Box<dyn Error>> {
+    let master = config.seed.unwrap_or_else(rand::random);
+    evolve_trials(config, master, trials, |config| reconstruct_image(target, config))
+}
+
+// Runs `evolve` once per trial with the trial's seed. Inside a trial the
+// cells share the trials' workers rather than starting a pool of their own.
+#[cfg(feature = "native")]
+fn evolve_trials(
+    config: &GaConfig,
+    master: u64,
+    trials: usize,
+    evolve: impl Fn(&GaConfig) -> Result<(RgbImage, f64), Box<dyn Error>> + Sync,
+) -> Result<Vec<Trial>, Box<dyn Error>> {
+    let cell_threads = (config.threads == Some(1)).then_some(1);
+    let run = |index| {
+        let seed = trial_seed(master, index);
+        let (image, avg_fitness) = evolve(&GaConfig { seed: Some(seed), threads: cell_threads, ..config.clone() }).map_err(|e| e.to_string())?;
+        Ok(Trial { seed, image, avg_fitness })
+    };
+    let results: Vec<Result<Trial, String>> = match Workers::new(config.threads)? {
+        Workers::Sequential => (0..trials).map(run).collect(),
+        Workers::Pool(pool) => {
+            let all = || (0..trials).into_par_iter().map(run).collect();
+            match pool {
+                Some(pool) => pool.install(all),
+                None => all(),
+            }
+        }
+    };
+    Ok(results.into_iter().collect::<Result<_, _>>()?)
+}
+
+/// Evolves one population toward the single color `target` for
+/// `config.iterations` generations and returns its best chromosome and that
+/// chromosome's fitness: the GA without any grid, resizing or rendering.
+/// Grid settings such as `width`, `block_size` and neighbor crossover do not
+/// apply.
+pub fn reconstruct_pixel(target: Rgb<u8>, config: &GaConfig) -> (Chromosome, f64) {
+    let target = RgbImage::from_pixel(1, 1, target);
+    let mut ga = SimpleGA::new((0, 0), config, &target, config.color_space());
+    for generation in 0..config.iterations {
+        match config.algorithm {
+            Algorithm::Ga => ga.step(&target, config, generation),
+            Algorithm::HillClimb => ga.hill_climb_step(&target, config, generation),
+        }
+    }
+    (ga.get_best(&target).clone(), ga.best_fitness(&target) as f64)
+}
+
+/// Evolves an in-memory target one generation at a time, for callers that
+/// draw every frame themselves, such as a browser canvas. It touches no
+/// files, so it is available without the `native` feature.
+pub struct Reconstruction {
+    config: GaConfig,
+    target: RgbImage,
+    target_alpha: Option<GrayImage>,
+    palette_floor: Option<Vec<(Fitness, Fitness)>>,
+    grid: Vec<Vec<SimpleGA>>,
+    workers: Workers,
+    neighbors: Option<Arc<RgbImage>>,
+    generation: usize,
+    stats: Option<GenerationStats>,
+}
+
+impl Reconstruction {
+    /// Decodes `image_bytes` (any format `image` reads, e.g. PNG) and fits it
+    /// to `config.width` x `config.height`. With `config.alpha` the image's
+    /// alpha channel is evolved too.
+    pub fn new(image_bytes: &[u8], config: GaConfig) -> Result<Self, Box<dyn Error>> {
+        let img = image::load_from_memory(image_bytes)?;
+        let (width, height) = (config.width as u32, config.height as u32);
+        let target = fit_target(&img, width, height, config.aspect, config.resize_filter, config.grayscale, None);
+        let target_alpha = config.alpha.then(|| fit_alpha(&img, width, height, config.aspect, config.resize_filter));
+        check_modes(&config, &Options::default(), std::slice::from_ref(&target))?;
+        if config.pyramid_levels > 1 {
+            return Err("Step-by-step reconstruction does not support pyramid levels".into());
+        }
+
+        let init_image = load_init_image(&config, target.width(), target.height())?;
+        let init_reference = init_image.as_ref().unwrap_or(&target);
+        let blurred_reference = blurred_init_reference(&config, init_reference);
+        let init_reference = blurred_reference.as_ref().unwrap_or(init_reference);
+        let target_deep = config.deep_color.then(|| widen(&target));
+        let space = config.color_space();
+        let mut grid = build_grid(&config, &target, init_reference, target_alpha.as_ref(), target_deep.as_ref(), None, space);
+        let palette_floor = config.palette.as_ref().map(|palette| palette_floor(&target, palette, &config));
+        let neighbors = (config.gradient_weight > 0.0)
+            .then(|| Arc::new(render_grid(&mut grid, None, &target, target_alpha.as_ref(), None, palette_floor.as_deref(), &config, None).frame));
+        let workers = Workers::new(config.threads)?;
+        Ok(Reconstruction { config, target, target_alpha, palette_floor, grid, workers, neighbors, generation: 0, stats: None })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.target.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.target.height()
+    }
+
+    /// Generations completed so far.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Fitness and RMSE statistics of the last generation, if any ran.
+    pub fn stats(&self) -> Option<&GenerationStats> {
+        self.stats.as_ref()
+    }
+
+    /// Runs one more generation and returns the best image as RGBA bytes,
+    /// row by row, as a canvas `ImageData` expects.
+    pub fn step_generation(&mut self) -> Vec<u8> {
+        if self.config.spatial_crossover_rate > 0.0 {
+            share_donors(&mut self.grid, &self.target);
+        }
+        step_cells(&self.workers, self.grid.iter_mut().flatten(), &self.target, &self.config, self.generation, self.neighbors.as_ref());
+        let rendered = render_grid(&mut self.grid, None, &self.target, self.target_alpha.as_ref(), None, self.palette_floor.as_deref(), &self.config, None);
+        if self.neighbors.is_some() {
+            self.neighbors = Some(Arc::new(rendered.frame.clone()));
+        }
+        self.generation += 1;
+        self.stats = Some(rendered.stats);
+        match &rendered.alpha {
+            Some(alpha) => with_alpha(&rendered.frame, alpha).into_raw(),
+            None => DynamicImage::ImageRgb8(rendered.frame).to_rgba8().into_raw(),
+        }
+    }
+}
+
+/// Runs `run_ga` on every file in `input_dir` (in name order), writing
+/// `<name>_result.png` and `<name>_result.gif` to `output_dir`. Files that
+/// fail to load or evolve are reported and skipped. Returns each image's
+/// name and final average fitness. Images whose grid would exceed
+/// `max_pixels` (see `check_grid_size`) are skipped too. Each image stops
+/// early once it has used `max_evaluations` fitness evaluations of its own.
+#[cfg(feature = "native")]
+pub fn run_batch(
+    input_dir: &str,
+    output_dir: &str,
+    config: &GaConfig,
+    max_pixels: Option<usize>,
+    max_evaluations: Option<u64>,
+) -> Result<Vec<(String, f64)>, Box<dyn Error>> {
+    config.validate()?;
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(input_dir)
+        .map_err(|e| format!("Failed to read {}: {}", input_dir, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    std::fs::create_dir_all(output_dir).map_err(|e| format!("Failed to create {}: {}", output_dir, e))?;
+
+    let mut results = Vec::new();
+    for path in paths {
+        let name = path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        match reconstruct_file(&path, &name, Path::new(output_dir), config, max_pixels, max_evaluations) {
+            Ok(fitness) => {
+                info!("{}: final average fitness {:.4}", name, fitness);
+                results.push((name, fitness));
+            }
+            Err(e) => warn!("Skipping {}: {}", path.display(), e),
+        }
+    }
+    info!("Reconstructed {} image(s) into {}", results.len(), output_dir);
+    Ok(results)
+}
+
+#[cfg(feature = "native")]
+fn reconstruct_file(
+    path: &Path,
+    name: &str,
+    output_dir: &Path,
+    config: &GaConfig,
+    max_pixels: Option<usize>,
+    max_evaluations: Option<u64>,
+) -> Result<f64, Box<dyn Error>> {
+    let path = path.to_str().ok_or("path is not valid UTF-8")?;
+    let source = source_dimensions(path)?;
+    let (width, height) = match config.keep_native_size {
+        true => source,
+        false => (config.width as u32, config.height as u32),
+    };
+    check_grid_size(width as usize, height as usize, Some(source), max_pixels)?;
+    let target = load_target_image(path, width, height, config.aspect, config.resize_filter, config.grayscale, None)?;
+
+    let mut evolution = Evolution::new(&target, config)?;
+    let mut frames = Vec::new();
+    let mut final_fitness = 0.0;
+    while let Some(generation) = evolution.next() {
+        frames.push((generation.generation + 1, generation.frame));
+        final_fitness = generation.stats.avg_fitness;
+        if max_evaluations.is_some_and(|max| evolution.fitness_counts().evaluations >= max) {
+            break;
+        }
+    }
+    let result = match frames.last() {
+        Some((_, frame)) => frame.clone(),
+        None => evolution.finish(),
+    };
+
+    result.save(output_dir.join(format!("{}_result.png", name)))?;
+    let gif_path = output_dir.join(format!("{}_result.gif", name));
+    create_simple_gif_from_frames(&frames, None, &gif_path.to_string_lossy(), 1, None, 256, false, AnimationTiming::default())?;
+    Ok(final_fitness)
+}
+
+#[cfg(feature = "native")]
+fn animation_path(options: &Options) -> String {
+    options.animation_output.clone().unwrap_or_else(|| format!("result.{}", options.video.extension()))
+}
+
+/// Where the snapshot of 1-based `generation` is saved: the output path with
+/// `_gen` and the generation number before the extension, zero-padded to at
+/// least four digits (or those of `generations`) so the files sort in order.
+#[cfg(feature = "native")]
+pub fn snapshot_path(output: &str, generation: usize, generations: usize) -> String {
+    let path = Path::new(output);
+    let stem = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let extension = path.extension().map_or("png".into(), |extension| extension.to_string_lossy());
+    let digits = generations.to_string().len().max(4);
+    let file = format!("{}_gen{:0digits$}.{}", stem, generation, extension, digits = digits);
+    path.with_file_name(file).to_string_lossy().into_owned()
+}
+
+// Encodes `image` as a PNG on stdout, for an output path of `-`.
+#[cfg(feature = "native")]
+fn write_png_to_stdout(image: &DynamicImage) -> Result<(), Box<dyn Error>> {
+    let mut png = Vec::new();
+    image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png)?;
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&png)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+// Opens `path` for writing without truncating it, removing it again if it
+// did not exist.
+#[cfg(feature = "native")]
+fn check_writable(path: &str) -> std::io::Result<()> {
+    let existed = Path::new(path).exists();
+    std::fs::OpenOptions::new().append(true).create(true).open(path)?;
+    if !existed {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+// Reports what `run_ga_with_output` would do with the loaded `targets`,
+// including a rough estimate of the memory the grid and frames need.
+#[cfg(feature = "native")]
+fn dry_run(config: &GaConfig, options: &Options, targets: &[RgbImage]) -> Result<(), Box<dyn Error>> {
+    check_modes(config, options, targets)?;
+    let (width, height) = targets[0].dimensions();
+    let block_size = config.block_size.max(1) as u32;
+    let cells = (width.div_ceil(block_size) * height.div_ceil(block_size)) as usize;
+    info!("Configuration: {:#?}", config);
+    info!("Target: {}x{} pixels, {} target frame(s)", width, height, targets.len());
+    info!("Grid: {}x{} cells of up to {}x{} pixels", width.div_ceil(block_size), height.div_ceil(block_size), block_size, block_size);
+
+    let pool = match config.algorithm {
+        Algorithm::Ga => config.population.size(1.0) * config.islands,
+        Algorithm::HillClimb => 1,
+    };
+    let channels = if config.palette.is_some() { 1 } else { config.color_space().channels() };
+    let pixels_per_cell = (width * height) as usize / cells;
+    let chromosome = size_of::<Chromosome>() + (channels * pixels_per_cell + config.alpha as usize) * size_of::<Channel>();
+    let grids = 1 + options.blend_hsv.is_some() as usize;
+    // The current and the previous generation are both held.
+    let population_bytes = grids * cells * pool * chromosome * 2;
+    let recorded = config.generation_limit().div_ceil(options.frame_stride.max(1)) + 1;
+    let frames = if options.records_frames() { recorded } else { 1 };
+    let frame_bytes = (frames + options.diff_gif.as_ref().map_or(0, |_| recorded)) * (width * height * 3) as usize;
+    let mib = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+    info!("Estimated memory: {:.1} MiB for {} chromosomes, {:.1} MiB for {} recorded frames",
+          mib(population_bytes), grids * cells * pool, mib(frame_bytes), frames);
+
+    let mut outputs = Vec::new();
+    outputs.extend(options.save_png.then(|| options.output.clone()).filter(|path| path != STDIO_PATH));
+    outputs.extend(options.save_gif.then(|| animation_path(options)));
+    outputs.extend(options.save_target.then(|| "target_sample.png".to_string()));
+    let optional = [&options.diff_gif, &options.convergence_map, &options.fitness_map, &options.comparison, &options.fitness_plot, &options.quality_report, &options.output_html, &options.stats_csv, &options.manifest];
+    outputs.extend(optional.into_iter().flatten().cloned());
+    outputs.extend(options.sprite_sheet.as_ref().map(|(path, _)| path.clone()));
+    outputs.extend(options.checkpoint.as_ref().map(|(path, _)| path.clone()));
+    outputs.extend(options.snapshot_interval.map(|every| snapshot_path(&options.output, every, config.generation_limit())));
+    for path in &outputs {
+        check_writable(path).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+    }
+    info!("Outputs are writable: {}", outputs.join(", "));
+    if options.save_gif && options.video.uses_ffmpeg() {
+        check_ffmpeg()?;
+        info!("ffmpeg is available for the {} video", options.video.extension());
+    }
+    info!("Dry run complete; no generations were run");
+    Ok(())
+}
+
+/// With `options.auto_mode`, the config to run a target decoded as `color`
+/// with, if it differs from `config`: grayscale sources are evolved in
+/// grayscale mode. Modes that need color channels (`alpha`, `palette`,
+/// `deep_color`, HSV genes, per-channel rates, `blend_hsv`) count as an
+/// explicit choice of RGB and keep it.
+#[cfg(feature = "native")]
+pub fn auto_mode(config: &GaConfig, options: &Options, color: ColorType) -> Option<GaConfig> {
+    let needs_color = config.alpha
+        || config.palette.is_some()
+        || config.deep_color
+        || config.encoding_space == EncodingSpace::Hsv
+        || config.channel_mutation_rates.is_some()
+        || config.channel_crossover_rates.is_some()
+        || options.blend_hsv.is_some();
+    let switch = options.auto_mode && !color.has_color() && !config.grayscale && !needs_color;
+    switch.then(|| GaConfig { grayscale: true, ..config.clone() })
+}
+
+/// Largest side of the target copy `Options::determinism_check` evolves.
+pub const DETERMINISM_CHECK_SIZE: u32 = 32;
+
+// Evolves `target`, shrunk to at most DETERMINISM_CHECK_SIZE a side, twice
+// with the same seed: once on the configured workers and once sequentially.
+// Fails with the number of differing pixels and the largest channel
+// difference if the results are not identical.
+#[cfg(feature = "native")]
+fn determinism_check(config: &GaConfig, options: &Options, target: &RgbImage) -> Result<(), Box<dyn Error>> {
+    let (width, height) = (target.width().min(DETERMINISM_CHECK_SIZE), target.height().min(DETERMINISM_CHECK_SIZE));
+    let target = imageops::resize(target, width, height, config.resize_filter);
+    let seed = config.seed.unwrap_or_else(rand::random);
+    let config = GaConfig { width: width as usize, height: height as usize, seed: Some(seed), ..config.clone() };
+    info!("Checking determinism on a {}x{} copy of the target with seed {}", width, height, seed);
+
+    let run = |threads| -> Result<RgbImage, Box<dyn Error>> {
+        let config = GaConfig { threads, ..config.clone() };
+        let target_alpha = config.alpha.then(|| GrayImage::from_pixel(width, height, Luma([255])));
+        let init_image = load_init_image(&config, width, height)?;
+        let init_reference = init_image.as_ref().unwrap_or(&target);
+        Ok(Evolution::start(&config, options, vec![target.clone()], target_alpha, None, None, init_reference, None)?.finish())
+    };
+    let (first, second) = (run(config.threads)?, run(Some(1))?);
+    let differing = changed_pixels(&first, &second);
+    if differing > 0 {
+        return Err(format!(
+            "Determinism check failed with seed {}: {} of {} pixels differ, by up to {} in a channel",
+            seed,
+            differing,
+            width * height,
+            max_channel_delta(&first, &second)
+        )
+        .into());
+    }
+    info!("Determinism check passed: both runs produced the same image");
+    Ok(())
+}
+
+/// Largest side, in pixels, of the target copy `--compare-crossover` evolves.
+pub const COMPARE_CROSSOVER_SIZE: u32 = 32;
+
+/// Every crossover operator, in the order `--compare-crossover` runs them.
+pub const CROSSOVER_KINDS: [CrossoverKind; 3] = [CrossoverKind::Uniform, CrossoverKind::SinglePoint, CrossoverKind::TwoPoint];
+
+/// One row of the `--compare-crossover` table.
+#[derive(Clone, Debug)]
+pub struct CrossoverResult {
+    pub crossover: CrossoverKind,
+    /// PSNR of the final frame against the shrunk target, in dB.
+    pub psnr: f64,
+    /// Generations until every pixel matched, if the run got there.
+    pub generations_to_target: Option<usize>,
+    pub generations: usize,
+    pub runtime: Duration,
+}
+
+/// Evolves `target`, shrunk to at most `COMPARE_CROSSOVER_SIZE` a side, once
+/// per crossover operator. Every run starts from the same seed and config,
+/// so the operator is the only thing that differs between rows.
+#[cfg(feature = "native")]
+pub fn compare_crossover(config: &GaConfig, options: &Options, target: &RgbImage) -> Result<Vec<CrossoverResult>, Box<dyn Error>> {
+    if config.algorithm != Algorithm::Ga {
+        return Err("--compare-crossover needs the genetic algorithm; hill climbing never crosses over".into());
+    }
+    let (width, height) = (target.width().min(COMPARE_CROSSOVER_SIZE), target.height().min(COMPARE_CROSSOVER_SIZE));
+    let target = imageops::resize(target, width, height, config.resize_filter);
+    let seed = config.seed.unwrap_or_else(rand::random);
+    info!("Comparing crossover operators on a {}x{} copy of the target with seed {}", width, height, seed);
+
+    CROSSOVER_KINDS
+        .iter()
+        .map(|&crossover| {
+            let config = GaConfig { width: width as usize, height: height as usize, seed: Some(seed), crossover, ..config.clone() };
+            let target_alpha = config.alpha.then(|| GrayImage::from_pixel(width, height, Luma([255])));
+            let init_image = load_init_image(&config, width, height)?;
+            let init_reference = init_image.as_ref().unwrap_or(&target);
+            let start = Instant::now();
+            let mut evolution = Evolution::start(&config, options, vec![target.clone()], target_alpha, None, None, init_reference, None)?;
+            let (mut last, mut generations_to_target) = (None, None);
+            for generation in evolution.by_ref() {
+                if generation.solved {
+                    generations_to_target = Some(generation.generation + 1);
+                }
+                last = Some(generation);
+            }
+            let runtime = start.elapsed();
+            let last = last.ok_or("The evolution produced no generations")?;
+            Ok(CrossoverResult {
+                crossover,
+                psnr: quality_report(&last.frame, &target).psnr,
+                generations_to_target,
+                generations: last.generation + 1,
+                runtime,
+            })
+        })
+        .collect()
+}
+
+// Logs the `compare_crossover` results as a table, one operator per row.
+#[cfg(feature = "native")]
+fn log_crossover_table(results: &[CrossoverResult]) {
+    info!("{:<14} {:>10} {:>20} {:>12}", "crossover", "PSNR (dB)", "generations to target", "runtime (s)");
+    for result in results {
+        let solved = match result.generations_to_target {
+            Some(generations) => generations.to_string(),
+            None => format!("not in {}", result.generations),
+        };
+        info!("{:<14} {:>10.2} {:>20} {:>12.3}", format!("{:?}", result.crossover), result.psnr, solved, result.runtime.as_secs_f64());
+    }
+}
+
+#[cfg(feature = "native")]
+pub fn run_ga_with_output(config: &GaConfig, options: &Options) -> Result<(), Box<dyn Error>> {
+    config.validate()?;
+    if options.output == STDIO_PATH {
+        logging::reserve_stdout();
+    }
+    let input = options.input.as_deref().unwrap_or("target.png");
+    let target_loaded = options.target_sequence.is_none() && (options.input.is_some() || Path::new(input).exists());
+    let source = match options.target_sequence.as_deref().or(target_loaded.then_some(input)) {
+        Some(path) => Some(source_dimensions(path).map_err(|e| format!("Failed to read {}: {}", path, e))?),
+        None => None,
+    };
+    let native_config;
+    let config = match source {
+        Some((width, height)) if config.keep_native_size => {
+            native_config = GaConfig { width: width as usize, height: height as usize, ..config.clone() };
+            &native_config
+        }
+        _ => config,
+    };
+    check_grid_size(config.width, config.height, source, options.pixel_budget())?;
+    let target_deep = match (config.deep_color, target_loaded) {
+        (true, true) => Some(
+            load_target_image16(input, config.width as u32, config.height as u32, config.aspect, config.resize_filter)
+                .map_err(|e| format!("Failed to load {}: {}", input, e))?,
+        ),
+        _ => None,
+    };
+    let mut source_color = None;
+    let targets = if let Some(path) = &options.target_sequence {
+        let frames = load_target_sequence(path, config.width as u32, config.height as u32, config.aspect, config.resize_filter, config.grayscale, options.quantize_target)
+            .map_err(|e| format!("Failed to load target sequence {}: {}", path, e))?;
+        info!("Loaded {} target frames from {}", frames.len(), path);
+        frames
+    } else if let Some(deep) = &target_deep {
+        info!("Target image loaded successfully (16-bit)");
+        vec![top_byte(deep)]
+    } else if target_loaded {
+        let (img, color) = load_target_image_with_color(input, config.width as u32, config.height as u32, config.aspect, config.resize_filter, config.grayscale, options.quantize_target)
+            .map_err(|e| format!("Failed to load {}: {}", input, e))?;
+        info!("Target image loaded successfully");
+        source_color = Some(color);
+        vec![img]
+    } else {
+        info!("Could not find target.png, using generated sample image");
+        vec![create_sample_image(options.sample_pattern, config.width as u32, config.height as u32)]
+    };
+
+    let auto_config = source_color.and_then(|color| auto_mode(config, options, color));
+    if auto_config.is_some() {
+        info!("{} is stored as grayscale, evolving in grayscale mode", input);
+    }
+    let config = auto_config.as_ref().unwrap_or(config);
+
+    let targets: Vec<RgbImage> = match &options.recolor_palette {
+        Some(palette) if !palette.is_empty() => targets.iter().map(|target| recolor_target(target, palette)).collect(),
+        _ => targets,
+    };
+    let target_image = &targets[0];
+    let (width, height) = (target_image.width() as usize, target_image.height() as usize);
+
+    let target_alpha = match (config.alpha, target_loaded) {
+        (true, true) => Some(
+            load_target_alpha(input, config.width as u32, config.height as u32, config.aspect, config.resize_filter)
+                .map_err(|e| format!("Failed to load {}: {}", input, e))?,
+        ),
+        (true, false) => Some(GrayImage::from_pixel(width as u32, height as u32, Luma([255]))),
+        (false, _) => None,
+    };
+
+    let roi = match &options.roi_mask {
+        Some(path) => {
+            let mask = load_roi_mask(path, width as u32, height as u32, config.aspect, config.resize_filter)
+                .map_err(|e| format!("Failed to load ROI mask {}: {}", path, e))?;
+            if mask.pixels().all(|p| p[0] == 0) {
+                return Err(format!("ROI mask {} is black everywhere", path).into());
+            }
+            Some(mask)
+        }
+        None => None,
+    };
+
+    let init_image = load_init_image(config, width as u32, height as u32)?;
+    let init_reference = init_image.as_ref().unwrap_or(target_image);
+
+    if options.dry_run {
+        return dry_run(config, options, &targets);
+    }
+    if options.determinism_check {
+        return determinism_check(config, options, target_image);
+    }
+    if options.compare_crossover {
+        log_crossover_table(&compare_crossover(config, options, target_image)?);
+        return Ok(());
+    }
+    if options.save_gif && options.video.uses_ffmpeg() {
+        check_ffmpeg()?;
+    }
+    let best_config;
+    let config = if options.trials > 1 {
+        let master = config.seed.unwrap_or_else(rand::random);
+        info!("Running {} trials from master seed {}", options.trials, master);
+        let trials = evolve_trials(config, master, options.trials, |config| {
+            let mut evolution = Evolution::start(config, options, targets.clone(), target_alpha.clone(), target_deep.as_ref(), roi.clone(), init_reference, None)?;
+            let last = evolution.by_ref().last().ok_or("The evolution produced no generations")?;
+            Ok((last.frame, last.stats.avg_fitness))
+        })?;
+        for (index, trial) in trials.iter().enumerate() {
+            info!("Trial {}: seed {}, average fitness {:.4}", index + 1, trial.seed, trial.avg_fitness);
+        }
+        let best = &trials[best_trial(&trials).unwrap_or(0)];
+        info!("Keeping the trial with seed {}; rerunning it to write the outputs", best.seed);
+        best_config = GaConfig { seed: Some(best.seed), ..config.clone() };
+        &best_config
+    } else {
+        config
+    };
+
+    let resume = match &options.checkpoint {
+        Some((path, _)) if Path::new(path).exists() => {
+            let checkpoint = load_checkpoint(path).map_err(|e| format!("Failed to load checkpoint {}: {}", path, e))?;
+            if checkpoint.generation >= config.generation_limit() {
+                info!("Checkpoint {} already covers all {} generations", path, config.generation_limit());
+            } else {
+                info!("Resuming from {} at generation {}", path, checkpoint.generation);
+            }
+            Some(checkpoint)
+        }
+        _ => None,
+    };
+    let resumed = resume.is_some();
+    let start_gen = resume.as_ref().map_or(config.pyramid_start(), |checkpoint| checkpoint.generation);
+    if !resumed && start_gen > 0 {
+        info!("Evolving {} coarser levels for {} generations before full resolution", config.pyramid_levels - 1, start_gen);
+    }
+
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut diff_frames = Vec::new();
+    let mut alpha_frames = Vec::new();
+    // The newest generation's frame, alpha and difference image, recorded
+    // after the loop if the stride skipped it.
+    let mut latest: Option<(Frame, Option<GrayImage>, Option<Frame>)> = None;
+    let mut stats = Vec::new();
+    let mut previous_fitness: Option<Vec<Fitness>> = None;
+    let mut settled_generations = 0;
+    let mut deep_frame = None;
+    let mut final_fitness_map = None;
+    // 1-based generation each pixel first matched its target, row-major.
+    let mut first_match: Vec<Option<usize>> = vec![None; width * height];
+    let mut snapshots = Vec::new();
+    // In palette mode a pixel is matched once it shows its nearest palette color.
+    let match_label = if config.palette.is_some() { "Nearest palette matches" } else { "Perfect matches" };
+
+    let progress = (logging::enabled(Level::Info) && std::io::stderr().is_terminal())
+        .then(|| ProgressBar { start: Instant::now(), first: start_gen, total: config.generation_limit() });
+    if let Some(bar) = &progress {
+        bar.draw(start_gen, None);
+    }
+
+    let started = Instant::now();
+    let mut evolution = Evolution::start(config, options, targets.clone(), target_alpha.clone(), target_deep.as_ref(), roi.clone(), init_reference, resume)?;
+    while let Some(mut generation) = evolution.next() {
+        if let Some(strength) = options.block_smoothing {
+            generation.frame = smooth_blocks(&generation.frame, config.block_size as u32, strength);
+        }
+        let gen = generation.generation;
+        let s = &generation.stats;
+        let target = &evolution.targets[generation.target_index];
+        match &progress {
+            Some(bar) => bar.clear(),
+            None => info!("Generation {}/{}", gen + 1, config.generation_limit()),
+        }
+
+        let level = if gen % 25 == 0 || gen == config.generation_limit() - 1 { Level::Info } else { Level::Trace };
+        if logging::enabled(level) {
+            log!(level, "  Average fitness: {:.4}, Normalized: {:.4}, {}: {:.2}% ({}/{})", 
+                     s.avg_fitness, s.normalized_fitness, match_label, s.match_percent, s.perfect_matches, s.weighted_pixels);
+            match config.palette {
+                Some(_) => log!(level, "  RMSE - Avg: {:.2}, Max: {:.2}, Palette floor: {:.2}", s.avg_rmse, s.max_rmse, s.quantization_rmse),
+                None => log!(level, "  RMSE - Avg: {:.2}, Max: {:.2}", s.avg_rmse, s.max_rmse),
+            }
+            if s.frozen_pixels > 0 {
+                log!(level, "  Frozen pixels: {}/{}", s.frozen_pixels, width * height);
+            }
+
+            let (avg_fit, max_fit, min_fit, std_dev) = grid_fitness_stats(&evolution.grid, target);
+            log!(level, "  Cell fitness - Avg: {:.4}, Max: {:.4}, Min: {:.4}, Std dev: {:.4}", avg_fit, max_fit, min_fit, std_dev);
+        }
+        
+        if logging::enabled(Level::Debug) {
+            if let Some(previous) = &previous_fitness {
+                let (cell, delta) = generation.cell_fitness
+                    .iter()
+                    .zip(previous)
+                    .map(|(now, before)| now - before)
+                    .enumerate()
+                    .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                    .unwrap();
+                debug!("  Most improved pixel: ({}, {}) {:+.4}", cell % width, cell / width, delta);
+            }
+        }
+        previous_fitness = Some(generation.cell_fitness.to_vec());
+
+        let settled = match (options.settle, &latest) {
+            (Some((max_changed, generations)), Some(((_, previous), ..))) => {
+                if changed_pixels(previous, &generation.frame) <= max_changed {
+                    settled_generations += 1;
+                } else {
+                    settled_generations = 0;
+                }
+                settled_generations >= generations
+            }
+            _ => false,
+        };
+
+        if let Some((path, every)) = &options.checkpoint {
+            if (gen + 1) % every == 0 {
+                let evaluations = evolution.fitness_counts().evaluations;
+                save_checkpoint(&evolution.grid, evolution.hsv_grid.as_deref(), gen + 1, evaluations, path)
+                    .map_err(|e| format!("Failed to save checkpoint: {}", e))?;
+                debug!("  Checkpoint saved as {}", path);
+            }
+        }
+
+        if let Some(every) = options.snapshot_interval {
+            if (gen + 1) % every == 0 {
+                let path = snapshot_path(&options.output, gen + 1, config.generation_limit());
+                let saved = match generation.alpha.as_ref() {
+                    Some(alpha) => with_alpha(&generation.frame, alpha).save(&path),
+                    None => generation.frame.save(&path),
+                };
+                saved.map_err(|e| format!("Failed to save snapshot {}: {}", path, e))?;
+                debug!("  Snapshot saved as {}", path);
+                snapshots.push(path);
+            }
+        }
+
+        let number = gen - start_gen + 1;
+        let diff = options.diff_gif.is_some().then(|| (number, difference_image(&generation.frame, target)));
+        if (number - 1).is_multiple_of(options.frame_stride.max(1)) {
+            if options.records_frames() {
+                frames.push((number, generation.frame.clone()));
+                alpha_frames.extend(generation.alpha.clone());
+            }
+            diff_frames.extend(diff.clone());
+        }
+        latest = Some(((number, generation.frame.clone()), generation.alpha.clone(), diff));
+        if config.deep_color {
+            deep_frame = Some(render_deep(&evolution.grid, target));
+        }
+        if options.fitness_map.is_some() {
+            final_fitness_map = Some(fitness_map(&generation.frame, target));
+        }
+        for (first, &matched) in first_match.iter_mut().zip(&generation.matched) {
+            if first.is_none() && matched {
+                *first = Some(gen + 1);
+            }
+        }
+        stats.push(s.clone());
+
+        if generation.solved {
+            info!("All pixels match the target after {} generations", gen + 1);
+            break;
+        }
+        if settled {
+            info!("Image settled after {} generations", gen + 1);
+            break;
+        }
+        if generation.plateaued {
+            info!("Average fitness plateaued after {} generations", gen + 1);
+            break;
+        }
+        if generation.immigrated {
+            info!("Average fitness plateaued after {} generations, injecting random immigrants", gen + 1);
+        }
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            info!("Interrupted after {} generations, saving partial results", gen + 1);
+            break;
+        }
+        if gen + 1 < config.generation_limit() {
+            if let Some(max_evaluations) = options.max_evaluations {
+                if evolution.fitness_counts().evaluations >= max_evaluations {
+                    info!("Evaluation budget of {} reached after {} generations", max_evaluations, gen + 1);
+                    break;
+                }
+            }
+            if let Some(bar) = &progress {
+                bar.draw(gen + 1, Some(s.avg_fitness));
+            }
+        }
+    }
+    let elapsed = started.elapsed();
+    let counts = evolution.fitness_counts();
+
+    if let Some((frame, alpha, diff)) = latest {
+        if frames.last().is_none_or(|(number, _)| *number != frame.0) {
+            frames.push(frame);
+            alpha_frames.extend(alpha);
+        }
+        diff_frames.extend(diff.filter(|diff| diff_frames.last().is_none_or(|(number, _)| *number != diff.0)));
+    }
+
+    let mut artifacts: Vec<(String, &str)> = Vec::new();
+
+    if let (true, Some((_, final_frame))) = (options.save_png, frames.last()) {
+        let result = match (&deep_frame, alpha_frames.last()) {
+            (Some(deep), _) => DynamicImage::ImageRgb16(deep.clone()),
+            (None, Some(alpha)) => DynamicImage::ImageRgba8(with_alpha(final_frame, alpha)),
+            (None, None) => DynamicImage::ImageRgb8(final_frame.clone()),
+        };
+        if options.output == STDIO_PATH {
+            write_png_to_stdout(&result).map_err(|e| format!("Failed to write result image to stdout: {}", e))?;
+            info!("Result written to stdout");
+        } else {
+            result.save(&options.output).map_err(|e| format!("Failed to save result image: {}", e))?;
+            info!("Result saved as {}", options.output);
+            artifacts.push((options.output.clone(), "png"));
+        }
+    }
+    if !snapshots.is_empty() {
+        info!("{} snapshots saved, from {} to {}", snapshots.len(), snapshots[0], snapshots[snapshots.len() - 1]);
+    }
+    artifacts.extend(snapshots.into_iter().map(|path| (path, "png")));
+    if let Some(last) = stats.last() {
+        info!("SSIM vs target: {:.4}", last.ssim);
+        info!("RMSE vs target: {:.2} average, {:.2} max", last.avg_rmse, last.max_rmse);
+        if config.palette.is_some() {
+            info!("Palette quantization floor: {:.2} average RMSE", last.quantization_rmse);
+        }
+    }
+    let converged: Vec<usize> = first_match.iter().flatten().copied().collect();
+    if !converged.is_empty() {
+        let matched = if config.palette.is_some() { "reached their nearest palette color" } else { "matched exactly" };
+        info!("Pixels that {}: {}/{}, after {:.1} generations on average",
+              matched, converged.len(), first_match.len(), converged.iter().sum::<usize>() as f64 / converged.len() as f64);
+    }
+
+    let timing = AnimationTiming {
+        frame_delay: options.frame_delay,
+        loop_count: options.loop_count,
+        max_frames: options.max_frames,
+        interpolation_steps: options.interpolation_steps,
+    };
+    if options.save_gif {
+        let animation_path = animation_path(options);
+        let alphas = target_alpha.is_some().then_some(alpha_frames.as_slice());
+        match options.video {
+            VideoFormat::Gif => {
+                create_simple_gif_from_frames(&frames, alphas, &animation_path, options.gif_scale, options.gif_counter, options.gif_colors, options.dither, timing)
+                    .map_err(|e| format!("Failed to create GIF: {}", e))?;
+                info!("GIF saved as {}", animation_path);
+            }
+            VideoFormat::Apng => {
+                create_apng_from_frames(&frames, alphas, &animation_path, options.gif_scale, options.gif_counter, timing)
+                    .map_err(|e| format!("Failed to create APNG: {}", e))?;
+                info!("APNG saved as {}", animation_path);
+            }
+            VideoFormat::Mp4 | VideoFormat::WebM => {
+                create_video_from_frames(&frames, &animation_path, options.video, options.gif_scale, options.gif_counter, timing)
+                    .map_err(|e| format!("Failed to create video: {}", e))?;
+                info!("Video saved as {}", animation_path);
+            }
+        }
+        artifacts.push((animation_path, options.video.extension()));
+    }
+
+    if let Some(path) = &options.diff_gif {
+        create_simple_gif_from_frames(&diff_frames, None, path, options.gif_scale, options.gif_counter, options.gif_colors, options.dither, timing)
+            .map_err(|e| format!("Failed to create difference GIF: {}", e))?;
+        info!("Difference GIF saved as {}", path);
+        artifacts.push((path.clone(), "gif"));
+    }
+
+    if let Some(path) = &options.convergence_map {
+        let generations = start_gen + stats.len();
+        convergence_map(&first_match, width as u32, height as u32, generations)
+            .save(path)
+            .map_err(|e| format!("Failed to save convergence map: {}", e))?;
+        info!("Convergence map saved as {}", path);
+        artifacts.push((path.clone(), "png"));
+    }
+
+    if let (Some(path), Some(map)) = (&options.fitness_map, &final_fitness_map) {
+        map.save(path).map_err(|e| format!("Failed to save fitness map: {}", e))?;
+        info!("Fitness map saved as {}", path);
+        artifacts.push((path.clone(), "png"));
+    }
+
+    if let (Some(path), Some((_, final_frame)), Some(last)) = (&options.comparison, frames.last(), stats.last()) {
+        let caption = options.comparison_labels.then(|| format!("RMSE {:.2} SSIM {:.4}", last.avg_rmse, last.ssim));
+        comparison_image(target_image, final_frame, options.comparison_divider, caption.as_deref())
+            .save(path)
+            .map_err(|e| format!("Failed to save comparison image: {}", e))?;
+        info!("Comparison image saved as {}", path);
+        artifacts.push((path.clone(), "png"));
+    }
+
+    if let Some(path) = &options.fitness_plot {
+        create_fitness_plot(&stats, options.fitness_plot_band)
+            .save(path)
+            .map_err(|e| format!("Failed to save fitness plot: {}", e))?;
+        info!("Fitness plot saved as {}", path);
+        artifacts.push((path.clone(), "png"));
+    }
+
+    if let (Some(path), Some((_, final_frame))) = (&options.quality_report, frames.last()) {
+        let report = quality_report(final_frame, target_image);
+        info!("Quality report (driven by {:?}):", config.fitness);
+        info!("  RGB RMSE: {:.2} average, {:.2} max, PSNR {:.2} dB", report.rgb_rmse, report.max_rgb_rmse, report.psnr);
+        info!("  CIEDE2000: {:.2} average, {:.2} max", report.delta_e, report.max_delta_e);
+        info!("  SSIM: {:.4}", report.ssim);
+        info!("  Average fitness: {:.4} under rgb, {:.4} under ciede2000", report.rgb_fitness, report.ciede2000_fitness);
+        write_quality_report(&report, config, path).map_err(|e| format!("Failed to write quality report: {}", e))?;
+        info!("Quality report saved as {}", path);
+        artifacts.push((path.clone(), "json"));
+    }
+
+    if let Some(path) = &options.output_html {
+        write_html_viewer(&frames, &stats, path, options.max_frames).map_err(|e| format!("Failed to create HTML viewer: {}", e))?;
+        info!("HTML viewer saved as {}", path);
+        artifacts.push((path.clone(), "html"));
+    }
+
+    if let Some(path) = &options.stats_csv {
+        write_stats_csv(&stats, path).map_err(|e| format!("Failed to write statistics: {}", e))?;
+        info!("Statistics saved as {}", path);
+        artifacts.push((path.clone(), "csv"));
+    }
+
+    if let Some((path, cols)) = &options.sprite_sheet {
+        create_sprite_sheet(&frames, path, *cols, options.sprite_sheet_labels, options.max_frames)
+            .map_err(|e| format!("Failed to create sprite sheet: {}", e))?;
+        info!("Sprite sheet saved as {}", path);
+        artifacts.push((path.clone(), "png"));
+    }
+
+    if options.save_target {
+        let saved = match (&target_deep, &target_alpha) {
+            (Some(deep), _) => deep.save("target_sample.png"),
+            (None, Some(alpha)) => with_alpha(target_image, alpha).save("target_sample.png"),
+            (None, None) => target_image.save("target_sample.png"),
+        };
+        saved.map_err(|e| format!("Failed to save target image: {}", e))?;
+        info!("Target image saved as target_sample.png");
+        artifacts.push(("target_sample.png".to_string(), "png"));
+    }
+
+    if options.sidecar {
+        let source_path = options.target_sequence.as_deref().or(target_loaded.then_some(input));
+        let (source, source_sha256) = if let Some(path) = source_path {
+            let sha = if path == STDIO_PATH || Path::new(path).is_file() {
+                Some(format!("{:x}", Sha256::digest(read_source(path)?)))
+            } else {
+                None
+            };
+            (path, sha)
+        } else {
+            ("generated sample", None)
+        };
+
+        let outputs: Vec<String> = artifacts
+            .iter()
+            .filter(|(path, kind)| matches!(*kind, "png" | "gif" | "mp4" | "webm") && path != "target_sample.png")
+            .map(|(path, _)| path.clone())
+            .collect();
+        for output in outputs {
+            let sidecar_path = write_sidecar(&output, source, source_sha256.as_deref(), config, options)
+                .map_err(|e| format!("Failed to write metadata for {}: {}", output, e))?;
+            info!("Metadata saved as {}", sidecar_path);
+            artifacts.push((sidecar_path, "json"));
+        }
+    }
+
+    if let Some(path) = &options.manifest {
+        write_manifest(&artifacts, config, options, path).map_err(|e| format!("Failed to write manifest: {}", e))?;
+        info!("Manifest saved as {}", path);
+    }
+
+    info!("Summary:");
+    let resumed_at = if resumed { format!(", resumed at {}", start_gen) } else { String::new() };
+    info!("  Generations run: {}/{}{}, in {:.2?}", start_gen + stats.len(), config.generation_limit(), resumed_at, elapsed);
+    if let Some(last) = stats.last() {
+        info!("  Final average fitness: {:.4}, {}: {:.2}%", last.avg_fitness, match_label.to_lowercase(), last.match_percent);
+    }
+    let written: Vec<&str> = artifacts.iter().map(|(path, _)| path.as_str()).chain(options.manifest.as_deref()).collect();
+    info!("  Outputs: {}", written.join(", "));
+
+    info!("Fitness evaluations: {}", counts.evaluations);
+    let (lookups, hits) = (counts.cache_lookups, counts.cache_hits);
+    info!("Fitness cache hit rate: {:.2}% ({}/{})", hits as f64 / lookups.max(1) as f64 * 100.0, hits, lookups);
+    if config.diversity_threshold.is_some() {
+        info!("Diversity restarts: {}", counts.restarts);
+    }
+    info!("GA process completed!");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ga::chromosome::GeneEncoding;
+
+    #[test]
+    fn run_ga_reports_every_generation_and_returns_the_last_frame() {
+        let config = GaConfig { iterations: 5, seed: Some(3), ..GaConfig::default() };
+        let target = RgbImage::from_fn(4, 3, |x, y| Rgb([x as u8 * 60, y as u8 * 80, 128]));
+
+        let mut generations = Vec::new();
+        let mut last = None;
+        let result = run_ga(&target, &config, |gen, frame, avg_fitness| {
+            assert!(avg_fitness > 0.0);
+            generations.push(gen);
+            last = Some(frame.clone());
+        })
+        .unwrap();
+
+        assert_eq!(generations, [0, 1, 2, 3, 4]);
+        assert_eq!(result.dimensions(), (4, 3));
+        assert_eq!(Some(result), last);
+    }
+
+    #[test]
+    fn reconstruct_image_returns_the_final_fitness() {
+        let config = GaConfig { iterations: 4, seed: Some(2), ..GaConfig::default() };
+        let target = RgbImage::from_fn(3, 3, |x, y| Rgb([x as u8 * 90, 40, y as u8 * 90]));
+
+        let mut last_fitness = 0.0;
+        run_ga(&target, &config, |_, _, avg_fitness| last_fitness = avg_fitness).unwrap();
+        let (image, fitness) = reconstruct_image(&target, &config).unwrap();
+        assert_eq!(image.dimensions(), (3, 3));
+        assert_eq!(fitness, last_fitness);
+
+        assert!(reconstruct_image(&RgbImage::new(0, 0), &config).is_err());
+    }
+
+    #[test]
+    fn target_blur_init_starts_from_the_blurred_target() {
+        let init = InitStrategy::TargetBlur { sigma: 1.0, noise: 0.0 };
+        let config = GaConfig { iterations: 0, seed: Some(4), init, ..GaConfig::default() };
+        let target = RgbImage::from_fn(6, 4, |x, y| if (x + y) % 2 == 0 { Rgb([255, 0, 0]) } else { Rgb([0, 0, 255]) });
+
+        let result = run_ga(&target, &config, |_, _, _| {}).unwrap();
+        assert_eq!(result, imageops::blur(&target, 1.0));
+    }
+
+    #[test]
+    fn seeded_inits_start_from_their_source_and_missing_images_fail() {
+        let target = RgbImage::from_fn(5, 3, |x, y| Rgb([x as u8 * 50, y as u8 * 90, 17]));
+        let init_config = |init| GaConfig { iterations: 0, seed: Some(6), init, ..GaConfig::default() };
+
+        let solid = run_ga(&target, &init_config(InitStrategy::Solid { color: [9, 80, 200] }), |_, _, _| {}).unwrap();
+        assert!(solid.pixels().all(|pixel| pixel.0 == [9, 80, 200]));
+        let copy = run_ga(&target, &init_config(InitStrategy::FromTarget { noise: 0.0 }), |_, _, _| {}).unwrap();
+        assert_eq!(copy, target);
+
+        let missing = InitStrategy::FromImage { path: "no-such-init-image.png".into(), noise: 0.0 };
+        let error = run_ga(&target, &init_config(missing), |_, _, _| {}).unwrap_err();
+        assert!(error.to_string().contains("no-such-init-image.png"), "{}", error);
+    }
+
+    #[test]
+    fn reconstruction_steps_match_run_ga() {
+        let config = GaConfig { width: 4, height: 3, iterations: 3, seed: Some(5), ..GaConfig::default() };
+        let target = RgbImage::from_fn(4, 3, |x, y| Rgb([x as u8 * 60, y as u8 * 80, 200]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        target.write_to(&mut png, image::ImageOutputFormat::Png).unwrap();
+
+        let mut reconstruction = Reconstruction::new(png.get_ref(), config.clone()).unwrap();
+        let frames: Vec<Vec<u8>> = (0..3).map(|_| reconstruction.step_generation()).collect();
+        assert_eq!(reconstruction.generation(), 3);
+
+        let expected = run_ga(&target, &config, |_, _, _| {}).unwrap();
+        assert_eq!(frames[2], DynamicImage::ImageRgb8(expected).to_rgba8().into_raw());
+        assert!(Reconstruction::new(b"not an image", config).is_err());
+    }
+
+    #[test]
+    fn stats_report_raw_rmse_alongside_fitness() {
+        let target = RgbImage::from_fn(4, 3, |x, y| Rgb([x as u8 * 60, y as u8 * 80, 200]));
+        let config = GaConfig { width: 4, height: 3, seed: Some(2), ..GaConfig::default() };
+        let mut grid = build_grid(&config, &target, &target, None, None, None, ColorSpace::Rgb);
+        let stats = render_grid(&mut grid, None, &target, None, None, None, &config, None).stats;
+        assert!(stats.avg_rmse > 0.0 && stats.avg_rmse <= stats.max_rmse);
+
+        let exact = GaConfig { init: InitStrategy::FromTarget { noise: 0.0 }, ..config };
+        let mut grid = build_grid(&exact, &target, &target, None, None, None, ColorSpace::Rgb);
+        let stats = render_grid(&mut grid, None, &target, None, None, None, &exact, None).stats;
+        assert_eq!((stats.avg_rmse, stats.max_rmse, stats.avg_fitness), (0.0, 0.0, 2.0));
+    }
+
+    #[test]
+    fn perfect_matches_have_normalized_fitness_one_under_every_metric() {
+        let target = RgbImage::from_fn(4, 3, |x, y| Rgb([x as u8 * 60, y as u8 * 80, 200]));
+        for fitness in [FitnessMetric::RgbRmse, FitnessMetric::CieDe2000] {
+            let config = GaConfig { width: 4, height: 3, seed: Some(2), fitness, ..GaConfig::default() };
+            let mut grid = build_grid(&config, &target, &target, None, None, None, ColorSpace::Rgb);
+            let stats = render_grid(&mut grid, None, &target, None, None, None, &config, None).stats;
+            assert!(stats.normalized_fitness < 1.0);
+
+            let exact = GaConfig { init: InitStrategy::FromTarget { noise: 0.0 }, ..config };
+            let mut grid = build_grid(&exact, &target, &target, None, None, None, ColorSpace::Rgb);
+            let stats = render_grid(&mut grid, None, &target, None, None, None, &exact, None).stats;
+            assert_eq!(stats.normalized_fitness, 1.0);
+        }
+    }
+
+    #[test]
+    fn roi_mask_weights_stats_and_freezes_masked_out_cells() {
+        let target = RgbImage::from_fn(2, 1, |x, _| Rgb([x as u8 * 200, 90, 30]));
+        let roi = GrayImage::from_fn(2, 1, |x, _| Luma([if x == 0 { 255 } else { 0 }]));
+        let config = GaConfig { width: 2, height: 1, iterations: 3, seed: Some(4), ..GaConfig::default() };
+
+        let mut reports = Vec::new();
+        let mut evolution = Evolution::start(&config, &Options::default(), vec![target.clone()], None, None, Some(roi), &target, None).unwrap();
+        while let Some(generation) = evolution.next() {
+            let frozen: Vec<bool> = evolution.grid[0].iter().map(|ga| ga.is_frozen()).collect();
+            reports.push((generation.cell_fitness[0], generation.stats, frozen));
+        }
+
+        for (fitness, stats, frozen) in reports {
+            assert_eq!(stats.avg_fitness, fitness as f64);
+            assert_eq!(stats.weighted_pixels, 1);
+            assert_eq!(frozen, [false, true]);
+        }
+    }
+
+    #[test]
+    fn pyramid_levels_seed_full_resolution_from_a_coarse_run() {
+        let target = RgbImage::from_fn(16, 12, |x, y| Rgb([x as u8 * 15, y as u8 * 20, 120]));
+        let flat = GaConfig { width: 16, height: 12, iterations: 12, seed: Some(3), ..GaConfig::default() };
+        let pyramid = GaConfig { pyramid_levels: 3, ..flat.clone() };
+        assert_eq!(pyramid.pyramid_start(), 8);
+
+        let first_report = |config: &GaConfig| {
+            let mut first = None;
+            run_ga(&target, config, |gen, frame, fitness| {
+                assert_eq!(frame.dimensions(), (16, 12));
+                first.get_or_insert((gen, fitness));
+            })
+            .unwrap();
+            first.unwrap()
+        };
+        let (flat_gen, flat_fitness) = first_report(&flat);
+        let (pyramid_gen, pyramid_fitness) = first_report(&pyramid);
+        assert_eq!((flat_gen, pyramid_gen), (0, 8));
+        assert!(pyramid_fitness > flat_fitness, "{} <= {}", pyramid_fitness, flat_fitness);
+
+        let sequence = [target.clone(), target.clone()];
+        assert!(Evolution::start(&pyramid, &Options::default(), sequence.to_vec(), None, None, None, &target, None).is_err());
+    }
+
+    #[test]
+    fn evolution_yields_the_same_generations_as_run_ga() {
+        let target = RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8 * 80, y as u8 * 120, 60]));
+        let config = GaConfig { width: 3, height: 2, iterations: 10, seed: Some(5), ..GaConfig::default() };
+        let mut frames = Vec::new();
+        run_ga(&target, &config, |_, frame, _| frames.push(frame.clone())).unwrap();
+
+        let sampled: Vec<Generation> = Evolution::new(&target, &config).unwrap().step_by(3).collect();
+        assert_eq!(sampled.iter().map(|generation| generation.generation).collect::<Vec<_>>(), [0, 3, 6, 9]);
+        for generation in sampled {
+            assert_eq!(generation.frame, frames[generation.generation]);
+        }
+
+        let exact = GaConfig { init: InitStrategy::FromTarget { noise: 0.0 }, ..config };
+        let mut evolution = Evolution::new(&target, &exact).unwrap();
+        assert!(evolution.next().is_some_and(|generation| generation.solved));
+        assert!(evolution.next().is_none());
+    }
+
+    #[test]
+    fn converged_cells_are_skipped_without_changing_the_result() {
+        let target = RgbImage::from_fn(6, 6, |x, y| Rgb([x as u8 * 40, y as u8 * 40, 90]));
+        let init = InitStrategy::FromTarget { noise: 0.02 };
+        let config = GaConfig { width: 6, height: 6, iterations: 30, mutation_rate: 0.01, seed: Some(4), init, ..GaConfig::default() };
+        let mut stepped = Evolution::new(&target, &config).unwrap();
+        stepped.skip_converged = false;
+        let stepped: Vec<Generation> = stepped.collect();
+        let skipped: Vec<Generation> = Evolution::new(&target, &config).unwrap().collect();
+
+        assert_eq!(stepped.len(), skipped.len());
+        for (a, b) in stepped.iter().zip(&skipped) {
+            assert_eq!(a.frame, b.frame);
+        }
+        assert_eq!(stepped.last().unwrap().stats.frozen_pixels, 0);
+        let frozen = skipped.last().unwrap().stats.frozen_pixels;
+        assert!(frozen > 0 && frozen < 36, "{} frozen", frozen);
+    }
+
+    #[test]
+    fn fitness_counts_cover_one_run_only() {
+        let config = GaConfig { iterations: 3, seed: Some(5), ..GaConfig::default() };
+        let target = RgbImage::from_fn(4, 3, |x, y| Rgb([x as u8 * 60, y as u8 * 80, 128]));
+        let run = || {
+            let mut evolution = Evolution::new(&target, &config).unwrap();
+            assert_eq!(evolution.fitness_counts(), FitnessCounts::default());
+            evolution.finish();
+            evolution.fitness_counts()
+        };
+
+        let counts = run();
+        assert!(counts.evaluations > 0 && counts.cache_hits <= counts.cache_lookups, "{:?}", counts);
+        assert_eq!(run(), counts);
+    }
+
+    #[test]
+    fn seeded_runs_match_across_thread_counts() {
+        let target = RgbImage::from_fn(6, 5, |x, y| Rgb([x as u8 * 40, y as u8 * 50, 90]));
+        let config = GaConfig { width: 6, height: 5, iterations: 8, seed: Some(9), ..GaConfig::default() };
+        let run = |threads| reconstruct_image(&target, &GaConfig { threads, ..config.clone() }).unwrap();
+        let sequential = run(Some(1));
+        assert_eq!(run(Some(3)), sequential);
+        assert_eq!(run(None), sequential);
+    }
+
+    #[test]
+    fn seeded_island_runs_match_across_thread_counts() {
+        let target = RgbImage::from_fn(6, 5, |x, y| Rgb([x as u8 * 40, y as u8 * 50, 90]));
+        let config = GaConfig { width: 6, height: 5, iterations: 8, islands: 4, migration_interval: 3, seed: Some(9), ..GaConfig::default() };
+        let run = |threads| reconstruct_image(&target, &GaConfig { threads, ..config.clone() }).unwrap();
+        assert_eq!(run(Some(4)), run(Some(1)));
+    }
+
+    #[test]
+    fn seeded_trials_pick_the_same_best_across_thread_counts() {
+        let target = RgbImage::from_fn(6, 5, |x, y| Rgb([x as u8 * 40, y as u8 * 50, 90]));
+        let config = GaConfig { width: 6, height: 5, iterations: 6, seed: Some(11), ..GaConfig::default() };
+        let run = |threads| run_trials(&target, &GaConfig { threads, ..config.clone() }, 4).unwrap();
+        let (sequential, parallel) = (run(Some(1)), run(Some(4)));
+        let best = best_trial(&sequential).unwrap();
+        assert_eq!(best_trial(&parallel), Some(best));
+        assert_eq!((parallel[best].seed, &parallel[best].image), (sequential[best].seed, &sequential[best].image));
+
+        let seeds: Vec<u64> = sequential.iter().map(|trial| trial.seed).collect();
+        assert_eq!(seeds, (0..4).map(|i| trial_seed(11, i)).collect::<Vec<_>>());
+        let alone = reconstruct_image(&target, &GaConfig { seed: Some(seeds[best]), ..config }).unwrap();
+        assert_eq!(alone, (sequential[best].image.clone(), sequential[best].avg_fitness));
+    }
+
+    #[test]
+    fn grid_fitness_stats_aggregate_every_cell() {
+        let target = RgbImage::from_fn(4, 3, |x, y| Rgb([x as u8 * 60, y as u8 * 80, 200]));
+        let config = GaConfig { width: 4, height: 3, seed: Some(3), ..GaConfig::default() };
+        let grid = build_grid(&config, &target, &target, None, None, None, ColorSpace::Rgb);
+        let (avg, max, min, std_dev) = grid_fitness_stats(&grid, &target);
+        assert!(min < avg && avg < max && std_dev > 0.0);
+
+        let exact = GaConfig { init: InitStrategy::FromTarget { noise: 0.0 }, ..config };
+        let grid = build_grid(&exact, &target, &target, None, None, None, ColorSpace::Rgb);
+        assert_eq!(grid_fitness_stats(&grid, &target), (2.0, 2.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn hsv_genes_decode_to_rgb_and_reject_palettes() {
+        let config = GaConfig { iterations: 30, encoding_space: EncodingSpace::Hsv, seed: Some(6), ..GaConfig::default() };
+        let target = RgbImage::from_pixel(3, 2, Rgb([220, 60, 30]));
+        let grid = build_grid(&config, &target, &target, None, None, None, config.color_space());
+        assert!(grid.iter().flatten().all(|ga| ga.population.pool[0].space == ColorSpace::Hsv));
+
+        let mut fitness = Vec::new();
+        run_ga(&target, &config, |_, _, avg_fitness| fitness.push(avg_fitness)).unwrap();
+        assert!(fitness[29] > fitness[0]);
+
+        let palette = GaConfig { palette: Some(vec![Rgb([0, 0, 0])]), ..config };
+        assert!(run_ga(&target, &palette, |_, _, _| {}).is_err());
+    }
+
+    #[test]
+    fn run_batch_skips_files_that_are_not_images() {
+        let dir = std::env::temp_dir().join(format!("ga-batch-{}", std::process::id()));
+        let (input, output) = (dir.join("in"), dir.join("out"));
+        std::fs::create_dir_all(&input).unwrap();
+        RgbImage::from_pixel(3, 2, Rgb([10, 200, 60])).save(input.join("sprite.png")).unwrap();
+        std::fs::write(input.join("notes.txt"), "not an image").unwrap();
+
+        let config = GaConfig { width: 3, height: 2, iterations: 3, seed: Some(1), ..GaConfig::default() };
+        let results = run_batch(input.to_str().unwrap(), output.to_str().unwrap(), &config, None, None).unwrap();
+        let written = (output.join("sprite_result.png").exists(), output.join("sprite_result.gif").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "sprite");
+        assert_eq!(written, (true, true));
+    }
+
+    #[test]
+    fn every_batch_image_gets_its_own_evaluation_budget() {
+        let dir = std::env::temp_dir().join(format!("ga-budget-{}", std::process::id()));
+        let (input, output) = (dir.join("in"), dir.join("out"));
+        std::fs::create_dir_all(&input).unwrap();
+        let target = RgbImage::from_fn(4, 4, |x, y| Rgb([x as u8 * 60, y as u8 * 60, 90]));
+        target.save(input.join("a.png")).unwrap();
+        target.save(input.join("b.png")).unwrap();
+
+        let config = GaConfig { width: 4, height: 4, iterations: 30, seed: Some(2), ..GaConfig::default() };
+        let (input, output) = (input.to_str().unwrap(), output.to_str().unwrap());
+        let uncapped = run_batch(input, output, &config, None, None).unwrap();
+        let capped = run_batch(input, output, &config, None, Some(500)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(capped[0].1, capped[1].1);
+        assert!(capped[0].1 < uncapped[0].1, "{:?} vs {:?}", capped, uncapped);
+    }
+
+    #[test]
+    fn native_size_batch_keeps_the_source_dimensions() {
+        let dir = std::env::temp_dir().join(format!("ga-native-{}", std::process::id()));
+        let (input, output) = (dir.join("in"), dir.join("out"));
+        std::fs::create_dir_all(&input).unwrap();
+        RgbImage::from_fn(5, 3, |x, _| Rgb([x as u8 * 50, 0, 0])).save(input.join("wide.png")).unwrap();
+
+        let config = GaConfig { keep_native_size: true, iterations: 2, seed: Some(1), ..GaConfig::default() };
+        run_batch(input.to_str().unwrap(), output.to_str().unwrap(), &config, None, None).unwrap();
+        let dimensions = image::image_dimensions(output.join("wide_result.png")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(dimensions, (5, 3));
+    }
+
+    #[test]
+    fn palette_mode_only_renders_palette_colors() {
+        let palette = vec![Rgb([0, 0, 0]), Rgb([255, 255, 255]), Rgb([200, 30, 30])];
+        let config = GaConfig { iterations: 5, seed: Some(3), palette: Some(palette.clone()), ..GaConfig::default() };
+        let target = RgbImage::from_fn(4, 3, |x, y| Rgb([x as u8 * 60, y as u8 * 80, 128]));
+
+        let result = run_ga(&target, &config, |_, _, _| {}).unwrap();
+        assert!(result.pixels().all(|pixel| palette.contains(pixel)));
+    }
+
+    #[test]
+    fn palette_runs_count_nearest_entries_as_matches_and_report_the_floor() {
+        let palette = vec![Rgb([0, 0, 0]), Rgb([250, 250, 250])];
+        let target = RgbImage::from_fn(4, 2, |x, _| if x < 2 { Rgb([10, 10, 10]) } else { Rgb([240, 240, 240]) });
+        let config = GaConfig { width: 4, height: 2, iterations: 30, seed: Some(2), palette: Some(palette), ..GaConfig::default() };
+
+        let last = Evolution::new(&target, &config).unwrap().last().unwrap();
+        assert!(last.solved);
+        assert_eq!(last.stats.perfect_matches, 8);
+        assert!(last.matched.iter().all(|&matched| matched));
+        assert!((last.stats.quantization_rmse - 10.0).abs() < 1e-6);
+        assert!((last.stats.avg_rmse - last.stats.quantization_rmse).abs() < 1e-6);
+    }
+
+    #[test]
+    fn snapshot_paths_sort_by_generation() {
+        assert_eq!(snapshot_path("result.png", 10, 50), "result_gen0010.png");
+        assert_eq!(snapshot_path("out/run.png", 7, 120000), "out/run_gen000007.png");
+        assert_eq!(snapshot_path("final", 3, 9), "final_gen0003.png");
+        let (a, b) = (snapshot_path("result.png", 90, 200), snapshot_path("result.png", 100, 200));
+        assert!(a < b);
+    }
+
+    #[test]
+    fn dry_run_checks_outputs_without_writing_them() {
+        let dir = std::env::temp_dir().join(format!("ga-dry-run-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("target.png");
+        RgbImage::from_pixel(3, 2, Rgb([10, 200, 60])).save(&input).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        let config = GaConfig { width: 3, height: 2, ..GaConfig::default() };
+        let options = Options {
+            input: Some(path("target.png")),
+            output: path("out.png"),
+            animation_output: Some(path("out.gif")),
+            dry_run: true,
+            ..Options::default()
+        };
+        let result = run_ga_with_output(&config, &options);
+        let written = (dir.join("out.png").exists(), dir.join("out.gif").exists());
+        let unwritable = Options { output: path("missing/out.png"), ..options };
+        let rejected = run_ga_with_output(&config, &unwritable).is_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(written, (false, false));
+        assert!(rejected);
+    }
+
+    #[test]
+    fn disabled_outputs_are_neither_written_nor_recorded() {
+        let dir = std::env::temp_dir().join(format!("ga-toggles-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        let config = GaConfig { width: 3, height: 2, iterations: 4, seed: Some(2), ..GaConfig::default() };
+        let options = Options {
+            output: path("out.png"),
+            animation_output: Some(path("out.gif")),
+            save_gif: false,
+            save_target: false,
+            comparison: Some(path("comparison.png")),
+            ..Options::default()
+        };
+        assert!(!options.records_frames());
+        run_ga_with_output(&config, &options).unwrap();
+        let written = (dir.join("out.png").exists(), dir.join("out.gif").exists(), dir.join("comparison.png").exists());
+
+        let no_png = Options { save_png: false, output: path("skipped.png"), comparison: None, ..options };
+        run_ga_with_output(&config, &no_png).unwrap();
+        let skipped = dir.join("skipped.png").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(written, (true, false, true));
+        assert!(!skipped);
+    }
+
+    #[test]
+    fn unchanged_frames_settle_at_a_zero_pixel_threshold() {
+        let dir = std::env::temp_dir().join(format!("ga-settle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        // Without mutation or crossover the best image never changes.
+        let config = GaConfig { width: 6, height: 4, iterations: 20, mutation_rate: 0.0, extra_flip_rate: 0.0, crossover_rate: 0.0, seed: Some(2), ..GaConfig::default() };
+        let options = Options {
+            output: path("out.png"),
+            stats_csv: Some(path("stats.csv")),
+            save_gif: false,
+            save_target: false,
+            settle: Some((0, 2)),
+            ..Options::default()
+        };
+        run_ga_with_output(&config, &options).unwrap();
+        let rows = std::fs::read_to_string(path("stats.csv")).unwrap().lines().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+        // The header, then the first frame and two unchanged ones.
+        assert_eq!(rows, 4);
+    }
+
+    #[test]
+    fn block_smoothing_changes_the_output_but_not_the_evolution() {
+        let dir = std::env::temp_dir().join(format!("ga-smoothing-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        let config = GaConfig { width: 8, height: 6, iterations: 4, block_size: 2, seed: Some(4), ..GaConfig::default() };
+        let run = |name: &str, block_smoothing| {
+            let options = Options {
+                output: path(&format!("{}.png", name)),
+                stats_csv: Some(path(&format!("{}.csv", name))),
+                save_gif: false,
+                save_target: false,
+                block_smoothing,
+                ..Options::default()
+            };
+            run_ga_with_output(&config, &options).unwrap();
+            (image::open(path(&format!("{}.png", name))).unwrap().to_rgb8(), std::fs::read_to_string(path(&format!("{}.csv", name))).unwrap())
+        };
+        let (hard, hard_stats) = run("hard", None);
+        let (smooth, smooth_stats) = run("smooth", Some(0.5));
+        let unblocked = run_ga_with_output(&GaConfig { block_size: 1, ..config.clone() }, &Options { block_smoothing: Some(0.5), ..Options::default() });
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(smooth_stats, hard_stats);
+        assert_eq!(smooth, smooth_blocks(&hard, 2, 0.5));
+        assert_ne!(smooth, hard);
+        assert!(unblocked.is_err());
+    }
+
+    #[test]
+    fn determinism_check_passes_on_a_shrunk_target() {
+        let target = RgbImage::from_fn(48, 40, |x, y| Rgb([x as u8 * 5, y as u8 * 6, 70]));
+        let config = GaConfig { width: 48, height: 40, iterations: 3, islands: 2, migration_interval: 2, ..GaConfig::default() };
+        assert!(determinism_check(&config, &Options::default(), &target).is_ok());
+
+        let palette = GaConfig { palette: Some(Vec::new()), ..config };
+        assert!(determinism_check(&palette, &Options::default(), &target).is_err());
+    }
+
+    #[test]
+    fn crossover_comparison_runs_every_operator_reproducibly() {
+        let target = RgbImage::from_fn(48, 40, |x, y| Rgb([x as u8 * 5, y as u8 * 6, 70]));
+        let config = GaConfig { width: 48, height: 40, iterations: 4, seed: Some(3), ..GaConfig::default() };
+        let results = compare_crossover(&config, &Options::default(), &target).unwrap();
+        let again = compare_crossover(&config, &Options::default(), &target).unwrap();
+        assert_eq!(results.iter().map(|result| result.crossover).collect::<Vec<_>>(), CROSSOVER_KINDS);
+        for (result, again) in results.iter().zip(&again) {
+            assert_eq!((result.psnr, result.generations_to_target, result.generations), (again.psnr, again.generations_to_target, again.generations));
+            assert!(result.psnr.is_finite() && result.generations <= 4);
+        }
+
+        let solid = RgbImage::from_pixel(8, 8, Rgb([0, 0, 0]));
+        let easy = GaConfig { width: 8, height: 8, iterations: 200, ..config.clone() };
+        let results = compare_crossover(&easy, &Options::default(), &solid).unwrap();
+        assert!(results.iter().all(|result| result.generations_to_target.is_some_and(|generations| generations == result.generations)));
+
+        let hill_climb = GaConfig { algorithm: Algorithm::HillClimb, ..config };
+        assert!(compare_crossover(&hill_climb, &Options::default(), &target).is_err());
+    }
+
+    #[test]
+    fn auto_mode_picks_grayscale_for_gray_sources_unless_color_is_asked_for() {
+        let config = GaConfig::default();
+        let auto = Options { auto_mode: true, ..Options::default() };
+        assert!(auto_mode(&config, &auto, ColorType::L8).is_some_and(|config| config.grayscale));
+        assert!(auto_mode(&config, &auto, ColorType::La16).is_some_and(|config| config.grayscale));
+        assert!(auto_mode(&config, &auto, ColorType::Rgb8).is_none());
+        assert!(auto_mode(&config, &Options::default(), ColorType::L8).is_none());
+
+        assert!(auto_mode(&GaConfig { alpha: true, ..config.clone() }, &auto, ColorType::L8).is_none());
+        assert!(auto_mode(&GaConfig { encoding_space: EncodingSpace::Hsv, ..config.clone() }, &auto, ColorType::L8).is_none());
+        assert!(auto_mode(&config, &Options { blend_hsv: Some(0.5), ..auto }, ColorType::L8).is_none());
+    }
+
+    #[test]
+    fn progress_bar_clamps_generations_past_the_total() {
+        let bar = ProgressBar { start: Instant::now(), first: 6, total: 3 };
+        assert_eq!(bar.line(6, None), format!("[{}] 3/3 avg fitness -- ETA --", "#".repeat(ProgressBar::WIDTH)));
+        let bar = ProgressBar { start: Instant::now(), first: 0, total: 10 };
+        assert_eq!(bar.line(5, Some(0.5)).split(']').next(), Some(format!("[{}{}", "#".repeat(15), "-".repeat(15)).as_str()));
+    }
+
+    #[test]
+    fn stdout_output_cannot_name_snapshots() {
+        let targets = [RgbImage::from_pixel(2, 2, Rgb([1, 2, 3]))];
+        let options = Options { output: STDIO_PATH.to_string(), snapshot_interval: Some(5), ..Options::default() };
+        assert!(check_modes(&GaConfig::default(), &options, &targets).is_err());
+        let options = Options { snapshot_interval: None, ..options };
+        assert!(check_modes(&GaConfig::default(), &options, &targets).is_ok());
+    }
+
+    #[test]
+    fn grids_over_the_pixel_budget_need_force() {
+        let budget = Options::default().pixel_budget();
+        let error = check_grid_size(3000, 2000, Some((3000, 2000)), budget).unwrap_err();
+        assert!(error.contains("6000000 pixels"), "{}", error);
+        assert!(check_grid_size(1000, 1000, None, budget).is_ok());
+        assert!(check_grid_size(100, 100, Some((10, 10)), budget).is_ok());
+        let forced = Options { force: true, ..Options::default() };
+        assert!(check_grid_size(3000, 2000, None, forced.pixel_budget()).is_ok());
+    }
+
+    #[test]
+    fn presets_trade_speed_for_quality() {
+        let target = RgbImage::from_fn(4, 4, |x, y| Rgb([x as u8 * 60, y as u8 * 60, 160]));
+        let run = |preset: GaConfig| {
+            let config = GaConfig { width: 4, height: 4, seed: Some(1), ..preset };
+            config.validate().unwrap();
+            let mut generations = 0;
+            let result = run_ga(&target, &config, |_, _, _| generations += 1).unwrap();
+            let work = generations * config.population.size(1.0) * config.islands;
+            (work, crate::ga::fitness::quality_report(&result, &target).rgb_rmse)
+        };
+        let [fast, balanced, high_quality] = [GaConfig::fast(), GaConfig::balanced(), GaConfig::high_quality()].map(run);
+        assert!(fast.0 < balanced.0 && balanced.0 < high_quality.0, "{:?} {:?} {:?}", fast, balanced, high_quality);
+        assert!(fast.1 > balanced.1 && balanced.1 > high_quality.1, "{:?} {:?} {:?}", fast, balanced, high_quality);
+    }
+
+    #[test]
+    fn frozen_cells_hand_their_generations_to_the_rest() {
+        let target = RgbImage::from_fn(4, 1, |x, _| if x < 2 { Rgb([0, 0, 0]) } else { Rgb([200, 100, 50]) });
+        let init = InitStrategy::Solid { color: [0, 0, 0] };
+        let config = GaConfig { width: 4, height: 1, iterations: 10, freeze_converged: true, init, seed: Some(3), ..GaConfig::default() };
+        assert_eq!(Evolution::new(&target, &config).unwrap().count(), 10);
+
+        // The black cells freeze after the first generation, so the other two
+        // get their remaining 2 x 9 steps on top of their own 9.
+        let budget = GaConfig { max_cell_generations: Some(100), ..config.clone() };
+        assert_eq!(Evolution::new(&target, &budget).unwrap().count(), 19);
+        let capped = GaConfig { max_cell_generations: Some(15), ..config };
+        assert_eq!(Evolution::new(&target, &capped).unwrap().count(), 15);
+    }
+
+    #[test]
+    fn hsv_twins_draw_on_the_same_cell_budget() {
+        // Seeded from the target without mutation, the RGB cells match and
+        // freeze after the first generation; their HSV twins cannot encode
+        // these colors exactly and keep stepping.
+        let target = RgbImage::from_fn(4, 1, |x, _| Rgb([201, 37 + x as u8, 90]));
+        let init = InitStrategy::FromTarget { noise: 0.0 };
+        let config = GaConfig {
+            width: 4,
+            height: 1,
+            iterations: 10,
+            mutation_rate: 0.0,
+            extra_flip_rate: 0.0,
+            freeze_converged: true,
+            max_cell_generations: Some(100),
+            init,
+            seed: Some(3),
+            ..GaConfig::default()
+        };
+        let hsv = Options { blend_hsv: Some(0.5), ..Options::default() };
+        // 8 cells x 10 generations: after the first, the 4 HSV cells share
+        // the remaining 72 steps.
+        let evolution = Evolution::start(&config, &hsv, vec![target.clone()], None, None, None, &target, None).unwrap();
+        assert_eq!(evolution.count(), 19);
+    }
+
+    #[test]
+    fn resuming_rejects_checkpoints_with_another_chromosome_layout() {
+        let target = RgbImage::from_fn(5, 3, |x, y| Rgb([x as u8 * 50, y as u8 * 80, 120]));
+        let rgb = GaConfig { width: 5, height: 3, block_size: 2, iterations: 4, seed: Some(8), ..GaConfig::default() };
+        let palette = |size: u8| GaConfig { palette: Some((0..size).map(|k| Rgb([k * 60; 3])).collect()), ..rgb.clone() };
+        let resume = |saved: &GaConfig, config: &GaConfig| {
+            let grid = build_grid(saved, &target, &target, None, None, None, saved.color_space());
+            let checkpoint = Checkpoint { generation: 2, grid, hsv_grid: None, fitness_evaluations: 0 };
+            Evolution::start(config, &Options::default(), vec![target.clone()], None, None, None, &target, Some(checkpoint))
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        };
+
+        assert_eq!(resume(&rgb, &rgb), Ok(()));
+        assert_eq!(resume(&palette(3), &palette(3)), Ok(()));
+        let rgba = GaConfig { alpha: true, block_size: 1, ..rgb.clone() };
+        assert_eq!(resume(&rgba, &rgba), Ok(()));
+        let mismatches = [
+            (rgb.clone(), palette(4)),
+            (palette(4), rgb.clone()),
+            // Both palettes need 2-bit indices.
+            (palette(3), palette(4)),
+            (rgb.clone(), GaConfig { encoding: GeneEncoding::GrayCode, ..rgb.clone() }),
+            (rgb.clone(), GaConfig { encoding_space: EncodingSpace::Hsv, ..rgb.clone() }),
+            (GaConfig { block_size: 1, ..rgb.clone() }, GaConfig { deep_color: true, block_size: 1, ..rgb.clone() }),
+            (rgb.clone(), GaConfig { grayscale: true, ..rgb.clone() }),
+            (GaConfig { grayscale: true, ..rgb.clone() }, rgb.clone()),
+            (GaConfig { alpha: false, ..rgba.clone() }, rgba.clone()),
+            (rgba.clone(), GaConfig { alpha: false, ..rgba.clone() }),
+        ];
+        for (saved, config) in &mismatches {
+            assert_eq!(resume(saved, config), Err("Checkpoint does not match the current grid settings".to_string()));
+        }
+    }
+
+    #[test]
+    fn reconstruct_pixel_finds_a_single_color() {
+        let target = Rgb([200, 40, 90]);
+        let config = GaConfig { population: PopulationStrategy::Uniform(30), iterations: 300, seed: Some(5), ..GaConfig::default() };
+        let (best, fitness) = reconstruct_pixel(target, &config);
+        assert!(best.get_val().iter().zip(target.0).all(|(&a, b)| a.abs_diff(b) <= 2), "{:?}", best.get_val());
+        assert_eq!(reconstruct_pixel(target, &config).1, fitness);
+
+        let climb = GaConfig { algorithm: Algorithm::HillClimb, iterations: 0, ..config };
+        let (start, start_fitness) = reconstruct_pixel(target, &climb);
+        assert!(start_fitness < fitness && start.get_val() != target.0);
+    }
+}
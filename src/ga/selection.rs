@@ -0,0 +1,190 @@
+use rand::prelude::*;
+
+use super::fitness::Fitness;
+
+/// Picks one parent from `pool`; `fitnesses[i]` is the fitness of `pool[i]`.
+pub trait Selection {
+    fn select<'a, G>(&self, pool: &'a [G], fitnesses: &[Fitness], rng: &mut impl Rng) -> &'a G;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionMethod {
+    Tournament,
+    RouletteWheel,
+    RankBased,
+}
+
+/// Draws `size` candidates uniformly with replacement; the i-th ranked one wins with probability
+/// `pressure * (1 - pressure)^i`, the last takes whatever is left.
+pub struct Tournament {
+    pub size: usize,
+    pub pressure: f64,
+}
+
+impl Selection for Tournament {
+    fn select<'a, G>(&self, pool: &'a [G], fitnesses: &[Fitness], rng: &mut impl Rng) -> &'a G {
+        let mut candidates: Vec<(&G, Fitness)> = (0..self.size.max(1))
+            .map(|_| {
+                let idx = rng.gen_range(0..pool.len());
+                (&pool[idx], fitnesses[idx])
+            })
+            .collect();
+
+        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+        let last = candidates.len() - 1;
+        for &(candidate, _) in &candidates[..last] {
+            if self.pressure >= 1.0 || rng.gen::<f64>() < self.pressure {
+                return candidate;
+            }
+        }
+
+        candidates[last].0
+    }
+}
+
+/// Fitness-proportionate selection. Falls back to a uniform pick when the
+/// total fitness is zero.
+pub struct RouletteWheel;
+
+impl Selection for RouletteWheel {
+    fn select<'a, G>(&self, pool: &'a [G], fitnesses: &[Fitness], rng: &mut impl Rng) -> &'a G {
+        let total: f64 = fitnesses.iter().map(|&f| f as f64).sum();
+        if total <= 0.0 || !total.is_finite() {
+            return &pool[rng.gen_range(0..pool.len())];
+        }
+
+        let mut spin = rng.gen::<f64>() * total;
+        for (chr, &fitness) in pool.iter().zip(fitnesses) {
+            if spin < fitness as f64 {
+                return chr;
+            }
+            spin -= fitness as f64;
+        }
+
+        &pool[pool.len() - 1]
+    }
+}
+
+/// Linear ranking: the worst chromosome has weight 1, the best weight `n`.
+pub struct RankBased;
+
+impl Selection for RankBased {
+    fn select<'a, G>(&self, pool: &'a [G], fitnesses: &[Fitness], rng: &mut impl Rng) -> &'a G {
+        let mut order: Vec<usize> = (0..pool.len()).collect();
+        order.sort_by(|&a, &b| fitnesses[a].partial_cmp(&fitnesses[b]).unwrap());
+
+        let n = order.len();
+        let mut spin = rng.gen_range(0..n * (n + 1) / 2);
+        for (rank, &idx) in order.iter().enumerate() {
+            if spin <= rank {
+                return &pool[idx];
+            }
+            spin -= rank + 1;
+        }
+
+        &pool[order[n - 1]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ga::chromosome::Chromosome;
+    use crate::ga::color::ColorSpace;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn tournament_does_not_favor_the_first_index() {
+        let pool: Vec<Chromosome> = (0..6).map(|i| Chromosome::from_color((0, 0), [i * 40, 0, 0], ColorSpace::Rgb)).collect();
+        let fitnesses = vec![0.5; pool.len()];
+        let tournament = Tournament { size: 3, pressure: 1.0 };
+        let mut rng = StdRng::seed_from_u64(6);
+
+        let trials = 60_000;
+        let mut counts = vec![0usize; pool.len()];
+        for _ in 0..trials {
+            let winner = tournament.select(&pool, &fitnesses, &mut rng);
+            counts[pool.iter().position(|chr| std::ptr::eq(chr, winner)).unwrap()] += 1;
+        }
+
+        let expected = trials / pool.len();
+        for count in counts {
+            assert!(count.abs_diff(expected) < expected / 10, "{} vs {}", count, expected);
+        }
+    }
+
+    #[test]
+    fn pressure_below_one_sometimes_lets_a_weaker_candidate_win() {
+        let pool: Vec<Chromosome> = (0..6).map(|i| Chromosome::from_color((0, 0), [i * 40, 0, 0], ColorSpace::Rgb)).collect();
+        let fitnesses: Vec<Fitness> = (0..6).map(|i| i as Fitness).collect();
+        let mut rng = StdRng::seed_from_u64(3);
+        // The index of the fittest of the next three candidates `rng` draws.
+        let fittest = |rng: &StdRng| {
+            let mut probe = rng.clone();
+            (0..3).map(|_| probe.gen_range(0..pool.len())).max().unwrap()
+        };
+
+        let plain = Tournament { size: 3, pressure: 1.0 };
+        for _ in 0..1000 {
+            let expected = fittest(&rng);
+            assert!(std::ptr::eq(plain.select(&pool, &fitnesses, &mut rng), &pool[expected]));
+        }
+
+        let soft = Tournament { size: 3, pressure: 0.6 };
+        let upsets = (0..1000)
+            .filter(|_| {
+                let expected = fittest(&rng);
+                !std::ptr::eq(soft.select(&pool, &fitnesses, &mut rng), &pool[expected])
+            })
+            .count();
+        assert!((200..500).contains(&upsets), "{}", upsets);
+    }
+
+    // How often `selection` picks each index of a pool of `fitnesses.len()`
+    // in `picks` seeded draws.
+    fn pick_counts(selection: &impl Selection, fitnesses: &[Fitness], picks: usize, seed: u64) -> Vec<usize> {
+        let pool: Vec<usize> = (0..fitnesses.len()).collect();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut counts = vec![0; pool.len()];
+        for _ in 0..picks {
+            counts[*selection.select(&pool, fitnesses, &mut rng)] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn roulette_picks_uniformly_when_every_fitness_is_zero() {
+        let counts = pick_counts(&RouletteWheel, &[0.0; 6], 60_000, 4);
+        for count in counts {
+            assert!(count.abs_diff(10_000) < 1_000, "{}", count);
+        }
+    }
+
+    #[test]
+    fn roulette_follows_fitness_proportions() {
+        let fitnesses = [1.0, 2.0, 0.0, 3.0, 4.0];
+        let counts = pick_counts(&RouletteWheel, &fitnesses, 100_000, 5);
+        assert_eq!(counts[2], 0);
+        for (count, fitness) in counts.iter().zip(fitnesses) {
+            let share = *count as f64 / 100_000.0;
+            assert!((share - fitness as f64 / 10.0).abs() < 0.01, "{} for fitness {}", share, fitness);
+        }
+    }
+
+    #[test]
+    fn rank_based_weights_by_rank_and_favors_the_best() {
+        // Ranks from worst to best: index 2, 0, 3, 1, so weights 2, 4, 1, 3 of 10.
+        let fitnesses = [0.3, 0.9, 0.1, 0.5];
+        let counts = pick_counts(&RankBased, &fitnesses, 100_000, 6);
+        assert_eq!(counts.iter().sum::<usize>(), 100_000);
+        for (count, weight) in counts.iter().zip([2.0, 4.0, 1.0, 3.0]) {
+            let share = *count as f64 / 100_000.0;
+            assert!((share - weight / 10.0).abs() < 0.01, "{} for weight {}", share, weight);
+        }
+        assert_eq!(counts.iter().enumerate().max_by_key(|&(_, count)| count).map(|(i, _)| i), Some(1));
+
+        assert_eq!(pick_counts(&RankBased, &[0.7], 10, 7), [10]);
+        assert_eq!(pick_counts(&RankBased, &[0.0; 3], 3_000, 8).iter().sum::<usize>(), 3_000);
+    }
+}
@@ -1,375 +1,689 @@
-use image::{ImageBuffer, Rgb, RgbImage};
-use rand::prelude::*;
-use std::f64;
-use std::fs::File;
-use std::io::BufWriter;
-
-const IMG_SIZE: usize = 100;
-const POPULATION_SIZE: usize = 6;
-const ITERATION: usize = 50;
-const MUTATION_RATE: f64 = 0.05;
-const CROSSOVER_RATE: f64 = 0.8;
-const GENE_LENGTH: usize = 8;
-const RGB_CHANNELS: usize = 3;
-const TOURNAMENT_SIZE: usize = 3;
-const ELITE_SIZE: usize = 2;
-
-#[derive(Clone, Debug)]
-struct Chromosome {
-    pos: (usize, usize),
-    gene: Vec<Vec<bool>>,
-}
-
-impl Chromosome {
-    fn new(pos: (usize, usize)) -> Self {
-        let mut rng = thread_rng();
-        let gene = (0..RGB_CHANNELS)
-            .map(|_| {
-                (0..GENE_LENGTH)
-                    .map(|_| rng.gen_bool(0.5))
-                    .collect()
-            })
-            .collect();
-
-        Chromosome { pos, gene }
-    }
-
-    fn mutate(&mut self) {
-        let mut rng = thread_rng();
-        
-        for channel in &mut self.gene {
-            for bit in channel {
-                if rng.gen::<f64>() < MUTATION_RATE {
-                    *bit = !*bit;
-                }
-            }
-        }
-        
-        if rng.gen::<f64>() < 0.1 {
-            let channel_idx = rng.gen_range(0..RGB_CHANNELS);
-            let bit_idx = rng.gen_range(0..GENE_LENGTH);
-            self.gene[channel_idx][bit_idx] = !self.gene[channel_idx][bit_idx];
-        }
+use ga_image::ga::chromosome::{CrossoverKind, GeneEncoding, MutationOperator};
+use ga_image::ga::color::{parse_hex_color, EncodingSpace, PICO8_PALETTE};
+use ga_image::ga::config::{Algorithm, CrossoverSchedule, GaConfig, MutationSchedule, PopulationStrategy};
+use ga_image::ga::fitness::FitnessMetric;
+use ga_image::ga::image::{AspectMode, LabelColor, SamplePattern};
+use ga_image::ga::logging::{level_from_env, set_max_level, Level};
+use ga_image::ga::population::{InitStrategy, DEFAULT_INIT_BLUR, DEFAULT_INIT_NOISE};
+use ga_image::ga::run::{run_batch, run_ga_with_output, Options, VideoFormat, INTERRUPTED};
+use ga_image::ga::selection::SelectionMethod;
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use image::imageops::FilterType;
+use image::Rgb;
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+
+fn parse_noise(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(n) if (0.0..=1.0).contains(&n) => Ok(n),
+        _ => Err(format!("init noise must be between 0 and 1, got '{}'", value)),
     }
+}
 
-    #[allow(dead_code)]
-    fn crossover(&self, other: &Chromosome) -> (Chromosome, Chromosome) {
-        let mut rng = thread_rng();
-
-        if rng.gen::<f64>() > CROSSOVER_RATE {
-            return (self.clone(), other.clone());
-        }
-
-        let mut child1 = self.clone();
-        let mut child2 = other.clone();
+fn parse_init_strategy(value: &str) -> Result<InitStrategy, String> {
+    let (kind, rest) = match value.split_once(':') {
+        Some((kind, rest)) => (kind, Some(rest)),
+        None => (value, None),
+    };
 
-        for i in 0..RGB_CHANNELS {
-            let crossover_point = rng.gen_range(1..GENE_LENGTH);
-            for j in crossover_point..GENE_LENGTH {
-                child1.gene[i][j] = other.gene[i][j];
-                child2.gene[i][j] = self.gene[i][j];
+    match (kind, rest) {
+        ("random", None) => Ok(InitStrategy::Random),
+        ("target", None) => Ok(InitStrategy::FromTarget { noise: DEFAULT_INIT_NOISE }),
+        ("target", Some(noise)) => Ok(InitStrategy::FromTarget { noise: parse_noise(noise)? }),
+        ("blur", None) => Ok(InitStrategy::TargetBlur { sigma: DEFAULT_INIT_BLUR, noise: DEFAULT_INIT_NOISE }),
+        ("blur", Some(rest)) => {
+            let (sigma, noise) = match rest.split_once(':') {
+                Some((sigma, noise)) => (sigma, parse_noise(noise)?),
+                None => (rest, DEFAULT_INIT_NOISE),
+            };
+            match sigma.parse::<f32>() {
+                Ok(sigma) if sigma > 0.0 && sigma.is_finite() => Ok(InitStrategy::TargetBlur { sigma, noise }),
+                _ => Err(format!("blur sigma must be a positive number, got '{}'", sigma)),
             }
         }
-
-        (child1, child2)
-    }
-
-    fn uniform_crossover(&self, other: &Chromosome) -> (Chromosome, Chromosome) {
-        let mut rng = thread_rng();
-
-        if rng.gen::<f64>() > CROSSOVER_RATE {
-            return (self.clone(), other.clone());
-        }
-
-        let mut child1 = self.clone();
-        let mut child2 = other.clone();
-
-        for i in 0..RGB_CHANNELS {
-            for j in 0..GENE_LENGTH {
-                if rng.gen_bool(0.5) {
-                    child1.gene[i][j] = other.gene[i][j];
-                    child2.gene[i][j] = self.gene[i][j];
-                }
+        ("image", Some(rest)) => {
+            let (path, noise) = match rest.rsplit_once(':') {
+                Some((path, noise)) if noise.parse::<f64>().is_ok() => (path, parse_noise(noise)?),
+                _ => (rest, DEFAULT_INIT_NOISE),
+            };
+            if path.is_empty() {
+                return Err("--init image requires a path".to_string());
             }
+            Ok(InitStrategy::FromImage { path: path.to_string(), noise })
         }
-
-        (child1, child2)
+        ("solid", Some(color)) => parse_hex_color(color)
+            .map(|color| InitStrategy::Solid { color })
+            .ok_or_else(|| format!("invalid solid color '{}', expected RRGGBB", color)),
+        _ => Err(format!(
+            "unknown init strategy '{}', expected random, target[:noise], blur[:sigma[:noise]], image:<path>[:noise] or solid:<RRGGBB>",
+            value
+        )),
     }
+}
 
-    fn get_val(&self) -> [u8; 3] {
-        let mut vals = [0u8; 3];
-
-        for (i, channel) in self.gene.iter().enumerate() {
-            let mut val = 0u8;
-            for &bit in channel {
-                val = (val << 1) | if bit { 1 } else { 0 };
-            }
-            vals[i] = val;
-        }
+fn parse_sample_pattern(value: &str) -> Result<SamplePattern, String> {
+    let (kind, rest) = match value.split_once(':') {
+        Some((kind, rest)) => (kind, Some(rest)),
+        None => (value, None),
+    };
+    let size = |rest: Option<&str>| match rest {
+        None => Ok(10),
+        Some(size) => positive::<u32>(size).map_err(|_| format!("invalid size '{}', expected a positive integer", size)),
+    };
 
-        vals
+    match (kind, rest) {
+        ("gradient", None) => Ok(SamplePattern::Gradient),
+        ("solid", Some(color)) => parse_hex_color(color)
+            .map(SamplePattern::Solid)
+            .ok_or_else(|| format!("invalid solid color '{}', expected RRGGBB", color)),
+        ("noise", None) => Ok(SamplePattern::Noise(0)),
+        ("noise", Some(seed)) => seed.parse().map(SamplePattern::Noise).map_err(|_| format!("invalid noise seed '{}'", seed)),
+        ("checkerboard", rest) => size(rest).map(SamplePattern::Checkerboard),
+        ("circles", rest) => size(rest).map(SamplePattern::Circles),
+        _ => Err(format!(
+            "unknown sample pattern '{}', expected gradient, solid:<RRGGBB>, noise[:seed], checkerboard[:size] or circles[:width]",
+            value
+        )),
     }
+}
 
-    fn get_fitness(&self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> f64 {
-        let target_pixel = target_image.get_pixel(self.pos.1 as u32, self.pos.0 as u32);
-        let val = self.get_val();
+fn positive<T: FromStr + PartialOrd + Default>(value: &str) -> Result<T, String> {
+    match value.parse::<T>() {
+        Ok(n) if n > T::default() => Ok(n),
+        _ => Err("expected a positive integer".to_string()),
+    }
+}
 
-        let mut diff_sum = 0.0;
-        for i in 0..3 {
-            let diff = val[i] as f64 - target_pixel[i] as f64;
-            diff_sum += diff * diff;
-        }
-        
-        let rmse = (diff_sum / 3.0).sqrt();
-        let fitness = (-rmse / 50.0).exp();
-        
-        if rmse < 1.0 {
-            fitness * 2.0
-        } else {
-            fitness
-        }
+fn count_in<T>(range: RangeInclusive<T>) -> impl Fn(&str) -> Result<T, String> + Clone
+where
+    T: FromStr + PartialOrd + Display + Clone,
+{
+    move |value| match value.parse::<T>() {
+        Ok(n) if range.contains(&n) => Ok(n),
+        _ => Err(format!("expected a value between {} and {}", range.start(), range.end())),
     }
 }
 
-struct SimpleGA {
-    #[allow(dead_code)]
-    pos: (usize, usize),
-    pool: Vec<Chromosome>,
+fn fraction(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+        _ => Err("expected a number between 0 and 1".to_string()),
+    }
 }
 
-impl SimpleGA {
-    fn new(pos: (usize, usize)) -> Self {
-        let pool = (0..POPULATION_SIZE)
-            .map(|_| Chromosome::new(pos))
-            .collect();
+fn parse_channel_rates(value: &str) -> Result<[f64; 3], String> {
+    let rates: Vec<f64> = value.split(',').map(fraction).collect::<Result<_, _>>()?;
+    rates.try_into().map_err(|_| "expected three rates, e.g. 0.02,0.02,0.1".to_string())
+}
 
-        SimpleGA { pos, pool }
+fn non_negative(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(w) if w >= 0.0 && w.is_finite() => Ok(w),
+        _ => Err("expected a non-negative number".to_string()),
     }
+}
 
-    fn tournament_selection(&self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> &Chromosome {
-        let mut rng = thread_rng();
-
-        let mut best = &self.pool[0];
-        let mut best_fitness = best.get_fitness(target_image);
+fn parse_colors(value: &str) -> Result<Vec<[u8; 3]>, String> {
+    value
+        .split(',')
+        .map(|c| parse_hex_color(c.trim()).ok_or_else(|| format!("invalid color '{}', expected RRGGBB", c)))
+        .collect()
+}
 
-        for _ in 1..TOURNAMENT_SIZE {
-            let candidate = &self.pool[rng.gen_range(0..self.pool.len())];
-            let fitness = candidate.get_fitness(target_image);
-            if fitness > best_fitness {
-                best = candidate;
-                best_fitness = fitness;
-            }
-        }
+fn parse_palette(value: &str) -> Result<Vec<[u8; 3]>, String> {
+    let palette = match value {
+        "pico8" => PICO8_PALETTE.to_vec(),
+        _ => parse_colors(value)?,
+    };
+    if palette.len() > 256 {
+        return Err(format!("at most 256 colors are supported, got {}", palette.len()));
+    }
+    Ok(palette)
+}
 
-        best
+fn parse_counter_color(value: &str) -> Result<LabelColor, String> {
+    match value {
+        "auto" => Ok(LabelColor::Auto),
+        _ => parse_hex_color(value).map(LabelColor::Fixed).ok_or_else(|| "expected RRGGBB or auto".to_string()),
     }
+}
 
-    fn get_fitness_stats(&self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> (f64, f64, f64) {
-        let fitnesses: Vec<f64> = self.pool.iter()
-            .map(|chr| chr.get_fitness(target_image))
-            .collect();
-        
-        let avg = fitnesses.iter().sum::<f64>() / fitnesses.len() as f64;
-        let max = fitnesses.iter().fold(0.0f64, |a, &b| a.max(b));
-        let min = fitnesses.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-        
-        (avg, max, min)
+fn parse_loop_count(value: &str) -> Result<Option<u16>, String> {
+    match value {
+        "infinite" => Ok(None),
+        _ => value.parse::<u16>().map(Some).map_err(|_| "expected 'infinite' or 0-65535".to_string()),
     }
+}
 
-    fn step(&mut self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) {
-        let mut new_pool = Vec::new();
+fn parse_mutation(value: &str) -> Result<MutationOperator, String> {
+    match value.split_once(':') {
+        None if value == "bitflip" => Ok(MutationOperator::BitFlip),
+        Some(("fixed", n)) => match n.parse::<usize>() {
+            Ok(n) => Ok(MutationOperator::FixedFlips { n }),
+            _ => Err("fixed flip count must be a non-negative whole number".to_string()),
+        },
+        Some(("creep", sigma)) => match sigma.parse::<f64>() {
+            Ok(sigma) if sigma > 0.0 && sigma.is_finite() => Ok(MutationOperator::Creep { sigma }),
+            _ => Err("creep sigma must be a positive number".to_string()),
+        },
+        _ => Err("expected bitflip, fixed:<n> or creep:<sigma>".to_string()),
+    }
+}
 
-        self.pool.sort_by(|a, b| {
-            b.get_fitness(target_image)
-                .partial_cmp(&a.get_fitness(target_image))
-                .unwrap()
-        });
+fn parse_population(value: &str) -> Result<PopulationStrategy, String> {
+    let sizes: Option<Vec<usize>> = value.split(':').skip(1).map(|n| n.parse().ok().filter(|&n| n > 0)).collect();
+    match (value.parse::<usize>(), sizes.as_deref()) {
+        (Ok(n), _) if n > 0 => Ok(PopulationStrategy::Uniform(n)),
+        (_, Some(&[min, max])) if value.starts_with("adaptive:") && min <= max => Ok(PopulationStrategy::Adaptive { min, max }),
+        _ => Err("expected a positive size or adaptive:<min>:<max> with 0 < min <= max".to_string()),
+    }
+}
 
-        for i in 0..ELITE_SIZE.min(self.pool.len()) {
-            new_pool.push(self.pool[i].clone());
-        }
+fn parse_fitness(value: &str) -> Result<FitnessMetric, String> {
+    let weights: Option<Vec<f64>> = value.split(':').skip(1).map(|w| w.parse().ok().filter(|w: &f64| *w >= 0.0 && w.is_finite())).collect();
+    match (value.split(':').next(), weights.as_deref()) {
+        (Some("rgb"), Some([])) => Ok(FitnessMetric::RgbRmse),
+        (Some("ciede2000"), Some([])) => Ok(FitnessMetric::CieDe2000),
+        (Some("weighted"), Some(&[r, g, b])) if r + g + b > 0.0 => Ok(FitnessMetric::Weighted { r, g, b }),
+        _ => Err("expected rgb, ciede2000 or weighted:<r>:<g>:<b> with non-negative weights, not all zero".to_string()),
+    }
+}
 
-        while new_pool.len() < POPULATION_SIZE {
-            let parent1 = self.tournament_selection(target_image);
-            let parent2 = self.tournament_selection(target_image);
+fn parse_mutation_schedule(value: &str) -> Result<MutationSchedule, String> {
+    let parts: Vec<&str> = value.split(':').collect();
+    let rates: Option<Vec<f64>> = parts[1..].iter().map(|r| r.parse::<f64>().ok().filter(|r| (0.0..=1.0).contains(r))).collect();
+    match (parts[0], rates.as_deref()) {
+        ("constant", Some([])) => Ok(MutationSchedule::Constant),
+        ("linear", Some(&[start, end])) => Ok(MutationSchedule::Linear { start, end }),
+        ("exp", Some(&[start, end])) => Ok(MutationSchedule::Exponential { start, end }),
+        _ => Err("expected constant, linear:<start>:<end> or exp:<start>:<end> with rates in [0, 1]".to_string()),
+    }
+}
 
-            let (mut child1, mut child2) = parent1.uniform_crossover(parent2);
+fn parse_crossover_schedule(value: &str) -> Result<CrossoverSchedule, String> {
+    parse_mutation_schedule(value).map(|schedule| match schedule {
+        MutationSchedule::Constant => CrossoverSchedule::Constant,
+        MutationSchedule::Linear { start, end } => CrossoverSchedule::Linear { start, end },
+        MutationSchedule::Exponential { start, end } => CrossoverSchedule::Exponential { start, end },
+    })
+}
 
-            child1.mutate();
-            child2.mutate();
+fn parse_sprite_sheet(value: &str) -> Result<(String, u32), String> {
+    match value.rsplit_once(':').map(|(path, cols)| (path, cols.parse::<u32>())) {
+        Some((path, Ok(cols))) if cols > 0 && !path.is_empty() => Ok((path.to_string(), cols)),
+        _ => Err("expected <path>:<cols> with cols > 0".to_string()),
+    }
+}
 
-            new_pool.push(child1);
-            if new_pool.len() < POPULATION_SIZE {
-                new_pool.push(child2);
-            }
-        }
+fn parse_settle(value: &str) -> Result<(usize, usize), String> {
+    let parsed = value
+        .split_once(':')
+        .and_then(|(k, n)| Some((k.parse::<usize>().ok()?, n.parse::<usize>().ok()?)));
+    match parsed {
+        Some((k, n)) if n > 0 => Ok((k, n)),
+        _ => Err("expected <pixels>:<generations>".to_string()),
+    }
+}
 
-        new_pool.truncate(POPULATION_SIZE);
-        self.pool = new_pool;
+fn parse_checkpoint(value: &str) -> Result<(String, usize), String> {
+    match value.rsplit_once(':').map(|(path, n)| (path, n.parse::<usize>())) {
+        Some((path, Ok(n))) if !path.is_empty() && n > 0 => Ok((path.to_string(), n)),
+        _ => Err("expected <path>:<generations>".to_string()),
     }
+}
 
-    fn get_best(&self, target_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> &Chromosome {
-        self.pool
-            .iter()
-            .max_by(|a, b| {
-                a.get_fitness(target_image)
-                    .partial_cmp(&b.get_fitness(target_image))
-                    .unwrap()
-            })
-            .unwrap()
+fn parse_size(value: &str) -> Result<(usize, usize), String> {
+    let (width, height) = value.split_once('x').unwrap_or((value, value));
+    match (width.parse::<usize>(), height.parse::<usize>()) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err("expected <width>x<height> or <size> with positive values".to_string()),
     }
 }
 
-fn load_target_image(path: &str) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn std::error::Error>> {
-    let img = image::open(path)?;
-    let img_rgb = img.to_rgb8();
-    Ok(image::imageops::resize(&img_rgb, IMG_SIZE as u32, IMG_SIZE as u32, image::imageops::FilterType::CatmullRom))
+fn value(name: &'static str, value_name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name).long(name).value_name(value_name).help(help)
 }
 
-fn create_sample_image() -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-    let mut img = RgbImage::new(IMG_SIZE as u32, IMG_SIZE as u32);
+fn flag(name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name).long(name).action(ArgAction::SetTrue).help(help)
+}
 
-    for (x, y, pixel) in img.enumerate_pixels_mut() {
-        let r = (x * 255 / IMG_SIZE as u32) as u8;
-        let g = (y * 255 / IMG_SIZE as u32) as u8;
-        let b = ((x + y) * 255 / (IMG_SIZE as u32 * 2)) as u8;
-        *pixel = Rgb([r, g, b]);
-    }
+fn choice<T: Clone + Send + Sync + 'static>(
+    name: &'static str,
+    help: &'static str,
+    choices: &'static [(&'static str, T)],
+) -> Arg {
+    let parser = PossibleValuesParser::new(choices.iter().map(|(name, _)| *name))
+        .map(move |picked| choices.iter().find(|(name, _)| *name == picked).unwrap().1.clone());
+    Arg::new(name).long(name).value_name("KIND").help(help).value_parser(parser)
+}
 
-    img
+fn cli() -> Command {
+    Command::new("ga-image")
+        .about("Reconstructs an image pixel by pixel with a genetic algorithm")
+        .version(env!("CARGO_PKG_VERSION"))
+        .next_help_heading("Input and output")
+        .args([
+            value("input", "PATH", "Target image, or - for stdin [default: target.png, or a generated sample if missing]"),
+            value("sample", "PATTERN", "Generated target without an input image: gradient, solid:<RRGGBB>, noise[:seed], checkerboard[:size] or circles[:width]")
+                .value_parser(parse_sample_pattern),
+            value("output", "PATH", "Where to save the final image, or - for stdout [default: result.png]"),
+            value("gif", "PATH", "Where to save the evolution animation [default: result.<format>]"),
+            flag("no-png", "Do not write the result image"),
+            flag("no-gif", "Do not write the evolution animation, and only keep the final frame in memory"),
+            flag("no-target", "Do not write target_sample.png"),
+            value("target-sequence", "PATH", "Folder or animated GIF of targets to evolve through in turn"),
+            flag("target-loop", "Keep cycling the target sequence until the generation budget runs out"),
+            value("roi-mask", "PATH", "Grayscale image weighting each pixel's share of the fitness; black pixels are frozen after one generation"),
+            flag("dry-run", "Load the target, print the settings and check the outputs are writable, then exit"),
+            flag("determinism-check", "Evolve a small copy of the target twice with one seed and fail if the results differ")
+                .conflicts_with_all(["dry-run", "batch"]),
+            flag("compare-crossover", "Evolve a small copy of the target once per crossover operator with one seed and print a comparison table")
+                .conflicts_with_all(["dry-run", "determinism-check", "batch", "crossover"]),
+            value("batch", "DIR", "Reconstruct every image in <INPUT_DIR> into <OUTPUT_DIR>")
+                .num_args(2)
+                .value_names(["INPUT_DIR", "OUTPUT_DIR"]),
+            value("checkpoint", "PATH:N", "Save a resumable checkpoint every N generations").value_parser(parse_checkpoint),
+            value("snapshot-interval", "N", "Also save the best image every N generations as <output>_gen<N>.png")
+                .value_parser(positive::<usize>),
+            flag("sidecar", "Write the run configuration next to the result image"),
+            value("manifest", "PATH", "Write a manifest of the produced files")
+                .num_args(0..=1)
+                .default_missing_value("manifest.json"),
+            value("stats-csv", "PATH", "Write per-generation statistics")
+                .num_args(0..=1)
+                .default_missing_value("stats.csv"),
+            value("output-html", "PATH", "Write an HTML viewer of the run"),
+            value("sprite-sheet", "PATH:COLS", "Write sampled frames as a sprite sheet").value_parser(parse_sprite_sheet),
+            flag("sprite-sheet-labels", "Label each sprite sheet tile with its generation"),
+            value("diff-gif", "PATH", "Write an animation of the per-pixel error"),
+            value("fitness-map", "PATH", "Write a grayscale map of each pixel's final match to the target")
+                .num_args(0..=1)
+                .default_missing_value("fitness_map.png"),
+            value("convergence-map", "PATH", "Write a map of the generation each pixel first matched the target")
+                .num_args(0..=1)
+                .default_missing_value("convergence_map.png"),
+            value("comparison", "PATH", "Write the target and the result side by side")
+                .num_args(0..=1)
+                .default_missing_value("comparison.png"),
+            flag("comparison-divider", "Separate the comparison halves with a gray divider"),
+            flag("comparison-labels", "Write the final RMSE and SSIM under the comparison"),
+            value("fitness-plot", "PATH", "Chart the average fitness per generation as a PNG")
+                .num_args(0..=1)
+                .default_missing_value("fitness_plot.png"),
+            flag("fitness-plot-band", "Shade the lowest to highest pixel fitness behind the fitness plot"),
+            value("quality-report", "PATH", "Print and save the result's RMSE, PSNR, CIEDE2000 and SSIM whatever the fitness metric")
+                .num_args(0..=1)
+                .default_missing_value("quality.json"),
+            value("recolor-palette", "RRGGBB,...", "Recolor the result with the nearest of these colors").value_parser(parse_colors),
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .action(ArgAction::Count)
+                .help("Print more detail; repeat (-vv) for per-generation statistics"),
+            flag("quiet", "Only print warnings and errors").short('q').conflicts_with("verbose"),
+        ])
+        .next_help_heading("Animation")
+        .args([
+            choice("video", "Animation format", &[("gif", VideoFormat::Gif), ("apng", VideoFormat::Apng), ("mp4", VideoFormat::Mp4), ("webm", VideoFormat::WebM)]),
+            value("gif-scale", "N", "Upscale animation frames by this factor").value_parser(positive::<u32>),
+            flag("gif-counter", "Draw the generation counter on each frame"),
+            value("gif-counter-color", "RRGGBB", "Counter color, or auto").value_parser(parse_counter_color),
+            value("gif-colors", "N", "Colors per GIF frame").value_parser(count_in(2usize..=256)),
+            flag("dither", "Floyd-Steinberg dither GIF frames onto the palette"),
+            value("frame-delay", "CS", "Delay between frames in centiseconds").value_parser(positive::<u16>),
+            value("loop-count", "N", "GIF repetitions, or infinite").value_parser(parse_loop_count),
+            value("max-frames", "N", "Most frames any animation keeps").value_parser(positive::<usize>),
+            value("interpolate", "N", "Blended frames between each pair of animation frames").value_parser(clap::value_parser!(usize)),
+            value("frame-stride", "N", "Keep only every Nth generation's frame in memory").value_parser(positive::<usize>),
+        ])
+        .next_help_heading("Target")
+        .args([
+            value("size", "WxH", "Grid size in pixels").value_parser(parse_size),
+            choice("aspect", "How to fit the target to the grid", &[
+                ("stretch", AspectMode::Stretch),
+                ("letterbox", AspectMode::Letterbox),
+                ("fit", AspectMode::Fit),
+            ]),
+            choice("resize-filter", "Filter used to resize the target to the grid", &[
+                ("nearest", FilterType::Nearest),
+                ("triangle", FilterType::Triangle),
+                ("catmull-rom", FilterType::CatmullRom),
+                ("gaussian", FilterType::Gaussian),
+                ("lanczos3", FilterType::Lanczos3),
+            ]),
+            flag("native-size", "Size the grid to the target image instead of resizing it"),
+            value("max-pixels", "N", "Refuse grids of more pixels than this unless --force is given [default: 1000000]")
+                .value_parser(positive::<usize>),
+            flag("force", "Run grids over the --max-pixels budget"),
+            value("quantize-target", "LEVELS", "Quantize each target channel to this many levels").value_parser(count_in(2u32..=256)),
+            value("blend-hsv", "WEIGHT", "Also evolve in HSV and blend it in with this weight").value_parser(fraction),
+            value("block-smoothing", "STRENGTH", "Blend rendered blocks toward their neighbors' colors (output only)")
+                .value_parser(fraction),
+            flag("rgba", "Evolve an alpha channel too"),
+            flag("grayscale", "Evolve a single luminance channel per pixel"),
+            flag("auto-mode", "Evolve grayscale source images in grayscale mode, unless a color mode is given"),
+            flag("16bit", "Evolve 16 bits per channel and save a 16-bit result PNG"),
+            value("palette", "pico8|RRGGBB,...", "Restrict every pixel to these colors").value_parser(parse_palette),
+        ])
+        .next_help_heading("Genetic algorithm")
+        .args([
+            choice("preset", "Starting population, generations and schedules; other flags override them", &[
+                ("fast", GaConfig::fast as fn() -> GaConfig),
+                ("balanced", GaConfig::balanced),
+                ("high-quality", GaConfig::high_quality),
+            ]),
+            value("generations", "N", "Generations to run").value_parser(positive::<usize>),
+            value("pyramid-levels", "N", "Evolve coarse-to-fine over N resolutions, halving each level down").value_parser(positive::<usize>),
+            value("population", "N|adaptive:MIN:MAX", "Chromosomes in each island's pool, or MIN to MAX by the target's local contrast")
+                .value_parser(parse_population),
+            value("mutation-rate", "P", "Per-bit mutation probability").value_parser(fraction),
+            value("crossover-rate", "P", "Chance two parents are crossed over").value_parser(fraction),
+            value("crossover-schedule", "SCHEDULE", "constant, linear:<start>:<end> or exp:<start>:<end>")
+                .value_parser(parse_crossover_schedule),
+            value("channel-mutation-rates", "R,G,B", "Per-bit mutation probability of each color channel").value_parser(parse_channel_rates),
+            value("channel-crossover-rates", "R,G,B", "Chance each color channel is crossed over").value_parser(parse_channel_rates),
+            value("spatial-crossover-rate", "P", "Chance of breeding with a neighboring pixel's best").value_parser(fraction),
+            value("centroid-rate", "P", "Chance an offspring is the average color of its island's elites").value_parser(fraction),
+            value("seed", "N", "Seed for a reproducible run").value_parser(clap::value_parser!(u64)),
+            value("threads", "N", "Worker threads for stepping pixels (default: one per core)").value_parser(positive::<usize>),
+            flag("sequential", "Step pixels one at a time on the main thread, for debugging").conflicts_with("threads"),
+            value("trials", "N", "Evolve N trials seeded from --seed in parallel and keep the best")
+                .value_parser(positive::<usize>)
+                .conflicts_with_all(["checkpoint", "batch"]),
+            value("init", "STRATEGY", "random, target[:noise], blur[:sigma[:noise]], image:<path>[:noise] or solid:<RRGGBB>")
+                .value_parser(parse_init_strategy),
+            value("warm-start", "PATH", "Refine a previous result image instead of starting from --init")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["init", "pyramid-levels"]),
+            choice("algorithm", "Search algorithm", &[("ga", Algorithm::Ga), ("hillclimb", Algorithm::HillClimb)]),
+            value("fitness", "METRIC", "Color difference metric: rgb, ciede2000 or weighted:<r>:<g>:<b>").value_parser(parse_fitness),
+            value("gradient-weight", "W", "Weight of the neighbor gradient term").value_parser(non_negative),
+            value("fitness-temperature", "T", "Divisor of the RGB error in exp(-rmse / T)").value_parser(
+                |value: &str| match value.parse::<f64>() {
+                    Ok(t) if t > 0.0 && t.is_finite() => Ok(t),
+                    _ => Err("expected a positive number".to_string()),
+                },
+            ),
+            value("exact-match-bonus", "X", "Score multiplier for exact color matches").value_parser(non_negative),
+            value("exact-match-threshold", "RMSE", "RGB error below which a color counts as an exact match").value_parser(non_negative),
+            flag("no-exact-match-bonus", "Score exact matches on the same continuous curve as near ones")
+                .conflicts_with_all(["exact-match-bonus", "exact-match-threshold"]),
+            choice("selection", "Parent selection", &[
+                ("tournament", SelectionMethod::Tournament),
+                ("roulette", SelectionMethod::RouletteWheel),
+                ("rank", SelectionMethod::RankBased),
+            ]),
+            value("selection-pressure", "P", "Chance the fittest tournament candidate wins, else the next, and so on")
+                .value_parser(fraction),
+            choice("crossover", "Crossover operator", &[
+                ("uniform", CrossoverKind::Uniform),
+                ("single-point", CrossoverKind::SinglePoint),
+                ("two-point", CrossoverKind::TwoPoint),
+            ]),
+            value("mutation", "OPERATOR", "bitflip, fixed:<n> or creep:<sigma>").value_parser(parse_mutation),
+            value("mutation-schedule", "SCHEDULE", "constant, linear:<start>:<end> or exp:<start>:<end>")
+                .value_parser(parse_mutation_schedule),
+            value("extra-flip-rate", "P", "Chance of one extra forced bit flip").value_parser(fraction),
+            choice("encoding", "Gene encoding", &[("binary", GeneEncoding::Binary), ("gray", GeneEncoding::GrayCode)]),
+            choice("encoding-space", "Quantities the genes hold", &[("rgb", EncodingSpace::Rgb), ("hsv", EncodingSpace::Hsv)]),
+            value("block-size", "N", "Side of the square block one chromosome covers").value_parser(positive::<usize>),
+            value("islands", "N", "Independent subpopulations per pixel").value_parser(positive::<usize>),
+            value("migration-interval", "N", "Generations between island migrations").value_parser(positive::<usize>),
+        ])
+        .next_help_heading("Stopping and restarts")
+        .args([
+            value("max-evaluations", "N", "Stop after this many fitness evaluations").value_parser(positive::<u64>),
+            value("patience", "N", "Stop after N generations without improvement").value_parser(positive::<usize>),
+            flag("freeze-converged", "Stop stepping pixels once they match the target exactly"),
+            value("max-cell-generations", "N", "Give the generations of frozen pixels to the rest, up to N per pixel")
+                .value_parser(positive::<usize>),
+            value("min-delta", "D", "Smallest average fitness gain that counts as improvement").value_parser(non_negative),
+            value("immigrant-fraction", "P", "Share of non-elites replaced with random chromosomes on the first plateau, instead of stopping")
+                .value_parser(fraction),
+            value("stop-when-settled", "PIXELS:N", "Stop once at most PIXELS pixels changed over N generations")
+                .value_parser(parse_settle),
+            value("diversity-threshold", "BITS", "Restart cells whose diversity stays below this").value_parser(
+                |value: &str| match value.parse::<f64>() {
+                    Ok(t) if t > 0.0 && t.is_finite() => Ok(t),
+                    _ => Err("expected a positive number of bits".to_string()),
+                },
+            ),
+            value("diversity-patience", "N", "Generations below the threshold before a restart").value_parser(positive::<usize>),
+        ])
 }
 
-fn create_simple_gif_from_frames(frames: &[RgbImage], output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let file = File::create(output_path)?;
-    
-    let mut palette = Vec::new();
-    for r in 0..6 {
-        for g in 0..6 {
-            for b in 0..6 {
-                palette.push((r * 51) as u8);
-                palette.push((g * 51) as u8);
-                palette.push((b * 51) as u8);
-            }
-        }
-    }
-    
-    while palette.len() < 768 {
-        palette.push(0);
+fn parse_args(matches: &ArgMatches) -> (GaConfig, Options) {
+    let mut config = matches.get_one::<fn() -> GaConfig>("preset").map_or_else(GaConfig::default, |preset| preset());
+    let mut options = Options::default();
+
+    macro_rules! set {
+        ($target:expr, $name:literal) => {
+            $target = matches.get_one($name).cloned().unwrap_or($target)
+        };
+        ($target:expr, $name:literal, Some) => {
+            $target = matches.get_one($name).cloned().or($target)
+        };
     }
 
-    let mut encoder = gif::Encoder::new(BufWriter::new(file), IMG_SIZE as u16, IMG_SIZE as u16, &palette)?;
-    encoder.set_repeat(gif::Repeat::Infinite)?;
+    set!(options.input, "input", Some);
+    set!(options.sample_pattern, "sample");
+    set!(options.output, "output");
+    set!(options.animation_output, "gif", Some);
+    options.save_png = !matches.get_flag("no-png");
+    options.save_gif = !matches.get_flag("no-gif");
+    options.save_target = !matches.get_flag("no-target");
+    set!(options.target_sequence, "target-sequence", Some);
+    options.target_loop = matches.get_flag("target-loop");
+    set!(options.roi_mask, "roi-mask", Some);
+    options.dry_run = matches.get_flag("dry-run");
+    options.determinism_check = matches.get_flag("determinism-check");
+    options.compare_crossover = matches.get_flag("compare-crossover");
+    set!(options.trials, "trials");
+    options.auto_mode = matches.get_flag("auto-mode");
+    set!(options.max_pixels, "max-pixels");
+    options.force = matches.get_flag("force");
+    if let Some(mut dirs) = matches.get_many::<String>("batch") {
+        options.batch = Some((dirs.next().unwrap().clone(), dirs.next().unwrap().clone()));
+    }
+    set!(options.checkpoint, "checkpoint", Some);
+    set!(options.snapshot_interval, "snapshot-interval", Some);
+    options.sidecar = matches.get_flag("sidecar");
+    set!(options.manifest, "manifest", Some);
+    set!(options.stats_csv, "stats-csv", Some);
+    set!(options.output_html, "output-html", Some);
+    set!(options.sprite_sheet, "sprite-sheet", Some);
+    options.sprite_sheet_labels = matches.get_flag("sprite-sheet-labels");
+    set!(options.diff_gif, "diff-gif", Some);
+    set!(options.fitness_map, "fitness-map", Some);
+    set!(options.convergence_map, "convergence-map", Some);
+    set!(options.comparison, "comparison", Some);
+    options.comparison_divider = matches.get_flag("comparison-divider");
+    options.comparison_labels = matches.get_flag("comparison-labels");
+    set!(options.fitness_plot, "fitness-plot", Some);
+    options.fitness_plot_band = matches.get_flag("fitness-plot-band");
+    set!(options.quality_report, "quality-report", Some);
+    set!(options.recolor_palette, "recolor-palette", Some);
+
+    set!(options.video, "video");
+    set!(options.gif_scale, "gif-scale");
+    if matches.get_flag("gif-counter") {
+        options.gif_counter = Some(LabelColor::Auto);
+    }
+    set!(options.gif_counter, "gif-counter-color", Some);
+    set!(options.gif_colors, "gif-colors");
+    options.dither = matches.get_flag("dither");
+    set!(options.frame_delay, "frame-delay");
+    set!(options.loop_count, "loop-count");
+    set!(options.max_frames, "max-frames");
+    set!(options.interpolation_steps, "interpolate");
+    set!(options.frame_stride, "frame-stride");
+
+    set!((config.width, config.height), "size");
+    set!(config.aspect, "aspect");
+    set!(config.resize_filter, "resize-filter");
+    config.keep_native_size = matches.get_flag("native-size");
+    set!(options.quantize_target, "quantize-target", Some);
+    set!(options.blend_hsv, "blend-hsv", Some);
+    set!(options.block_smoothing, "block-smoothing", Some);
+    config.alpha = matches.get_flag("rgba");
+    config.grayscale = matches.get_flag("grayscale");
+    config.deep_color = matches.get_flag("16bit");
+    if let Some(palette) = matches.get_one::<Vec<[u8; 3]>>("palette") {
+        config.palette = Some(palette.iter().copied().map(Rgb).collect());
+    }
 
-    let step = if frames.len() > 50 { frames.len() / 50 } else { 1 };
+    // A preset's per-pixel cap keeps its ratio to the generations they override.
+    if let (Some(&generations), None, Some(cap)) =
+        (matches.get_one::<usize>("generations"), matches.get_one::<usize>("max-cell-generations"), config.max_cell_generations)
+    {
+        config.max_cell_generations = Some((cap * generations).div_ceil(config.iterations.max(1)));
+    }
+    set!(config.iterations, "generations");
+    set!(config.pyramid_levels, "pyramid-levels");
+    set!(config.population, "population");
+    set!(config.mutation_rate, "mutation-rate");
+    set!(config.crossover_rate, "crossover-rate");
+    set!(config.crossover_schedule, "crossover-schedule");
+    set!(config.channel_mutation_rates, "channel-mutation-rates", Some);
+    set!(config.channel_crossover_rates, "channel-crossover-rates", Some);
+    set!(config.spatial_crossover_rate, "spatial-crossover-rate");
+    set!(config.centroid_rate, "centroid-rate");
+    set!(config.seed, "seed", Some);
+    set!(config.threads, "threads", Some);
+    if matches.get_flag("sequential") {
+        config.threads = Some(1);
+    }
+    set!(config.init, "init");
+    set!(config.warm_start, "warm-start", Some);
+    set!(config.algorithm, "algorithm");
+    set!(config.fitness, "fitness");
+    set!(config.fitness_temperature, "fitness-temperature");
+    set!(config.exact_match_bonus, "exact-match-bonus");
+    set!(config.exact_match_threshold, "exact-match-threshold");
+    if matches.get_flag("no-exact-match-bonus") {
+        config.exact_match_bonus = 1.0;
+    }
+    set!(config.gradient_weight, "gradient-weight");
+    set!(config.selection, "selection");
+    set!(config.selection_pressure, "selection-pressure");
+    set!(config.crossover, "crossover");
+    set!(config.mutation, "mutation");
+    set!(config.mutation_schedule, "mutation-schedule");
+    set!(config.extra_flip_rate, "extra-flip-rate");
+    set!(config.encoding, "encoding");
+    set!(config.encoding_space, "encoding-space");
+    set!(config.block_size, "block-size");
+    set!(config.islands, "islands");
+    set!(config.migration_interval, "migration-interval");
+
+    set!(options.max_evaluations, "max-evaluations", Some);
+    set!(config.patience, "patience", Some);
+    config.freeze_converged |= matches.get_flag("freeze-converged");
+    set!(config.max_cell_generations, "max-cell-generations", Some);
+    set!(config.min_delta, "min-delta");
+    set!(config.immigrant_fraction, "immigrant-fraction");
+    set!(options.settle, "stop-when-settled", Some);
+    set!(config.diversity_threshold, "diversity-threshold", Some);
+    set!(config.diversity_patience, "diversity-patience");
+
+    (config, options)
+}
 
-    for (i, frame) in frames.iter().enumerate() {
-        if i % step != 0 {
-            continue;
-        }
+// The first Ctrl-C lets the run finish its generation and save; a second
+// one kills the process as usual.
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+    }
+    unsafe { libc::signal(libc::SIGINT, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t) };
+}
 
-        let mut indices = Vec::new();
-        
-        for pixel in frame.pixels() {
-            let r = ((pixel[0] as f32 / 51.0).round() as usize).min(5);
-            let g = ((pixel[1] as f32 / 51.0).round() as usize).min(5);
-            let b = ((pixel[2] as f32 / 51.0).round() as usize).min(5);
-            
-            let index = r * 36 + g * 6 + b;
-            indices.push(index as u8);
-        }
+#[cfg(not(unix))]
+fn install_interrupt_handler() {}
 
-        let mut gif_frame = gif::Frame::from_indexed_pixels(IMG_SIZE as u16, IMG_SIZE as u16, indices, None);
-        gif_frame.delay = 20;
-        encoder.write_frame(&gif_frame)?;
+// --quiet and --verbose take precedence over RUST_LOG.
+fn log_level(matches: &ArgMatches) -> Level {
+    match matches.get_count("verbose") {
+        _ if matches.get_flag("quiet") => Level::Warn,
+        0 => level_from_env(Level::Info),
+        1 => Level::Debug,
+        _ => Level::Trace,
     }
-
-    Ok(())
 }
 
-fn run_ga_with_output() {
-    let target_image = match load_target_image("target.png") {
-        Ok(img) => {
-            println!("Target image loaded successfully");
-            img
-        }
-        Err(_) => {
-            println!("Could not load target.png, using generated sample image");
-            create_sample_image()
-        }
+fn main() {
+    let matches = cli().get_matches();
+    set_max_level(log_level(&matches));
+    let (config, options) = parse_args(&matches);
+    install_interrupt_handler();
+
+    let result = match &options.batch {
+        Some((input, output)) => run_batch(input, output, &config, options.pixel_budget(), options.max_evaluations).map(|_| ()),
+        None => run_ga_with_output(&config, &options),
     };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
 
-    let mut ga_grid: Vec<Vec<SimpleGA>> = (0..IMG_SIZE)
-        .map(|i| {
-            (0..IMG_SIZE)
-                .map(|j| SimpleGA::new((i, j)))
-                .collect()
-        })
-        .collect();
-
-    let mut frames = Vec::new();
-
-    for gen in 0..ITERATION {
-        println!("Generation {}/{}", gen + 1, ITERATION);
-
-        for i in 0..IMG_SIZE {
-            for j in 0..IMG_SIZE {
-                ga_grid[i][j].step(&target_image);
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let mut frame = RgbImage::new(IMG_SIZE as u32, IMG_SIZE as u32);
-        let mut total_fitness = 0.0;
-        let mut perfect_matches = 0;
-        
-        for i in 0..IMG_SIZE {
-            for j in 0..IMG_SIZE {
-                let best = ga_grid[i][j].get_best(&target_image);
-                let val = best.get_val();
-                frame.put_pixel(j as u32, i as u32, Rgb([val[0], val[1], val[2]]));
-                
-                let fitness = best.get_fitness(&target_image);
-                total_fitness += fitness;
-                
-                let target_pixel = target_image.get_pixel(j as u32, i as u32);
-                if val[0] == target_pixel[0] && val[1] == target_pixel[1] && val[2] == target_pixel[2] {
-                    perfect_matches += 1;
-                }
-            }
-        }
-        
-        if gen % 25 == 0 || gen == ITERATION - 1 {
-            let avg_fitness = total_fitness / (IMG_SIZE * IMG_SIZE) as f64;
-            let match_percent = (perfect_matches as f64 / (IMG_SIZE * IMG_SIZE) as f64) * 100.0;
-            println!("  Average fitness: {:.4}, Perfect matches: {:.2}% ({}/{})", 
-                     avg_fitness, match_percent, perfect_matches, IMG_SIZE * IMG_SIZE);
-            
-            let (avg_fit, max_fit, min_fit) = ga_grid[IMG_SIZE/2][IMG_SIZE/2].get_fitness_stats(&target_image);
-            println!("  Sample pixel fitness - Avg: {:.4}, Max: {:.4}, Min: {:.4}", avg_fit, max_fit, min_fit);
-        }
-        
-        frames.push(frame);
+    #[test]
+    fn cli_definition_is_consistent() {
+        cli().debug_assert();
     }
 
-    if let Some(final_frame) = frames.last() {
-        match final_frame.save("result.png") {
-            Ok(_) => println!("Result saved as result.png"),
-            Err(e) => println!("Failed to save result image: {}", e),
-        }
+    #[test]
+    fn run_flags_populate_config_and_paths() {
+        let matches = cli()
+            .try_get_matches_from([
+                "ga-image", "--input", "in.png", "--output", "out.png", "--gif", "anim.gif", "--generations", "12",
+                "--population", "9", "--mutation-rate", "0.1", "--crossover-rate", "0.5", "--seed", "3",
+            ])
+            .unwrap();
+        let (config, options) = parse_args(&matches);
+        assert_eq!((config.iterations, config.population, config.seed), (12, PopulationStrategy::Uniform(9), Some(3)));
+        assert_eq!((config.mutation_rate, config.crossover_rate), (0.1, 0.5));
+        assert_eq!(options.input.as_deref(), Some("in.png"));
+        assert_eq!(options.output, "out.png");
+        assert_eq!(options.animation_output.as_deref(), Some("anim.gif"));
+
+        assert!(cli().try_get_matches_from(["ga-image", "--mutation-rate", "1.5"]).is_err());
+        assert_eq!(parse_population("adaptive:4:12"), Ok(PopulationStrategy::Adaptive { min: 4, max: 12 }));
+        assert!(parse_population("adaptive:12:4").is_err());
+        assert_eq!(parse_fitness("weighted:0.3:0.6:0.1"), Ok(FitnessMetric::Weighted { r: 0.3, g: 0.6, b: 0.1 }));
+        assert!(parse_fitness("weighted:0:0:0").is_err());
+        assert_eq!(parse_sample_pattern("checkerboard"), Ok(SamplePattern::Checkerboard(10)));
+        assert_eq!(parse_sample_pattern("solid:ff8000"), Ok(SamplePattern::Solid([255, 128, 0])));
+        assert!(parse_sample_pattern("circles:0").is_err());
     }
 
-    match create_simple_gif_from_frames(&frames, "result.gif") {
-        Ok(_) => println!("GIF saved as result.gif"),
-        Err(e) => println!("Failed to create GIF: {}", e),
+    #[test]
+    fn preset_caps_follow_overridden_generations() {
+        let config = |args: &[&str]| parse_args(&cli().try_get_matches_from(args).unwrap()).0;
+        let longer = config(&["ga-image", "--preset", "high-quality", "--generations", "2000"]);
+        assert_eq!((longer.max_cell_generations, longer.freeze_converged), (Some(4000), true));
+        assert_eq!(longer.validate(), Ok(()));
+        let capped = config(&["ga-image", "--preset", "high-quality", "--generations", "2000", "--max-cell-generations", "2500"]);
+        assert_eq!(capped.max_cell_generations, Some(2500));
+        assert_eq!(config(&["ga-image", "--generations", "50"]).max_cell_generations, None);
     }
 
-    match target_image.save("target_sample.png") {
-        Ok(_) => println!("Target image saved as target_sample.png"),
-        Err(e) => println!("Failed to save target image: {}", e),
+    #[test]
+    fn verbosity_flags_select_the_log_level() {
+        let level = |args: &[&str]| log_level(&cli().try_get_matches_from(args).unwrap());
+        assert_eq!(level(&["ga-image", "-v"]), Level::Debug);
+        assert_eq!(level(&["ga-image", "-vv"]), Level::Trace);
+        assert_eq!(level(&["ga-image", "--quiet"]), Level::Warn);
     }
-
-    println!("GA process completed!");
 }
-
-fn main() {
-    run_ga_with_output();
-}
\ No newline at end of file
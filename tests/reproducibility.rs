@@ -0,0 +1,22 @@
+use ga_image::ga::config::GaConfig;
+use ga_image::ga::image::{create_sample_image, SamplePattern};
+use ga_image::ga::run::run_ga;
+
+// Golden result of 20 seeded generations on the 16x16 sample target. If a
+// change is meant to alter the evolution, regenerate it and say so in review.
+const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample_16x16_seed1.png");
+
+#[test]
+fn seeded_sample_run_is_reproducible_and_improves() {
+    let target = create_sample_image(SamplePattern::Gradient, 16, 16);
+    let config = GaConfig { width: 16, height: 16, iterations: 20, seed: Some(1), ..GaConfig::default() };
+
+    let mut fitness = Vec::new();
+    let result = run_ga(&target, &config, |_, _, avg_fitness| fitness.push(avg_fitness)).unwrap();
+
+    assert_eq!(fitness.len(), 20);
+    assert!(fitness[19] > fitness[0], "average fitness went from {} to {}", fitness[0], fitness[19]);
+
+    let golden = image::open(GOLDEN).unwrap().to_rgb8();
+    assert_eq!(result.as_raw(), golden.as_raw(), "result differs from {}", GOLDEN);
+}